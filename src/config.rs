@@ -0,0 +1,141 @@
+// Copyright 2026 Daniel Smith
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Default flags from a config file.
+//!
+//! `$GRABCHARS_CONFIG` names the file; otherwise `~/.grabcharsrc` is used if
+//! it exists.  Each `key = value` line becomes the equivalent command-line
+//! argument, and those arguments are parsed ahead of the real ones, so
+//! anything given on the command line overrides the file.  A flag without a
+//! value that is set here is switched off with `--no-<name>` (`--no-silent`).
+//!
+//! ```text
+//! # ~/.grabcharsrc
+//! highlight = b          # same as -Hb
+//! timeout-select = first # same as --timeout-select=first
+//! Z = 0                  # single letters are short flags: -Z0
+//! s = true               # true/false for flags without a value
+//! ```
+
+use std::process;
+
+/// Readable names for short flags.  Everything else is used as given:
+/// a single letter is a short flag, anything longer a long option.
+const ALIASES: &[(&str, char)] = &[
+    ("both", 'b'),
+    ("editing", 'E'),
+    ("esc-code", 'B'),
+    ("filter", 'F'),
+    ("flush", 'f'),
+    ("highlight", 'H'),
+    ("json", 'J'),
+    ("lower", 'L'),
+    ("return", 'r'),
+    ("silent", 's'),
+    ("timeout", 't'),
+    ("trailing-newline", 'Z'),
+    ("upper", 'U'),
+];
+
+/// Read the config file and return its settings as argv-style strings.
+/// A missing `~/.grabcharsrc` is not an error; a missing `$GRABCHARS_CONFIG`
+/// file is.  Setting `GRABCHARS_CONFIG` to the empty string disables both.
+pub fn load() -> Vec<String> {
+    let (path, required) = match std::env::var("GRABCHARS_CONFIG") {
+        Ok(p) if p.is_empty() => return Vec::new(),
+        Ok(p) => (p, true),
+        Err(_) => match std::env::var("HOME") {
+            Ok(home) if !home.is_empty() => (format!("{}/.grabcharsrc", home), false),
+            _ => return Vec::new(),
+        },
+    };
+
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(c) => c,
+        Err(e) => {
+            if required {
+                eprintln!("grabchars: cannot read config '{}': {}", path, e);
+                process::exit(255);
+            }
+            return Vec::new();
+        }
+    };
+
+    let mut args = Vec::new();
+    for (lineno, line) in contents.lines().enumerate() {
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            eprintln!("grabchars: {}:{}: expected 'key = value'", path, lineno + 1);
+            process::exit(255);
+        };
+        let key = key.trim();
+        let value = unquote(value.trim());
+        if key.is_empty() {
+            eprintln!("grabchars: {}:{}: missing key", path, lineno + 1);
+            process::exit(255);
+        }
+        if let Some(arg) = to_arg(key, value) {
+            args.push(arg);
+        }
+    }
+    args
+}
+
+/// Turn one setting into a command-line argument, or `None` for `= false`.
+fn to_arg(key: &str, value: &str) -> Option<String> {
+    let short = match ALIASES.iter().find(|(name, _)| *name == key) {
+        Some((_, c)) => Some(*c),
+        None if key.chars().count() == 1 => key.chars().next(),
+        None => None,
+    };
+    match value {
+        "true" | "yes" | "on" => Some(match short {
+            Some(c) => format!("-{}", c),
+            None => format!("--{}", key),
+        }),
+        "false" | "no" | "off" => None,
+        _ => Some(match short {
+            Some(c) => format!("-{}{}", c, value),
+            None => format!("--{}={}", key, value),
+        }),
+    }
+}
+
+/// Drop a `#` comment, leaving `#` inside quotes alone.
+fn strip_comment(line: &str) -> &str {
+    let mut quote: Option<char> = None;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '#') => return &line[..i],
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Strip one pair of matching surrounding quotes, so values can keep
+/// leading or trailing spaces.
+fn unquote(value: &str) -> &str {
+    for q in ['"', '\''] {
+        if value.len() >= 2 && value.starts_with(q) && value.ends_with(q) {
+            return &value[1..value.len() - 1];
+        }
+    }
+    value
+}
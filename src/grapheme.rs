@@ -0,0 +1,201 @@
+// Copyright 2026 Daniel Smith
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Grapheme clusters for `--graphemes`.
+//!
+//! A cluster is a base codepoint plus whatever continues it: combining
+//! marks, variation selectors, emoji skin-tone modifiers, tag characters,
+//! anything joined by ZWJ (U+200D), and the second of a regional-indicator
+//! (flag) pair.  This is the practical subset of UAX #29 that matters for
+//! typed input, kept table-free so grabchars stays at two dependencies.
+//!
+//! `Units` is what the line editor measures the buffer in: single bytes by
+//! default (the original behavior), clusters with `--graphemes`.
+
+const ZWJ: char = '\u{200D}';
+
+/// Decode the first UTF-8 codepoint of `buf`.  Returns the char and its
+/// length in bytes; an invalid or truncated sequence is one byte, `None`.
+pub fn decode(buf: &[u8]) -> (Option<char>, usize) {
+    let len = match buf.first() {
+        None => return (None, 0),
+        Some(&b) => utf8_len(b),
+    };
+    if len == 0 || buf.len() < len {
+        return (None, 1);
+    }
+    match std::str::from_utf8(&buf[..len]) {
+        Ok(s) => (s.chars().next(), len),
+        Err(_) => (None, 1),
+    }
+}
+
+/// Length of the UTF-8 sequence that starts with `lead`, or 0 if `lead`
+/// cannot start one.
+pub fn utf8_len(lead: u8) -> usize {
+    match lead {
+        0x00..=0x7F => 1,
+        0xC2..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF4 => 4,
+        _ => 0,
+    }
+}
+
+fn is_regional_indicator(c: char) -> bool {
+    ('\u{1F1E6}'..='\u{1F1FF}').contains(&c)
+}
+
+/// Codepoints that attach to whatever precedes them.
+fn is_extend(c: char) -> bool {
+    matches!(c,
+        '\u{0300}'..='\u{036F}'     // combining diacritical marks
+        | '\u{0483}'..='\u{0489}'   // Cyrillic combining marks
+        | '\u{0591}'..='\u{05BD}'   // Hebrew points
+        | '\u{064B}'..='\u{065F}'   // Arabic harakat
+        | '\u{0900}'..='\u{0903}'   // Devanagari signs
+        | '\u{093A}'..='\u{094F}'
+        | '\u{1AB0}'..='\u{1AFF}'   // combining marks extended
+        | '\u{1DC0}'..='\u{1DFF}'   // combining marks supplement
+        | '\u{200C}'                // ZWNJ
+        | ZWJ
+        | '\u{20D0}'..='\u{20FF}'   // combining marks for symbols (incl. keycap)
+        | '\u{302A}'..='\u{302F}'
+        | '\u{3099}'..='\u{309A}'   // kana voicing marks
+        | '\u{FE00}'..='\u{FE0F}'   // variation selectors
+        | '\u{FE20}'..='\u{FE2F}'   // combining half marks
+        | '\u{1F3FB}'..='\u{1F3FF}' // emoji skin-tone modifiers
+        | '\u{E0020}'..='\u{E007F}' // tags (subdivision flags)
+        | '\u{E0100}'..='\u{E01EF}' // variation selectors supplement
+    )
+}
+
+/// Whether `next` belongs to the cluster whose codepoints so far are `cluster`.
+pub fn continues(cluster: &[u8], next: char) -> bool {
+    let chars: Vec<char> = String::from_utf8_lossy(cluster).chars().collect();
+    let Some(&last) = chars.last() else {
+        return false;
+    };
+    if is_extend(next) || last == ZWJ {
+        return true;
+    }
+    // Flags are pairs of regional indicators: join an unpaired one
+    if is_regional_indicator(next) {
+        let trailing = chars.iter().rev().take_while(|c| is_regional_indicator(**c)).count();
+        return trailing % 2 == 1;
+    }
+    false
+}
+
+/// Byte length of the first cluster in `buf`.
+fn cluster_len(buf: &[u8]) -> usize {
+    let (_, mut end) = decode(buf);
+    while end < buf.len() {
+        match decode(&buf[end..]) {
+            (Some(c), n) if continues(&buf[..end], c) => end += n,
+            _ => break,
+        }
+    }
+    end
+}
+
+/// Byte offsets where clusters start, plus `buf.len()`.
+fn boundaries(buf: &[u8]) -> Vec<usize> {
+    let mut out = vec![0];
+    let mut pos = 0;
+    while pos < buf.len() {
+        pos += cluster_len(&buf[pos..]);
+        out.push(pos);
+    }
+    out
+}
+
+/// Terminal columns for one cluster: 2 for wide (CJK, emoji), else 1.
+fn cluster_width(cluster: &[u8]) -> usize {
+    if cluster.first().is_some_and(|&b| is_caret_control(b)) {
+        return 2;
+    }
+    let s = String::from_utf8_lossy(cluster);
+    let Some(base) = s.chars().next() else {
+        return 0;
+    };
+    let wide = matches!(base,
+        '\u{1100}'..='\u{115F}'
+        | '\u{2E80}'..='\u{303E}'
+        | '\u{3041}'..='\u{33FF}'
+        | '\u{3400}'..='\u{4DBF}'
+        | '\u{4E00}'..='\u{9FFF}'
+        | '\u{A000}'..='\u{A4CF}'
+        | '\u{AC00}'..='\u{D7A3}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{FE30}'..='\u{FE4F}'
+        | '\u{FF00}'..='\u{FF60}'
+        | '\u{FFE0}'..='\u{FFE6}'
+        | '\u{1F1E6}'..='\u{1F1FF}'
+        | '\u{1F300}'..='\u{1F64F}'
+        | '\u{1F900}'..='\u{1F9FF}'
+        | '\u{20000}'..='\u{3FFFD}'
+    );
+    // VS16 asks for emoji presentation, which terminals draw two wide
+    if wide || s.contains('\u{FE0F}') { 2 } else { 1 }
+}
+
+/// A control byte the editor draws in caret notation (`^[`), two columns
+/// wide.  Tab is not one: it is drawn as a single space.
+pub fn is_caret_control(b: u8) -> bool {
+    (b < 0x20 && b != b'\t') || b == 0x7F
+}
+
+/// The editor's unit of measure for the input buffer.
+#[derive(Clone, Copy)]
+pub struct Units {
+    pub graphemes: bool,
+}
+
+impl Units {
+    /// Start of the unit that ends at byte offset `pos`.
+    pub fn prev(self, buf: &[u8], pos: usize) -> usize {
+        if !self.graphemes {
+            return pos - 1;
+        }
+        boundaries(&buf[..pos]).into_iter().rev().nth(1).unwrap_or(0)
+    }
+
+    /// End of the unit that starts at byte offset `pos`.
+    pub fn next(self, buf: &[u8], pos: usize) -> usize {
+        if !self.graphemes {
+            return pos + 1;
+        }
+        pos + cluster_len(&buf[pos..])
+    }
+
+    /// Number of units in `buf`.
+    pub fn count(self, buf: &[u8]) -> usize {
+        if !self.graphemes {
+            return buf.len();
+        }
+        boundaries(buf).len() - 1
+    }
+
+    /// Terminal columns `buf` occupies.
+    pub fn width(self, buf: &[u8]) -> usize {
+        if !self.graphemes {
+            return buf.len() + buf.iter().filter(|&&b| is_caret_control(b)).count();
+        }
+        boundaries(buf)
+            .windows(2)
+            .map(|w| cluster_width(&buf[w[0]..w[1]]))
+            .sum()
+    }
+}
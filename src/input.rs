@@ -15,13 +15,77 @@
 //! Key input parsing: reading raw bytes, escape sequences, and logical keys.
 
 use std::io;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicI32, AtomicU32, Ordering};
+
+use crate::{grapheme, timer, TIMED_OUT};
+
+// Bytes read ahead while looking for the end of a grapheme cluster that
+// turned out to start the next key.  `read_byte` drains these first.
+static PENDING: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+
+// How long to wait for the next byte of an escape sequence (`--esc-delay`).
+// A lone ESC is Escape once this passes; a sequence that stalls partway is
+// dropped as Unknown instead of blocking.
+static ESC_DELAY_MS: AtomicI32 = AtomicI32::new(50);
+
+pub fn set_esc_delay(ms: i32) {
+    ESC_DELAY_MS.store(ms, Ordering::Relaxed);
+}
+
+// Control bytes read as plain characters rather than as their editing
+// key (`--disable-edit-keys`): bit n stands for byte n.
+static LITERAL_CONTROLS: AtomicU32 = AtomicU32::new(0);
+
+/// The editing commands bound to control bytes, by key name and by the
+/// Ctrl chord that sends them.
+const EDIT_CONTROLS: &[(&str, &str, u8)] = &[
+    ("home", "ctrl-a", 0x01),
+    ("left", "ctrl-b", 0x02),
+    ("ctrl-d", "ctrl-d", 0x04),
+    ("end", "ctrl-e", 0x05),
+    ("right", "ctrl-f", 0x06),
+    ("kill-to-end", "ctrl-k", 0x0B),
+    ("kill-to-start", "ctrl-u", 0x15),
+    ("literal-next", "ctrl-v", 0x16),
+    ("kill-word-back", "ctrl-w", 0x17),
+];
+
+/// `--disable-edit-keys`: turn off the control-byte bindings named in the
+/// comma-separated `spec` (`kill-to-start` or `ctrl-u`), so those bytes
+/// come through as characters.  The arrow and Home/End keys still work.
+/// Repeating the option adds to the keys already turned off.
+pub fn disable_edit_keys(spec: &str) -> Result<(), String> {
+    let mut mask = 0u32;
+    for item in spec.split(',').filter(|i| !i.is_empty()) {
+        let lower = item.to_ascii_lowercase();
+        match EDIT_CONTROLS.iter().find(|(name, chord, _)| *name == lower || *chord == lower) {
+            Some(&(_, _, b)) => mask |= 1 << b,
+            None => return Err(format!("unknown editing key '{}'", item)),
+        }
+    }
+    LITERAL_CONTROLS.fetch_or(mask, Ordering::Relaxed);
+    Ok(())
+}
+
+/// The next byte of an escape sequence, or `None` if it doesn't arrive
+/// within the escape delay (or the read is interrupted by the timeout).
+fn seq_byte(fd: i32) -> Option<u8> {
+    if !byte_available(fd, ESC_DELAY_MS.load(Ordering::Relaxed)) {
+        return None;
+    }
+    read_byte(fd).ok()
+}
 
 pub enum KeyInput {
     Char(u8),
     Backspace,
     Delete,
+    CtrlD,         // 0x04: Delete unless --ctrl-d says otherwise; loops map it on read
     Left,
     Right,
+    AltLeft,       // Alt-Left: the filter cursor in select-lr, where Left browses
+    AltRight,      // Alt-Right: likewise; elsewhere the same as Right
     Up,
     Down,
     Home,
@@ -31,22 +95,110 @@ pub enum KeyInput {
     KillToEnd,     // Ctrl-K: delete from cursor to end of line
     KillToStart,   // Ctrl-U: delete from start of line to cursor
     KillWordBack,  // Ctrl-W: delete word backward
+    KillWordForward, // Ctrl-Delete / Alt-Delete: delete word forward
+    Count(u8),     // Alt-digit (ESC digit): repeat count for the next key
+    Function(u8),  // F1-F12, unmodified; nothing binds them unless a key list names them
     Enter,
+    Paste(String), // bracketed paste: the text between ESC [200~ and ESC [201~
     Unknown,
 }
 
+/// Canonical name of a non-character key, as used in key specs.
+pub fn key_name(key: &KeyInput) -> Option<&'static str> {
+    match key {
+        KeyInput::Char(_) | KeyInput::Paste(_) | KeyInput::Unknown => None,
+        KeyInput::Backspace => Some("backspace"),
+        KeyInput::Delete => Some("delete"),
+        KeyInput::CtrlD => Some("ctrl-d"),
+        KeyInput::Left => Some("left"),
+        KeyInput::Right => Some("right"),
+        KeyInput::AltLeft => Some("alt-left"),
+        KeyInput::AltRight => Some("alt-right"),
+        KeyInput::Up => Some("up"),
+        KeyInput::Down => Some("down"),
+        KeyInput::Home => Some("home"),
+        KeyInput::End => Some("end"),
+        KeyInput::Tab => Some("tab"),
+        KeyInput::Escape => Some("escape"),
+        KeyInput::KillToEnd => Some("kill-to-end"),
+        KeyInput::KillToStart => Some("kill-to-start"),
+        KeyInput::KillWordBack => Some("kill-word-back"),
+        KeyInput::KillWordForward => Some("kill-word-forward"),
+        KeyInput::Count(_) => Some("alt-digit"),
+        KeyInput::Function(n) => F_NAMES.get(*n as usize - 1).copied(),
+        KeyInput::Enter => Some("enter"),
+    }
+}
+
+const F_NAMES: [&str; 12] = ["f1", "f2", "f3", "f4", "f5", "f6", "f7", "f8", "f9", "f10", "f11", "f12"];
+
+/// Every name `key_name` can return, for validating key specs.
+const KEY_NAMES: &[&str] = &[
+    "backspace", "delete", "ctrl-d", "left", "right", "alt-left", "alt-right", "up", "down", "home", "end", "tab",
+    "escape", "kill-to-end", "kill-to-start", "kill-word-back", "kill-word-forward", "alt-digit", "enter",
+    "f1", "f2", "f3", "f4", "f5", "f6", "f7", "f8", "f9", "f10", "f11", "f12",
+];
+
+/// A set of keys given on the command line as a comma-separated list of key
+/// names (`tab,up,down`) and single characters (`q`).  `space` and `comma`
+/// name those two characters.
+#[derive(Default)]
+pub struct KeySet {
+    names: Vec<&'static str>,
+    chars: Vec<u8>,
+}
+
+impl KeySet {
+    pub fn parse(spec: &str) -> Result<KeySet, String> {
+        let mut set = KeySet::default();
+        for item in spec.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let lower = item.to_lowercase();
+            if let Some(name) = KEY_NAMES.iter().find(|n| **n == lower) {
+                set.names.push(name);
+            } else if lower == "space" {
+                set.chars.push(b' ');
+            } else if lower == "comma" {
+                set.chars.push(b',');
+            } else if item.len() == 1 {
+                set.chars.push(item.as_bytes()[0]);
+            } else {
+                return Err(format!("unknown key '{}'", item));
+            }
+        }
+        Ok(set)
+    }
+
+    pub fn contains(&self, key: &KeyInput) -> bool {
+        match key {
+            KeyInput::Char(b) => self.chars.contains(b),
+            _ => key_name(key).is_some_and(|n| self.names.contains(&n)),
+        }
+    }
+}
+
 /// Read one logical key from stdin.  Handles escape sequences for arrows, etc.
+/// Each key read counts as activity for the `--idle` timer.
 pub fn read_key(fd: i32) -> Result<KeyInput, io::Error> {
+    let key = decode_key(fd)?;
+    timer::touch();
+    Ok(key)
+}
+
+fn decode_key(fd: i32) -> Result<KeyInput, io::Error> {
     let b = read_byte(fd)?;
+    if b < 0x20 && LITERAL_CONTROLS.load(Ordering::Relaxed) & (1 << b) != 0 {
+        return Ok(KeyInput::Char(b));
+    }
     match b {
         0x01 => Ok(KeyInput::Home),          // Ctrl-A
         0x02 => Ok(KeyInput::Left),          // Ctrl-B
-        0x04 => Ok(KeyInput::Delete),        // Ctrl-D
+        0x04 => Ok(KeyInput::CtrlD),         // Ctrl-D
         0x05 => Ok(KeyInput::End),           // Ctrl-E
         0x06 => Ok(KeyInput::Right),         // Ctrl-F
         0x0B => Ok(KeyInput::KillToEnd),     // Ctrl-K
         0x15 => Ok(KeyInput::KillToStart),   // Ctrl-U
         0x17 => Ok(KeyInput::KillWordBack),  // Ctrl-W
+        0x16 => Ok(KeyInput::Char(read_byte(fd)?)), // Ctrl-V: insert next byte literally
         0x09 => Ok(KeyInput::Tab),
         0x7F | 0x08 => Ok(KeyInput::Backspace),
         0x0A | 0x0D => Ok(KeyInput::Enter),
@@ -56,6 +208,9 @@ pub fn read_key(fd: i32) -> Result<KeyInput, io::Error> {
 }
 
 pub fn read_byte(fd: i32) -> Result<u8, io::Error> {
+    if let Some(b) = PENDING.lock().unwrap().pop() {
+        return Ok(b);
+    }
     let mut buf = [0u8; 1];
     let n = unsafe { libc::read(fd, buf.as_mut_ptr() as *mut libc::c_void, 1) };
     if n == 1 {
@@ -68,7 +223,10 @@ pub fn read_byte(fd: i32) -> Result<u8, io::Error> {
 }
 
 /// Check if a byte is available on the given fd within `timeout_ms` milliseconds.
-fn byte_available(fd: i32, timeout_ms: i32) -> bool {
+pub fn byte_available(fd: i32, timeout_ms: i32) -> bool {
+    if !PENDING.lock().unwrap().is_empty() {
+        return true;
+    }
     let mut pfd = libc::pollfd {
         fd,
         events: libc::POLLIN,
@@ -78,52 +236,357 @@ fn byte_available(fd: i32, timeout_ms: i32) -> bool {
     ret > 0 && (pfd.revents & libc::POLLIN) != 0
 }
 
+/// Push bytes back so the next `read_byte` calls return them in order.
+fn unread(bytes: &[u8]) {
+    let mut pending = PENDING.lock().unwrap();
+    pending.extend(bytes.iter().rev());
+}
+
+/// Read the rest of one UTF-8 codepoint whose first byte is `lead`.
+pub fn read_codepoint(fd: i32, lead: u8) -> Vec<u8> {
+    let mut bytes = vec![lead];
+    for _ in 1..grapheme::utf8_len(lead) {
+        match read_byte(fd) {
+            Ok(b) if b & 0xC0 == 0x80 => bytes.push(b),
+            Ok(b) => {
+                unread(&[b]);
+                break;
+            }
+            Err(_) => break,
+        }
+    }
+    bytes
+}
+
+/// `--graphemes`: read the whole grapheme cluster that starts with `lead`.
+/// Continuation codepoints arrive in the same burst as the base, so a
+/// short peek decides whether the cluster goes on; a byte that starts
+/// something else is pushed back for the next read.
+pub fn read_cluster(fd: i32, lead: u8) -> Vec<u8> {
+    let mut cluster = read_codepoint(fd, lead);
+    while byte_available(fd, 10) {
+        let next = match read_byte(fd) {
+            Ok(b) => read_codepoint(fd, b),
+            Err(_) => break,
+        };
+        match grapheme::decode(&next) {
+            (Some(c), _) if grapheme::continues(&cluster, c) => cluster.extend(next),
+            _ => {
+                unread(&next);
+                break;
+            }
+        }
+    }
+    cluster
+}
+
+/// Read a Device Status Report reply (`ESC [ row ; col R`) after the caller
+/// has sent `ESC [ 6n`.  Bytes before the reply (type-ahead) are skipped.
+/// Returns `None` if no complete reply arrives within `timeout_ms` per byte.
+pub fn read_cursor_report(fd: i32, timeout_ms: i32) -> Option<(u32, u32)> {
+    let next = || -> Option<u8> {
+        if !byte_available(fd, timeout_ms) {
+            return None;
+        }
+        read_byte(fd).ok()
+    };
+    'scan: loop {
+        if next()? != 0x1B {
+            continue;
+        }
+        if next()? != b'[' {
+            continue;
+        }
+        let mut nums = [0u32; 2];
+        let mut field = 0;
+        loop {
+            match next()? {
+                b @ b'0'..=b'9' => nums[field] = nums[field].saturating_mul(10).saturating_add((b - b'0') as u32),
+                b';' if field == 0 => field = 1,
+                b'R' if field == 1 => return Some((nums[0], nums[1])),
+                _ => continue 'scan,
+            }
+        }
+    }
+}
+
+/// Read a DCS reply (`ESC P ... ESC \`, or BEL-terminated) and return the
+/// bytes between the introducer and the terminator.  Bytes before the reply
+/// are skipped.  Returns `None` if nothing complete arrives within
+/// `timeout_ms` per byte.
+pub fn read_dcs_reply(fd: i32, timeout_ms: i32) -> Option<Vec<u8>> {
+    let next = || -> Option<u8> {
+        if !byte_available(fd, timeout_ms) {
+            return None;
+        }
+        read_byte(fd).ok()
+    };
+    loop {
+        if next()? == 0x1B && next()? == b'P' {
+            break;
+        }
+    }
+    let mut body = Vec::new();
+    loop {
+        match next()? {
+            0x07 => return Some(body),
+            0x1B => {
+                next()?; // the backslash of ST
+                return Some(body);
+            }
+            b => body.push(b),
+        }
+    }
+}
+
+/// `--query`: read the terminal's reply, up to and including `end`, or
+/// without one until no byte follows within the `--esc-delay`.  The first
+/// byte may take `timeout_ms`.  Returns `None` when nothing arrives, or
+/// when `end` is given and never does.
+pub fn read_reply(fd: i32, end: Option<u8>, timeout_ms: i32) -> Option<Vec<u8>> {
+    let mut reply = Vec::new();
+    let mut wait = timeout_ms;
+    while byte_available(fd, wait) {
+        let b = read_byte(fd).ok()?;
+        reply.push(b);
+        if end == Some(b) {
+            return Some(reply);
+        }
+        wait = ESC_DELAY_MS.load(Ordering::Relaxed);
+    }
+    (end.is_none() && !reply.is_empty()).then_some(reply)
+}
+
+/// Modifier keys held down with a key.  In CSI sequences the `;<m>`
+/// parameter carries them as `m - 1`, a bitset of the constants below.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct Modifiers(u8);
+
+impl Modifiers {
+    pub const NONE: Modifiers = Modifiers(0);
+    pub const SHIFT: Modifiers = Modifiers(1);
+    pub const ALT: Modifiers = Modifiers(2);
+    pub const CTRL: Modifiers = Modifiers(4);
+    pub const META: Modifiers = Modifiers(8);
+
+    fn from_param(m: u32) -> Modifiers {
+        Modifiers(m.saturating_sub(1).min(15) as u8)
+    }
+
+    pub fn has(self, m: Modifiers) -> bool {
+        self.0 & m.0 == m.0
+    }
+
+    fn with(self, m: Modifiers) -> Modifiers {
+        Modifiers(self.0 | m.0)
+    }
+
+    /// `Ctrl-Alt-` style prefix for a key name.
+    fn prefix(self) -> String {
+        let names = [(Modifiers::CTRL, "Ctrl-"), (Modifiers::ALT, "Alt-"), (Modifiers::SHIFT, "Shift-"), (Modifiers::META, "Meta-")];
+        names.iter().filter(|(m, _)| self.has(*m)).map(|(_, n)| *n).collect()
+    }
+}
+
+/// A CSI (`ESC [`) or SS3 (`ESC O`) sequence after its introducer: the
+/// first numeric parameter (0 if none), the modifiers from the second, and
+/// the final byte.
+struct Csi {
+    code: u32,
+    mods: Modifiers,
+    fin: u8,
+}
+
+fn read_csi(fd: i32) -> Option<Csi> {
+    let mut params = [0u32; 2];
+    let mut field = 0;
+    loop {
+        match seq_byte(fd)? {
+            b @ b'0'..=b'9' => params[field] = params[field].saturating_mul(10).saturating_add((b - b'0') as u32),
+            b';' => field = (field + 1).min(1),
+            fin @ 0x40..=0x7E => return Some(Csi { code: params[0], mods: Modifiers::from_param(params[1]), fin }),
+            _ => return None,
+        }
+    }
+}
+
+/// F1-F12 from `CSI n ~` (11-15, 17-21, 23, 24) or `P`-`S` (SS3 `ESC O P`,
+/// or CSI with modifiers), if `csi` is a function key.
+fn function_number(csi: &Csi) -> Option<u8> {
+    match (csi.fin, csi.code) {
+        (b'P'..=b'S', _) => Some(csi.fin - b'P' + 1),
+        (b'~', n @ 11..=15) => Some(n as u8 - 10),
+        (b'~', n @ 17..=21) => Some(n as u8 - 11),
+        (b'~', n @ 23..=24) => Some(n as u8 - 12),
+        _ => None,
+    }
+}
+
 fn parse_escape_seq(fd: i32) -> Result<KeyInput, io::Error> {
-    // Check if another byte follows ESC within 50ms; if not, it's a bare Escape
-    if !byte_available(fd, 50) {
+    // If nothing follows ESC within the escape delay, it's a bare Escape
+    let Some(b2) = seq_byte(fd) else {
         return Ok(KeyInput::Escape);
-    }
-    let b2 = match read_byte(fd) {
-        Ok(b) => b,
-        Err(_) => return Ok(KeyInput::Escape),
     };
-    if b2 != b'[' {
+    if b2.is_ascii_digit() {
+        return Ok(KeyInput::Count(b2 - b'0'));
+    }
+    // CSI, or SS3 (ESC O) for F1-F4 and application-mode arrows; either is
+    // read through its final byte so nothing is left in the stream
+    if b2 != b'[' && b2 != b'O' {
         return Ok(KeyInput::Unknown);
     }
-    let b3 = match read_byte(fd) {
-        Ok(b) => b,
-        Err(_) => return Ok(KeyInput::Unknown),
+    let Some(csi) = read_csi(fd) else {
+        return Ok(KeyInput::Unknown);
     };
-    match b3 {
-        b'A' => Ok(KeyInput::Up),
-        b'B' => Ok(KeyInput::Down),
-        b'C' => Ok(KeyInput::Right),
-        b'D' => Ok(KeyInput::Left),
-        b'H' => Ok(KeyInput::Home),
-        b'F' => Ok(KeyInput::End),
+    let plain = csi.mods == Modifiers::NONE;
+    if let Some(n) = function_number(&csi) {
+        return Ok(if plain { KeyInput::Function(n) } else { KeyInput::Unknown });
+    }
+    match (csi.fin, csi.code) {
+        (b'A', _) if plain => Ok(KeyInput::Up),
+        (b'B', _) if plain => Ok(KeyInput::Down),
+        (b'C', _) if plain => Ok(KeyInput::Right),
+        (b'D', _) if plain => Ok(KeyInput::Left),
+        // \x1b[1;3C and \x1b[1;3D
+        (b'C', _) if csi.mods == Modifiers::ALT => Ok(KeyInput::AltRight),
+        (b'D', _) if csi.mods == Modifiers::ALT => Ok(KeyInput::AltLeft),
+        (b'H', _) if plain => Ok(KeyInput::Home),
+        (b'F', _) if plain => Ok(KeyInput::End),
         // Sequences like \x1b[3~  \x1b[1~  \x1b[4~
-        b'1' | b'3' | b'4' => {
-            let b4 = match read_byte(fd) {
-                Ok(b) => b,
-                Err(_) => return Ok(KeyInput::Unknown),
-            };
-            if b4 == b'~' {
-                match b3 {
-                    b'3' => Ok(KeyInput::Delete),
-                    b'1' => Ok(KeyInput::Home),
-                    b'4' => Ok(KeyInput::End),
-                    _ => Ok(KeyInput::Unknown),
-                }
-            } else {
-                Ok(KeyInput::Unknown)
-            }
+        (b'~', 3) if plain => Ok(KeyInput::Delete),
+        (b'~', 1) if plain => Ok(KeyInput::Home),
+        (b'~', 4) if plain => Ok(KeyInput::End),
+        (b'~', 200) if plain => read_paste(fd),
+        // \x1b[3;5~ (Ctrl-Delete) and \x1b[3;3~ (Alt-Delete)
+        (b'~', 3) if csi.mods == Modifiers::CTRL || csi.mods == Modifiers::ALT => Ok(KeyInput::KillWordForward),
+        _ => Ok(KeyInput::Unknown),
+    }
+}
+
+// The most of one paste that is kept; the rest is read and dropped.
+const PASTE_MAX: usize = 64 * 1024;
+
+/// Bracketed paste: everything up to the `ESC [201~` that ends it.  A
+/// paste that stalls for the escape delay, or a timeout, ends it early,
+/// so a stray `ESC [200~` cannot hang the read.
+fn read_paste(fd: i32) -> Result<KeyInput, io::Error> {
+    const END: &[u8] = b"\x1b[201~";
+    let mut text = Vec::new();
+    let mut tail: Vec<u8> = Vec::new(); // the last bytes read, to spot END past the cap
+    while !TIMED_OUT.load(Ordering::Relaxed) {
+        let Some(b) = seq_byte(fd) else {
+            break;
+        };
+        tail.push(b);
+        if tail.len() > END.len() {
+            tail.remove(0);
         }
-        _ => {
-            // Consume trailing '~' for other CSI sequences like \x1b[5~ etc.
-            if b3.is_ascii_digit() {
-                let _ = read_byte(fd); // consume ~
-            }
-            Ok(KeyInput::Unknown)
+        if tail == END {
+            // The rest of END went in before its last byte; past the cap,
+            // what is left over is dropped with it
+            text.truncate(text.len() - (END.len() - 1));
+            break;
+        }
+        if text.len() < PASTE_MAX + END.len() {
+            text.push(b);
         }
     }
+    text.truncate(PASTE_MAX);
+    Ok(KeyInput::Paste(String::from_utf8_lossy(&text).into_owned()))
+}
+
+/// The bytes of a paste that can be typed.  Control characters are
+/// dropped, so a paste can neither press Enter nor start an escape sequence.
+pub fn paste_bytes(text: &str) -> Vec<u8> {
+    text.bytes().filter(|&b| b >= 0x20 && b != 0x7F).collect()
+}
+
+/// Queue pasted `text` to be read back as typed characters, for input
+/// that handles a paste key by key.
+pub fn type_paste(text: &str) {
+    unread(&paste_bytes(text));
+}
+
+/// `--key-names`: read one key and name it with its modifiers, e.g. `a`,
+/// `Ctrl-a`, `Alt-x`, `Shift-F5`, `Ctrl-Left`.  Unlike `read_key` nothing
+/// is mapped to an editing function, so every key gets its own name.
+pub fn read_key_name(fd: i32) -> Result<String, io::Error> {
+    let b = read_byte(fd)?;
+    timer::touch();
+    Ok(name_from(fd, b))
+}
+
+/// One key for `--codepoint`: a typed character, or the `--key-names`
+/// name of anything else.
+pub enum CodepointKey {
+    Char(char),
+    Named(String),
+}
+
+/// `--codepoint`: read one key.  Printable characters, including the whole
+/// of a multi-byte UTF-8 sequence, come back as the character; control
+/// keys and escape sequences by name.
+pub fn read_key_codepoint(fd: i32) -> Result<CodepointKey, io::Error> {
+    let b = read_byte(fd)?;
+    timer::touch();
+    if b < 0x20 || b == 0x7F {
+        return Ok(CodepointKey::Named(name_from(fd, b)));
+    }
+    Ok(match grapheme::decode(&read_codepoint(fd, b)) {
+        (Some(c), _) => CodepointKey::Char(c),
+        _ => CodepointKey::Named("Unknown".to_string()),
+    })
+}
+
+/// Name of the key whose first byte `b` has been read.
+fn name_from(fd: i32, b: u8) -> String {
+    if b != 0x1B {
+        return byte_name(fd, b);
+    }
+    // ESC followed by more: a CSI/SS3 sequence, or Alt plus a key
+    let Some(b2) = seq_byte(fd) else {
+        return "Escape".to_string();
+    };
+    if b2 != b'[' && b2 != b'O' {
+        return format!("{}{}", Modifiers::ALT.prefix(), byte_name(fd, b2));
+    }
+    let Some(csi) = read_csi(fd) else {
+        return "Unknown".to_string();
+    };
+    if let Some(n) = function_number(&csi) {
+        return format!("{}F{}", csi.mods.prefix(), n);
+    }
+    let base = match (csi.fin, csi.code) {
+        (b'A', _) => "Up",
+        (b'B', _) => "Down",
+        (b'C', _) => "Right",
+        (b'D', _) => "Left",
+        (b'H', _) | (b'~', 1 | 7) => "Home",
+        (b'F', _) | (b'~', 4 | 8) => "End",
+        (b'Z', _) => "Tab", // backtab, always shifted
+        (b'~', 2) => "Insert",
+        (b'~', 3) => "Delete",
+        (b'~', 5) => "PageUp",
+        (b'~', 6) => "PageDown",
+        _ => return "Unknown".to_string(),
+    };
+    let mods = if csi.fin == b'Z' { csi.mods.with(Modifiers::SHIFT) } else { csi.mods };
+    format!("{}{}", mods.prefix(), base)
+}
+
+/// Name of a key that arrives as the single byte `b` (plus the rest of its
+/// UTF-8 sequence for non-ASCII characters).
+fn byte_name(fd: i32, b: u8) -> String {
+    match b {
+        0x00 => "Ctrl-Space".to_string(),
+        0x09 => "Tab".to_string(),
+        0x0A | 0x0D => "Enter".to_string(),
+        0x08 | 0x7F => "Backspace".to_string(),
+        0x1B => "Escape".to_string(),
+        b' ' => "Space".to_string(),
+        0x01..=0x1A => format!("Ctrl-{}", (b + 0x60) as char),
+        0x1C..=0x1F => format!("Ctrl-{}", (b + 0x40) as char),
+        0x21..=0x7E => (b as char).to_string(),
+        _ => String::from_utf8_lossy(&read_codepoint(fd, b)).into_owned(),
+    }
 }
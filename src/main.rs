@@ -17,15 +17,19 @@
 //! A Rust port / update of my 1988 grabchars utility.
 
 use std::io::{self, Write};
-use std::os::unix::io::AsRawFd;
 use std::process;
 use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
 
+mod config;
+mod grapheme;
 mod input;
+mod json;
 mod mask;
 mod output;
+mod preview;
 mod select;
 mod term;
+mod timer;
 
 use input::KeyInput;
 use output::{CURSOR_LEFT, CURSOR_RIGHT, CLEAR_TO_EOL};
@@ -37,28 +41,95 @@ use output::{CURSOR_LEFT, CURSOR_RIGHT, CLEAR_TO_EOL};
 pub static TIMED_OUT: AtomicBool = AtomicBool::new(false);
 pub static EXIT_STAT: AtomicI32 = AtomicI32::new(-1);
 
+#[derive(Default)]
 pub enum HighlightStyle {
+    #[default]
     Reverse,
     Bracket,
     Arrow,
 }
 
-impl Default for HighlightStyle {
-    fn default() -> Self {
-        HighlightStyle::Reverse
-    }
-}
-
-pub enum FilterStyle {
+/// How select matches options against the filter (`-F`).  Matching and
+/// ranking live in select.rs (`MatchStrategy::matches`).
+#[derive(Default)]
+pub enum MatchStrategy {
+    #[default]
     Prefix,
     Fuzzy,
     Contains,
 }
 
-impl Default for FilterStyle {
-    fn default() -> Self {
-        FilterStyle::Prefix
-    }
+/// What select returns on timeout when no `-d` default applies.
+#[derive(Default, Clone, Copy, PartialEq)]
+pub enum TimeoutSelect {
+    #[default]
+    None,    // keep the plain timeout (exit 254, no value)
+    First,   // first option in the current match list
+    Current, // whichever match is highlighted
+}
+
+/// What Enter picks in select while the filter is empty (`--enter-empty`).
+#[derive(Default, Clone, Copy, PartialEq)]
+pub enum EnterEmpty {
+    #[default]
+    Current, // whichever match is highlighted (the -d option until moved)
+    First,   // first option in the match list, wherever the highlight is
+    Default, // the -d option; nothing happens without one
+    None,    // nothing: type a filter first
+}
+
+/// How `--codepoint` writes a character.
+#[derive(Clone, Copy, PartialEq)]
+pub enum CodepointStyle {
+    Decimal, // 233
+    Unicode, // U+00E9
+}
+
+/// What Ctrl-D does (`--ctrl-d`).
+#[derive(Default, Clone, Copy, PartialEq)]
+pub enum CtrlD {
+    #[default]
+    Delete, // delete the character under the cursor, like the Delete key
+    Submit, // end the read with what has been typed
+    Cancel, // on an empty input, Escape; otherwise delete, as in a shell
+}
+
+/// What Tab does in the main editor (`--tab`).
+#[derive(Default, Clone, Copy, PartialEq)]
+pub enum TabKey {
+    #[default]
+    Ignore,   // nothing (original behavior)
+    Insert,   // a literal tab, drawn as one space
+    Complete, // the rest of the -d default, when the input so far starts it
+    Submit,   // end the read, like Enter with -r
+}
+
+/// Where the `-Z` trailing newline goes (`--newline-to`).
+#[derive(Default, Clone, Copy, PartialEq)]
+pub enum NewlineChannel {
+    #[default]
+    Stderr, // always stderr, where the prompt and echo are (original behavior)
+    Value,  // the channel the value went to: stdout, stderr (-e), or both (-b)
+}
+
+/// What select writes to the data channel for the chosen option (`--print`).
+#[derive(Default, Clone, Copy, PartialEq)]
+pub enum PrintField {
+    #[default]
+    Value, // the option text
+    Index, // the 0-based original index
+    Both,  // INDEX<sep>VALUE
+}
+
+/// Display order of select options (`--sort`).  Exit codes and `--print=index`
+/// still refer to the position in the original list.
+#[derive(Default, Clone, Copy, PartialEq)]
+pub enum SelectSort {
+    #[default]
+    None,    // source order
+    Alpha,   // by text, case-sensitive
+    AlphaCi, // by text, ignoring case
+    Len,     // shortest first
 }
 
 #[derive(Default)]
@@ -75,10 +146,85 @@ pub struct Flags {
     pub lower: bool,
     pub upper: bool,
     pub trailing_newline: bool, // -Z: print trailing newline to stderr (default: true)
+    pub newline_channel: NewlineChannel, // --newline-to: stderr (default) or the value's channel
     pub highlight_style: HighlightStyle,
-    pub filter_style: FilterStyle,
+    pub match_strategy: MatchStrategy,
     pub esc_code: Option<i32>, // -B<n>: None = current behavior, Some(0) = no-op, Some(n) = exit n
+    pub on_cancel: Option<String>, // --on-cancel: string written as the value when Escape cancels
+    pub cancel_code: Option<i32>, // --cancel-code: exact Escape exit code (0 exits 0); overrides -B
+    pub timeout_code: i32, // --timeout-code: exit code on timeout (default 254)
     pub json: Option<JsonStyle>, // -J: None = off, Some(Compact), Some(Pretty)
+    pub timeout_select: TimeoutSelect, // --timeout-select: select's pick on timeout without -d
+    pub enter_empty: EnterEmpty, // --enter-empty: what Enter picks with an empty select filter
+    pub ctrl_d: CtrlD, // --ctrl-d: Ctrl-D deletes, submits, or cancels
+    pub tab: TabKey, // --tab: Tab is ignored, inserted, completes -d, or submits
+    pub idle_secs: u32, // --idle: seconds without a keystroke before timing out (0 = off)
+    pub empty_matches_none: bool, // --empty=none: select shows no matches until something is typed
+    pub key_names: bool, // --key-names: read one key and print its name with modifiers
+    pub codepoint: Option<CodepointStyle>, // --codepoint: read one key and print its codepoint
+    pub query_colors: bool, // --query-colors: print none|16|256|truecolor
+    pub cursor_pos: bool, // --cursor-pos: query the terminal and print "row col"
+    pub query: Option<String>, // --query: escape sequence to send; the reply is printed
+    pub query_end: Option<u8>, // --query-end: byte that ends the --query reply
+    pub reprompt_on_invalid: bool, // --reprompt-on-invalid: keep the buffer and resume editing when -V fails
+    pub print_field: PrintField, // --print: select output is value, index, or both
+    pub ignore_diacritics: bool, // --ignore-diacritics: select filter "cafe" matches "café"
+    pub sort: SelectSort, // --sort: display order of select options
+    pub ascii_only: bool, // --ascii-only: reject non-ASCII characters with a bell
+    pub timings: bool, // --timings[=<fd>]: report response time and keystroke count
+    pub timings_fd: Option<i32>, // --timings=<fd>: where the timings line goes
+    pub default_re: Option<regex::Regex>, // --default-re: highlight the first select option matching this
+    pub list_matches: Option<String>, // --list-matches: print the options this filter matches and exit
+    pub keep_widget: bool, // --keep-widget: leave "<filter> → <choice>" on screen after select
+    pub raw_backspace: u8, // --raw-backspace-byte: byte -E0 stores for Backspace (default 0x7F)
+    pub mask_values_only: bool, // --mask-values-only: output only what was typed, not the mask's literals
+    pub require_unique: bool, // --require-unique: select Enter only accepts a single remaining match
+    pub json_file: Option<String>, // --json-file: select options from a JSON array
+    pub initials: bool, // --initials: each select option gets a one-key hotkey, shown as (Q)uit
+    pub tty: bool, // --tty: read keys from /dev/tty even when stdin is a terminal
+    pub outcome_file: Option<String>, // --outcome-file: key=value summary of the result, written on exit
+    pub no_filter: bool, // --no-filter: select keys jump by first letter instead of filtering
+    pub range: Option<NumRange>, // --range: an integer between min and max
+    pub no_match_text: Option<String>, // --no-match-text: select's "(no matches)" replacement
+    pub no_match_color: bool, // --no-match-color: no-match line in red (off under NO_COLOR)
+    pub exit_map: Option<std::collections::HashMap<String, i32>>, // --exit-map: select exit code per chosen option
+    pub prompt: Option<(String, bool)>, // -p/-q: the prompt as printed, and whether it went to stderr
+    pub reprompt_text: Option<Option<String>>, // --reprompt-text: redraw a prompt on a new line before each retry (None inside = the -p/-q prompt)
+    pub max_n: usize, // --max-n: largest -n accepted
+    pub field_width: Option<(usize, char)>, // --field-width: draw the editor in a padded field of N columns
+    pub print_sep: String, // --print-sep: separator for --print=both (default tab)
+    pub ignore_keys: Option<input::KeySet>, // --ignore-keys: keys the editor drops silently
+    pub accept_keys: Option<input::KeySet>, // --accept-keys: select keys that confirm like Enter
+    pub strip: Option<String>, // --strip: characters trimmed from the end of the finished input
+    pub post: output::Post, // --post, --prefix, --suffix: transforms applied to the final value
+    pub strict: bool, // --strict: a mask the -c/-C/-U/-L filters make impossible is an error, not a warning
+    pub reveal_last: bool, // --reveal-last: with --echo-map, show the newest character briefly
+    pub echo_map: Option<output::EchoMap>, // --echo-map: display substitutes for typed characters
+    pub no_match_count: bool, // --no-trailing-match-count: drop the select count suffix and hint
+    pub position_indicator: bool, // --position-indicator: select-lr shows [I/N] instead of the match count
+    pub spinner: bool, // --spinner: activity indicator while a --preview command runs
+    pub watch_fd: Option<i32>, // --watch-fd: write the highlighted select option here on every change
+    pub timeout_dump: Option<i32>, // --timeout-dump: on a select timeout, list the matches here instead of picking
+    pub preview: Option<String>, // --preview: command run for the highlighted select option
+    pub tree: bool, // --tree: navigate options as paths, one level at a time
+    pub tree_sep: String, // --tree-sep: path separator for --tree (default "/")
+    pub case_sensitive: bool, // --case-sensitive: select filter matches case exactly
+    pub default_case_sensitive: bool, // --default-case-sensitive: -d must match an option's case exactly
+    pub collect: Option<(regex::Regex, usize)>, // --collect: read until this many keys from the set
+    pub collect_keep_others: bool, // --collect-others=keep: non-set keys go in the buffer uncounted
+    pub hex_output: bool, // --hex-output: write the buffer as space-separated hex bytes
+    pub no_auto_literal: bool, // --no-auto-literal: mask literals are typed, not auto-inserted
+    pub literal_color: Option<u8>, // --literal-color: 256-color index for auto-inserted mask literals (off under NO_COLOR)
+    pub edit_default: bool, // --edit-default: prefill the buffer with -d for editing
+    pub paragraph: bool,    // --paragraph: Enter starts a new line; an empty line ends the read
+    pub max_input_bytes: Option<usize>, // --max-input-bytes: hard cap on buffer/filter size
+    pub copy: bool, // --copy: also put the chosen select option on the clipboard (OSC 52)
+    pub echo_result: bool, // --echo-result: show the accepted value on stderr when done
+    pub beep_on_complete: bool, // --beep-on-complete: ring the bell when input is accepted
+    pub graphemes: bool, // --graphemes: a grapheme cluster is one character
+    pub dedup: bool, // --dedup: drop repeated select options, keeping the first
+    pub confirm_marker: Option<String>, // --confirm[=<marker>]: options starting with it need a second Enter
+    pub option_sep: Option<String>, // --sep: separator for the positional select list (None = auto)
 }
 
 #[derive(Clone, Copy)]
@@ -88,6 +234,23 @@ pub enum JsonStyle {
 }
 
 impl Flags {
+    /// `--reprompt-text`: what to redraw before a retry, and whether it goes
+    /// to stderr.  The default is the `-p`/`-q` prompt on its own stream; an
+    /// explicit text joins the editor on stderr.
+    fn reprompt(&self) -> Option<(&str, bool)> {
+        match self.reprompt_text {
+            Some(Some(ref text)) => Some((text, true)),
+            Some(None) => Some(self.prompt.as_ref().map_or(("", true), |(text, on_stderr)| (text.as_str(), *on_stderr))),
+            None => None,
+        }
+    }
+
+    /// Whether the result is reported beyond the plain value: as `-J`
+    /// JSON, or in an `--outcome-file`.
+    fn reports_outcome(&self) -> bool {
+        self.json.is_some() || self.outcome_file.is_some()
+    }
+
     fn new() -> Self {
         Flags {
             both: false,
@@ -102,16 +265,92 @@ impl Flags {
             lower: false,
             upper: false,
             trailing_newline: true,
+            newline_channel: NewlineChannel::Stderr,
             highlight_style: HighlightStyle::Reverse,
-            filter_style: FilterStyle::Prefix,
+            match_strategy: MatchStrategy::Prefix,
             esc_code: None,
+            on_cancel: None,
+            cancel_code: None,
+            timeout_code: 254,
             json: None,
+            timeout_select: TimeoutSelect::None,
+            enter_empty: EnterEmpty::Current,
+            ctrl_d: CtrlD::Delete,
+            tab: TabKey::Ignore,
+            idle_secs: 0,
+            empty_matches_none: false,
+            key_names: false,
+            codepoint: None,
+            query_colors: false,
+            cursor_pos: false,
+            query: None,
+            query_end: None,
+            reprompt_on_invalid: false,
+            print_field: PrintField::Value,
+            ignore_diacritics: false,
+            sort: SelectSort::None,
+            initials: false,
+            tty: false,
+            outcome_file: None,
+            no_filter: false,
+            range: None,
+            no_match_text: None,
+            no_match_color: false,
+            exit_map: None,
+            prompt: None,
+            reprompt_text: None,
+            max_n: DEFAULT_MAX_N,
+            field_width: None,
+            json_file: None,
+            require_unique: false,
+            mask_values_only: false,
+            raw_backspace: 0x7F,
+            keep_widget: false,
+            list_matches: None,
+            default_re: None,
+            timings: false,
+            ascii_only: false,
+            timings_fd: None,
+            print_sep: "\t".to_string(),
+            ignore_keys: None,
+            accept_keys: None,
+            strip: None,
+            post: output::Post::default(),
+            reveal_last: false,
+            strict: false,
+            echo_map: None,
+            no_match_count: false,
+            position_indicator: false,
+            spinner: false,
+            watch_fd: None,
+            timeout_dump: None,
+            preview: None,
+            tree: false,
+            tree_sep: "/".to_string(),
+            case_sensitive: false,
+            default_case_sensitive: false,
+            collect: None,
+            collect_keep_others: false,
+            hex_output: false,
+            no_auto_literal: false,
+            literal_color: None,
+            edit_default: false,
+            paragraph: false,
+            max_input_bytes: None,
+            copy: false,
+            echo_result: false,
+            beep_on_complete: false,
+            graphemes: false,
+            dedup: false,
+            confirm_marker: None,
+            option_sep: None,
         }
     }
 }
 
 /// Apply -c/-C include/exclude filters and -U/-L case mapping to a character.
 /// Returns `None` if the character is rejected, `Some(ch)` (possibly case-mapped) if accepted.
+#[allow(clippy::collapsible_if)]
 pub fn apply_char_filters(
     ch: char,
     flags: &Flags,
@@ -144,6 +383,222 @@ pub fn apply_char_filters(
     Some(ch)
 }
 
+/// Anchored single-character pattern for `-c`/`-C`.  A `[...]` set is used
+/// as given, a lone Unicode property (`\p{L}`, `\PN`) becomes its own class,
+/// and anything else is wrapped as the set `[...]`.
+fn char_class_pattern(val: &str) -> String {
+    let is_property = (val.starts_with("\\p") || val.starts_with("\\P"))
+        && match &val[2..] {
+            name if name.len() == 1 => name.chars().all(|c| c.is_ascii_alphabetic()),
+            name => name.starts_with('{') && name.ends_with('}') && name.len() > 2,
+        };
+    if is_property || (val.starts_with('[') && val.ends_with(']')) {
+        format!("^{}$", val)
+    } else {
+        format!("^[{}]$", val)
+    }
+}
+
+/// How long `--reveal-last` shows the newest character before masking it.
+const REVEAL_MS: i32 = 1000;
+
+/// Upper bound on an Alt-digit repeat count.
+const MAX_REPEAT: usize = 999;
+
+/// Largest `-n` accepted unless `--max-n` raises it.
+const DEFAULT_MAX_N: usize = 10000;
+
+/// The input buffer's starting size: enough for any typical read, without
+/// reserving a huge `-n` up front.
+const INITIAL_BUFFER: usize = 256;
+
+/// `--range`: an integer input between `min` and `max`, inclusive.
+#[derive(Clone, Copy)]
+pub struct NumRange {
+    min: i64,
+    max: i64,
+}
+
+impl NumRange {
+    /// Parse `<min>..<max>`.
+    fn parse(spec: &str) -> Result<NumRange, String> {
+        let Some((min, max)) = spec.split_once("..") else {
+            return Err(format!("expected <min>..<max>, got '{}'", spec));
+        };
+        let bound = |s: &str| s.trim().parse::<i64>().map_err(|_| format!("'{}' is not an integer", s));
+        let (min, max) = (bound(min)?, bound(max)?);
+        if min > max {
+            return Err(format!("{}..{} is backwards", min, max));
+        }
+        Ok(NumRange { min, max })
+    }
+
+    /// Characters enough for either bound, sign included.
+    fn width(self) -> usize {
+        self.min.to_string().len().max(self.max.to_string().len())
+    }
+
+    /// The buffer's value, if it is a whole integer (leading zeros allowed).
+    fn value(buf: &[u8]) -> Option<i64> {
+        std::str::from_utf8(buf).ok()?.parse::<i64>().ok()
+    }
+
+    /// Whether the finished buffer is a number in range.
+    fn contains(self, buf: &[u8]) -> bool {
+        Self::value(buf).is_some_and(|v| (self.min..=self.max).contains(&v))
+    }
+
+    /// Whether `buf`, as typed so far, is in range or can still get there
+    /// with more digits (at most `room` characters in all).  `5` is fine
+    /// for 10..99 since `50` is ahead; `5` for 1..4 is not.
+    fn allows_prefix(self, buf: &[u8], room: usize) -> bool {
+        let (negative, digits) = match buf.split_first() {
+            Some((b'-', rest)) => (true, rest),
+            _ => (false, buf),
+        };
+        if !digits.iter().all(u8::is_ascii_digit) || (negative && self.min >= 0) {
+            return false;
+        }
+        if digits.is_empty() {
+            return true; // nothing, or a lone '-' with negatives allowed
+        }
+        let Some(v) = Self::value(digits) else {
+            return false;
+        };
+        // Each further digit d turns v into v * 10 + d: after j more, the
+        // magnitude is somewhere in v * 10^j ..= v * 10^j + 10^j - 1
+        let mut scale: i64 = 1;
+        for _ in 0..=room.saturating_sub(buf.len()) {
+            let (Some(lo), Some(span)) = (v.checked_mul(scale), scale.checked_sub(1)) else {
+                break;
+            };
+            let hi = lo.saturating_add(span);
+            let (lo, hi) = if negative { (-hi, -lo) } else { (lo, hi) };
+            if lo <= self.max && hi >= self.min {
+                return true;
+            }
+            let Some(next) = scale.checked_mul(10) else {
+                break;
+            };
+            scale = next;
+        }
+        false
+    }
+}
+
+/// `--strip`: `buf` without its trailing run of characters from the set.
+fn strip_trailing<'a>(buf: &'a [u8], flags: &Flags) -> &'a [u8] {
+    let Some(ref set) = flags.strip else {
+        return buf;
+    };
+    match std::str::from_utf8(buf) {
+        Ok(text) => text.trim_end_matches(|c| set.contains(c)).as_bytes(),
+        // Not UTF-8 (e.g. a Ctrl-V byte): only ASCII members can match
+        Err(_) => {
+            let mut end = buf.len();
+            while end > 0 && buf[end - 1].is_ascii() && set.contains(buf[end - 1] as char) {
+                end -= 1;
+            }
+            &buf[..end]
+        }
+    }
+}
+
+/// Exit code for Escape, or `None` when Escape is a no-op.  `--cancel-code`
+/// wins; then `-B`, where `-B0` means no-op; otherwise the mode's
+/// `fallback` (no-op in normal mode, 255 in mask and select).
+pub fn escape_exit(flags: &Flags, fallback: Option<i32>) -> Option<i32> {
+    if flags.cancel_code.is_some() {
+        return flags.cancel_code;
+    }
+    match flags.esc_code {
+        Some(0) => None,
+        Some(n) => Some(n),
+        None => fallback,
+    }
+}
+
+/// `--max-input-bytes`: true (after ringing the bell) if adding `adding`
+/// bytes to a buffer of `len` bytes would exceed the cap.
+pub fn input_cap_reached(flags: &Flags, len: usize, adding: usize) -> bool {
+    match flags.max_input_bytes {
+        Some(max) if len + adding > max => {
+            output::alert();
+            true
+        }
+        _ => false,
+    }
+}
+
+/// `--ctrl-d`: the key Ctrl-D stands for, given whether anything has been
+/// typed yet, or `None` to end the read with what there is.
+pub fn ctrl_d_key(flags: &Flags, empty: bool) -> Option<KeyInput> {
+    match flags.ctrl_d {
+        CtrlD::Delete => Some(KeyInput::Delete),
+        CtrlD::Submit => None,
+        CtrlD::Cancel if empty => Some(KeyInput::Escape),
+        CtrlD::Cancel => Some(KeyInput::Delete),
+    }
+}
+
+/// `--ascii-only`: true (after ringing the bell) if `b` starts a non-ASCII
+/// character.  The rest of its UTF-8 sequence is read and dropped as well,
+/// so the whole codepoint is rejected rather than just its first byte.
+pub fn non_ascii_rejected(flags: &Flags, fd: i32, b: u8) -> bool {
+    if !flags.ascii_only || b.is_ascii() {
+        return false;
+    }
+    input::read_codepoint(fd, b);
+    output::alert();
+    true
+}
+
+/// `--graphemes` counterpart of `apply_char_filters`: the filters see the
+/// cluster's base character, and case mapping rewrites only that base.
+fn apply_cluster_filters(
+    cluster: Vec<u8>,
+    flags: &Flags,
+    valid_pattern: &Option<regex::Regex>,
+    exclude_pattern: &Option<regex::Regex>,
+) -> Option<Vec<u8>> {
+    let (base, len) = grapheme::decode(&cluster);
+    let base = base.unwrap_or(cluster[0] as char);
+    let mapped = apply_char_filters(base, flags, valid_pattern, exclude_pattern)?;
+    if mapped == base {
+        return Some(cluster);
+    }
+    let mut out = mapped.to_string().into_bytes();
+    out.extend_from_slice(&cluster[len..]);
+    Some(out)
+}
+
+/// Expand backslash escapes in an option value: `\t`, `\n`, `\r`, `\0`, `\e`
+/// (ESC), and `\\`.  Any other backslash sequence is kept as-is.
+pub fn unescape_arg(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('0') => out.push('\0'),
+            Some('e') => out.push('\x1b'),
+            Some('\\') => out.push('\\'),
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
 // ---------------------------------------------------------------------------
 // Usage
 // ---------------------------------------------------------------------------
@@ -157,20 +612,69 @@ fn print_usage() {
         "       -d<char(s)>          default char or string to return",
         "       -e                   output to stderr instead of stdout",
         "       -f                   flush any previous input before reading",
+        "       --tty                read keys from /dev/tty (automatic when stdin is not a terminal)",
         "       -h                   help screen",
         "       -J/-J1/-Jp/-J0        JSON output: compact/pretty/off (default: off)",
         "       -m<mask>             mask for positional input (U=upper l=lower c=alpha n=digit x=hex p=punct .=any)",
+        "       --no-auto-literal    with -m: type the mask's literals instead of having them inserted",
+        "       --literal-color=<n>  with -m: draw inserted literals in color <n> (0-255; not with NO_COLOR)",
+        "       --mask-values-only   with -m: output only the typed characters, without the literals",
+        "       --strict             with -m: exit 255 (not just warn) if -c/-C/-U/-L leave a mask element untypeable",
         "       -n<number>           number of characters to read",
         "       -p<prompt>           prompt to help user",
         "       -q<prompt>           prompt to help user (through stderr)",
         "       -r                   RETURN key exits (use with -n)",
         "       -R                   raw mode: capture bytes as-is (no escape parsing)",
         "       -s                   silent, just return status",
-        "       -t<seconds>          timeout after <seconds>",
+        "       --collect=<set>:<k>  read until <k> keys from <set> are seen (others don't count)",
+        "       --collect-others=<p> keys outside the --collect set: 'drop' (default) or 'keep' in the output",
+        "       --hex-output         write the input as hex bytes (e.g. '48 65 6c') instead of text",
+        "       --post=<ops>         transform the result in order: trim, upper, lower, title, quote",
+        "       --prefix=<str>       write <str> before the result (after --post; e.g. --prefix=--name=)",
+        "       --suffix=<str>       write <str> after the result, before the trailing newline",
+        "       -t<seconds>          timeout after <seconds> (fractions allowed: -t0.5)",
+        "       --idle=<seconds>     timeout after <seconds> without a keystroke (with -t: whichever is first)",
+        "       --timeout-code=<n>   exit code on timeout (default: 254)",
+        "       --cancel-code=<n>    exact exit code on Escape, 0-255 (overrides -B; 0 exits 0)",
+        "       --on-cancel=<str>    write <str> as the value when Escape cancels (e.g. __CANCELLED__)",
+        "       --esc-delay=<ms>     wait <ms> for the rest of an escape sequence (default: 50)",
+        "       --disable-edit-keys=<keys> Ctrl keys to read as characters, e.g. 'kill-to-start' or 'ctrl-u'",
         "       -B<n>                ESC exit code: 0=no-op, 1-253=exit n, 255=exit 255 (default: no-op in normal, 255 in mask/select)",
         "       -E/-E1/-E0            enable/disable line editing (default: on when -n > 1)",
+        "       --ignore-keys=<keys> keys to ignore while editing (e.g. tab,up,down,q)",
+        "       --strip[=<chars>]    trim trailing <chars> from the input (default: \\n\\r; needs editing)",
+        "       --echo-map=<rules>   show typed characters differently, e.g. '0-9:*' (needs editing)",
+        "       --reveal-last        with --echo-map, show the newest character as typed for a second",
+        "       --field-width=<n>[:<c>]  edit in a field of exactly n columns, padded with c (default '.')",
+        "       --range=<min>..<max> an integer in range: digits only, Enter ends (exit 255 if out of range)",
+        "       -V<regex>            whole input must match <regex> (exit 255 if not; needs editing)",
+        "       --reprompt-on-invalid  on -V failure keep the input and resume editing",
+        "       --reprompt-text[=<s>] retry on a new line after <s> (default: the -p/-q prompt)",
+        "       --raw-backspace-byte=<b> byte -E0 stores for Backspace: del/0x7f (default) or bs/0x08",
+        "       --edit-default       prefill the input with -d for editing; Enter accepts",
+        "       --paragraph          multi-line input: Enter starts a new line, Enter on an empty line ends",
+        "       --max-input-bytes=<n> reject (with a bell) input beyond <n> bytes",
+        "       --max-n=<n>          largest -n accepted (default 10000)",
+        "       --graphemes          count a grapheme cluster (emoji, accented letter) as one character",
+        "       --ascii-only         reject non-ASCII characters with a bell (also in mask and select)",
+        "       --flash              also flash the screen when a key is rejected with a bell",
+        "       --col=<n>            start input at screen column <n> and redraw from there",
         "       -U/-L                upper/lower case mapping on input",
         "       -Z0/-Z1              trailing newline to stderr (default: on)",
+        "       --newline-to=<ch>    trailing newline goes to stderr (default) or 'value': the value's channel",
+        "       --beep-on-complete   ring the terminal bell when input is accepted",
+        "       --echo-result        show the accepted value on stderr when done (e.g. with -s)",
+        "       --ctrl-d=<policy>    Ctrl-D: delete (default), submit the input, or cancel when empty",
+        "       --tab=<policy>       Tab: ignore (default), insert a tab, complete the -d default, or submit",
+        "       --echo-fd=<fd>       copy everything drawn on the terminal to <fd>, for recordings",
+        "       --outcome-file=<f>   also write value=, status=, exit=, count=, index= lines to <f>",
+        "       --timings[=<fd>]     report response time and keystrokes (in -J, else a line on stderr or <fd>)",
+        "       --cursor-pos         print the terminal cursor position as 'row col' and exit",
+        "       --query=<seq>        send <seq> (\\e for ESC), print the terminal's raw reply, and exit",
+        "       --query-end=<c>      with --query: the reply ends at byte <c> (default: when bytes stop)",
+        "       --query-colors       print the terminal's color support (none, 16, 256, truecolor) and exit",
+        "       --key-names          read one key and print its name with modifiers (Ctrl-Left, Alt-x, Shift-F5)",
+        "       --codepoint[=dec|u]  read one key and print its codepoint (233 with =dec, U+00E9 with =u); other keys by name",
         "       --version            show version and exit",
         "",
         "grabchars -c aeiou          get one of the vowels",
@@ -178,10 +682,17 @@ fn print_usage() {
         "grabchars -t2               timeout after two seconds",
         "grabchars -p 'prompt ' -n 3 print a prompt and grab three characters",
         "",
+        "grabchars pingkey [-n<count>]   time terminal round trips (default 5), print min/avg/max ms",
+        "grabchars anykey [-t<secs>]     wait for any one key (arrows, F-keys too), echo nothing, exit 0",
+        "",
         "grabchars select <options>      inline select from comma-separated list",
         "grabchars select --file <f>     inline select from file (one per line)",
         "grabchars select-lr <options>   horizontal select with all matches shown",
         "grabchars select-lr --file <f>  horizontal select from file (one per line)",
+        "",
+        "Defaults are read from ~/.grabcharsrc (or $GRABCHARS_CONFIG); the command line",
+        "overrides them, and --no-<flag> (--no-silent, --no-tree, ...) switches off a flag",
+        "that takes no value.  Run with GRABCHARS_CONFIG= to skip the file.",
     ];
     for line in &usage {
         eprintln!("{}", line);
@@ -194,18 +705,74 @@ fn print_select_usage() {
         "       grabchars select --file <f>         inline select from file (one per line)",
         "       grabchars select-lr <options>       horizontal select with all matches shown",
         "       grabchars select-lr --file <f>      horizontal select from file (one per line)",
+        "       --file <f> --file <g>           several files: their lines are joined in order",
+        "       --json-file=<f>                 options from a JSON array of strings or {\"value\",\"label\"} objects",
+        "                                       (a \"key\" member is ignored; --initials assigns hotkeys)",
+        "       --dedup                         drop repeated options, keeping the first",
+        "       --confirm[=<marker>]            options starting with <marker> (default !!) need Enter twice",
+        "       --sep=<str|nl>                  separator for the option list (default: newline if present, else comma)",
         "       -p<prompt>                      prompt text",
         "       -d<default>                     default selection",
-        "       -t<seconds>                     timeout",
+        "       --keep-widget                   leave '<filter> → <choice>' on screen after Enter",
+        "       --exit-map=<opt>=<n>,...        exit <n> (0-255) when <opt> is chosen, instead of its index",
+        "       --list-matches=<filter>         print every option <filter> matches, one per line, and exit (0 if any)",
+        "       --default-re=<regex>            start on the first option matching <regex> (-d wins if given)",
+        "       -t<seconds>                     timeout (fractions allowed: -t0.5)",
+        "       --idle=<seconds>                timeout after <seconds> without a keystroke",
         "       -s                              silent mode",
         "       -e                              output to stderr",
         "       -b                              output to both stdout and stderr",
         "       -U/-L                           case mapping on filter input",
         "       -H<r|b|a>                       highlight style: reverse/bracket/arrow (default: r)",
         "       -F<p|f|c>                       filter style: prefix/fuzzy/contains (default: p)",
+        "       -I                              substring filter, the same as -Fc",
         "       -J/-J1/-Jp/-J0                   JSON output: compact/pretty/off",
         "       -B<n>                           ESC exit code: 0=no-op, 1-253/255=exit n",
+        "       --cancel-code=<n>               exact ESC exit code, 0-255 (overrides -B)",
+        "       --on-cancel=<str>               write <str> as the value when ESC cancels",
+        "       --timeout-code=<n>              exit code on timeout (default: 254)",
         "       -Z0/-Z1                         trailing newline control",
+        "       --newline-to=<stderr|value>     channel for the trailing newline (default: stderr)",
+        "       --ctrl-d=<delete|submit|cancel> Ctrl-D deletes in the filter (default), confirms like Enter, or cancels when empty",
+        "       --tty                           read keys from /dev/tty (automatic when stdin is not a terminal)",
+        "       --echo-fd=<fd>                  copy everything drawn on the terminal to <fd>, for recordings",
+        "       --outcome-file=<f>              also write value=, status=, exit=, count=, index= lines to <f>",
+        "       --timings[=<fd>]                report response time and keystrokes (in -J, else a line on stderr or <fd>)",
+        "       --timeout-select=<first|current|none>",
+        "                                       on timeout without -d: return first/highlighted match (default: none)",
+        "       --sort=<none|alpha|alpha-ci|len> display order; exit code and index stay the original position",
+        "       --enter-empty=<current|first|default|none>",
+        "                                       Enter with an empty filter: highlighted/first match, the -d option, or nothing",
+        "       --empty=<all|none>              empty filter matches all options or none (default: all)",
+        "       --print=<value|index|both>      output option text, 0-based index, or INDEX<sep>VALUE",
+        "       --print-index                   same as --print=index",
+        "       --post=<ops>                    transform the chosen option in order: trim, upper, lower, title, quote",
+        "       --prefix=<str>/--suffix=<str>   text around the chosen option (after --post)",
+        "       --print-sep=<str>               separator for --print=both (default: tab; \\t \\n \\0 escapes)",
+        "       --beep-on-complete              ring the terminal bell when a choice is made",
+        "       --echo-result                   show the chosen option on stderr when done",
+        "       --copy                          also copy the chosen option to the clipboard (OSC 52)",
+        "       --max-input-bytes=<n>           reject (with a bell) filter input beyond <n> bytes",
+        "       --tree                          options are paths: pick one level at a time",
+        "       --tree-sep=<str>                path separator for --tree (default: /)",
+        "       --position-indicator            select-lr: show [3/17] (highlighted/matches)",
+        "       --no-trailing-match-count       compact: no match count or arrow hint after the options",
+        "       --no-match-text=<s>             show <s> instead of '(no matches)' (may be empty)",
+        "       --no-match-color                draw the line in red when nothing matches (not with NO_COLOR)",
+        "       --preview=<cmd>                 show <cmd>'s output for the highlighted option ({} = option)",
+        "       --spinner                       animate the preview area while <cmd> runs",
+        "       --accept-keys=<keys>            keys that confirm the highlighted option like Enter (e.g. space,tab)",
+        "       --watch-fd=<n>                  write the highlighted option to fd <n> whenever it or the filter changes",
+        "       --timeout-dump=<n>              on timeout, write '<index>\\t<option>' per match to fd <n> and exit 253",
+        "       --case-sensitive                filter matches case exactly (default: ignore case)",
+        "       --ignore-diacritics             filter ignores accents: 'cafe' matches 'café'",
+        "       --ascii-only                    reject non-ASCII characters typed into the filter",
+        "       --require-unique                Enter only accepts when exactly one option matches (else a bell)",
+        "       --no-filter                     menu only: a key jumps to the next option starting with it",
+        "       --flash                         also flash the screen when a key is rejected with a bell",
+        "       --col=<n>                       start the widget at screen column <n> and redraw from there",
+        "       --initials                      a hotkey per option, shown as (Q)uit; picks it while the filter is empty",
+        "       --default-case-sensitive        -d must match an option's case exactly (default: ignore case)",
     ];
     for line in &usage {
         eprintln!("{}", line);
@@ -241,200 +808,815 @@ impl ArgParser {
             }
         }
     }
-}
-
-// ---------------------------------------------------------------------------
-// Signals
-// ---------------------------------------------------------------------------
-
-fn setup_signals() {
-    unsafe {
-        libc::signal(libc::SIGINT, signal_handler as *const () as libc::sighandler_t);
-        libc::signal(libc::SIGQUIT, signal_handler as *const () as libc::sighandler_t);
-        libc::signal(libc::SIGTSTP, signal_handler as *const () as libc::sighandler_t);
-    }
-}
-
-extern "C" fn signal_handler(_sig: libc::c_int) {
-    term::restore_saved();
-    unsafe {
-        libc::_exit(EXIT_STAT.load(Ordering::Relaxed));
-    }
-}
 
-fn setup_alarm(secs: u32) {
-    unsafe {
-        // Use sigaction instead of signal() for portable behavior.
-        // signal() varies by platform: macOS sets SA_RESTART (read() resumes
-        // after signal, never returning EINTR), glibc on Linux also sets it.
-        // sigaction with sa_flags=0 guarantees read() returns EINTR on all
-        // POSIX systems (macOS, Linux, WSL).
-        let mut sa: libc::sigaction = std::mem::zeroed();
-        sa.sa_sigaction = alarm_handler as *const () as libc::sighandler_t;
-        sa.sa_flags = 0;
-        libc::sigaction(libc::SIGALRM, &sa, std::ptr::null_mut());
-        libc::alarm(secs);
+    /// Value for a long option: the text after `=`, or else the next argument.
+    fn get_longarg(&mut self, inline: Option<&str>) -> Option<String> {
+        match inline {
+            Some(v) => Some(v.to_string()),
+            None => {
+                self.pos += 1;
+                self.args.get(self.pos).cloned()
+            }
+        }
     }
 }
 
-extern "C" fn alarm_handler(_sig: libc::c_int) {
-    TIMED_OUT.store(true, Ordering::Relaxed);
-}
-
-// ---------------------------------------------------------------------------
-// JSON helpers
-// ---------------------------------------------------------------------------
-
-/// Hex-encode raw bytes for JSON value field in raw mode.
-fn raw_hex_value(buf: &[u8]) -> String {
-    buf.iter()
-        .map(|b| format!("{:02x}", b))
-        .collect::<Vec<_>>()
-        .join(" ")
-}
-
-// Emits JSON and exits — used when -J is active
-
-fn emit_json_and_exit(
-    flags: &Flags,
-    value: &str,
-    exit_code: i32,
-    status: &'static str,
-    mode: &'static str,
-    timed_out: bool,
-    default_used: bool,
-    index: Option<i32>,
-    filter: Option<String>,
-    output_to_stderr: bool,
-    orig_termios: &libc::termios,
-) -> ! {
-    if let Some(style) = flags.json {
-        let payload = output::JsonPayload {
-            value: value.to_string(),
-            exit: exit_code,
-            status,
-            mode,
-            timed_out,
-            default_used,
-            index,
-            filter,
-        };
-        output::emit_json(&payload, style, output_to_stderr, flags.both);
-    }
-    output::trailing_newline_if(flags);
-    EXIT_STAT.store(exit_code, Ordering::Relaxed);
-    term::restore_term(orig_termios);
-    process::exit(exit_code);
+/// Long options that take a value.  Needed by the select pre-scan so that
+/// `--opt value` is not mistaken for the positional option list.
+fn long_opt_takes_value(name: &str) -> bool {
+    matches!(name, "timeout-select" | "enter-empty" | "idle" | "empty" | "sort" | "print" | "print-sep" | "ignore-keys" | "accept-keys" | "sep" | "max-input-bytes" | "newline-to" | "tree-sep" | "collect" | "collect-others" | "echo-map" | "post" | "prefix" | "suffix" | "timeout-code" | "cancel-code" | "preview" | "esc-delay" | "on-cancel" | "watch-fd" | "default-re" | "list-matches" | "ctrl-d" | "raw-backspace-byte" | "json-file" | "field-width" | "max-n" | "exit-map" | "disable-edit-keys" | "no-match-text" | "echo-fd" | "range" | "outcome-file" | "col" | "query" | "query-end" | "timeout-dump" | "tab" | "literal-color")
 }
 
-// ---------------------------------------------------------------------------
-// Main
-// ---------------------------------------------------------------------------
-
-fn main() {
-    let mut flags = Flags::new();
-    let mut how_many: usize = 1;
-    let mut timeout_secs: u32 = 0;
-    let mut valid_pattern: Option<regex::Regex> = None;
-    let mut exclude_pattern: Option<regex::Regex> = None;
-    let mut default_string: Option<String> = None;
-    let mut output_to_stderr = false;
-    let mut mask_string: Option<String> = None;
-
-    // Detect select subcommand
-    let mut select_mode = false;
-    let mut select_lr_mode = false;
-    let mut select_options: Vec<String> = Vec::new();
-
-    let mut parser = ArgParser::new();
-
-    // --version flag
-    if parser.pos < parser.args.len() && parser.args[parser.pos] == "--version" {
-        eprintln!("grabchars {}", env!("CARGO_PKG_VERSION"));
-        process::exit(0);
-    }
-
-    if parser.pos < parser.args.len()
-        && (parser.args[parser.pos] == "select" || parser.args[parser.pos] == "select-lr")
-    {
-        select_lr_mode = parser.args[parser.pos] == "select-lr";
-        select_mode = true;
-        parser.pos += 1; // consume "select"
-
-        // Look for options source: --file or positional comma-separated string
-        // We need to scan for --file among the remaining args, or pick up the
-        // first non-flag arg as the comma-separated list.
-        let mut file_path: Option<String> = None;
-        let mut positional_opts: Option<String> = None;
-
-        // Pre-scan for --file (need to find it before normal flag parsing)
-        let mut pre_pos = parser.pos;
-        while pre_pos < parser.args.len() {
-            if parser.args[pre_pos] == "--file" {
-                if pre_pos + 1 < parser.args.len() {
-                    file_path = Some(parser.args[pre_pos + 1].clone());
-                    // Remove --file and its argument from args so flag parser doesn't see them
-                    parser.args.remove(pre_pos);
-                    parser.args.remove(pre_pos);
-                } else {
-                    eprintln!("select: --file requires a filename");
+/// Apply a `--name[=value]` option that only affects `Flags`.
+/// Returns false if `name` is not a recognized long option.
+fn apply_long_option(parser: &mut ArgParser, flags: &mut Flags, name: &str, inline: Option<&str>) -> bool {
+    match name {
+        "timeout-select" => {
+            let val = parser.get_longarg(inline).unwrap_or_else(|| {
+                eprintln!("--timeout-select option: need a policy (first, current, or none)");
+                process::exit(255);
+            });
+            flags.timeout_select = match val.as_str() {
+                "first" => TimeoutSelect::First,
+                "current" => TimeoutSelect::Current,
+                "none" => TimeoutSelect::None,
+                _ => {
+                    eprintln!("--timeout-select option: unrecognized policy '{}' (use first, current, or none)", val);
                     process::exit(255);
                 }
-                break;
+            };
+        }
+        "idle" => {
+            let val = parser.get_longarg(inline).unwrap_or_else(|| {
+                eprintln!("--idle option: need a number");
+                process::exit(255);
+            });
+            flags.idle_secs = val.parse::<u32>().unwrap_or(0);
+            if flags.idle_secs == 0 {
+                eprintln!("--idle option: number of idle seconds must be greater than zero");
+                process::exit(255);
             }
-            pre_pos += 1;
         }
-
-        // If no --file, look for first non-flag arg as comma-separated options
-        if file_path.is_none() {
-            let mut pre_pos2 = parser.pos;
-            while pre_pos2 < parser.args.len() {
-                let a = &parser.args[pre_pos2];
-                if !a.starts_with('-') && a != "--" {
-                    positional_opts = Some(parser.args.remove(pre_pos2));
-                    break;
+        "empty" => {
+            let val = parser.get_longarg(inline).unwrap_or_default();
+            flags.empty_matches_none = match val.as_str() {
+                "all" => false,
+                "none" => true,
+                _ => {
+                    eprintln!("--empty option: unrecognized value '{}' (use all or none)", val);
+                    process::exit(255);
                 }
-                pre_pos2 += 1;
-            }
+            };
         }
-
-        if let Some(ref fp) = file_path {
-            match std::fs::read_to_string(fp) {
-                Ok(contents) => {
-                    select_options = contents
-                        .lines()
-                        .filter(|l| !l.is_empty())
-                        .map(|l| l.to_string())
-                        .collect();
+        "sort" => {
+            let val = parser.get_longarg(inline).unwrap_or_default();
+            flags.sort = match val.as_str() {
+                "none" => SelectSort::None,
+                "alpha" => SelectSort::Alpha,
+                "alpha-ci" => SelectSort::AlphaCi,
+                "len" => SelectSort::Len,
+                _ => {
+                    eprintln!("--sort option: unrecognized value '{}' (use none, alpha, alpha-ci, or len)", val);
+                    process::exit(255);
                 }
-                Err(e) => {
-                    eprintln!("select: cannot read file '{}': {}", fp, e);
+            };
+        }
+        "enter-empty" => {
+            let val = parser.get_longarg(inline).unwrap_or_default();
+            flags.enter_empty = match val.as_str() {
+                "current" => EnterEmpty::Current,
+                "first" => EnterEmpty::First,
+                "default" => EnterEmpty::Default,
+                "none" => EnterEmpty::None,
+                _ => {
+                    eprintln!("--enter-empty option: unrecognized policy '{}' (use current, first, default, or none)", val);
+                    process::exit(255);
+                }
+            };
+        }
+        "ctrl-d" => {
+            let val = parser.get_longarg(inline).unwrap_or_default();
+            flags.ctrl_d = match val.as_str() {
+                "delete" => CtrlD::Delete,
+                "submit" => CtrlD::Submit,
+                "cancel" => CtrlD::Cancel,
+                _ => {
+                    eprintln!("--ctrl-d option: unrecognized policy '{}' (use delete, submit, or cancel)", val);
                     process::exit(255);
                 }
+            };
+        }
+        "tab" => {
+            let val = parser.get_longarg(inline).unwrap_or_default();
+            flags.tab = match val.as_str() {
+                "ignore" => TabKey::Ignore,
+                "insert" => TabKey::Insert,
+                "complete" => TabKey::Complete,
+                "submit" => TabKey::Submit,
+                _ => {
+                    eprintln!("--tab option: unrecognized policy '{}' (use ignore, insert, complete, or submit)", val);
+                    process::exit(255);
+                }
+            };
+        }
+        "cursor-pos" => flags.cursor_pos = true,
+        "query" => {
+            let val = unescape_arg(&parser.get_longarg(inline).unwrap_or_default());
+            if val.is_empty() {
+                eprintln!("--query option: need a sequence to send, e.g. '\\e[c'");
+                process::exit(255);
             }
-        } else if let Some(ref opts_str) = positional_opts {
-            select_options = opts_str.split(',').map(|s| s.trim().to_string()).collect();
+            flags.query = Some(val);
         }
-
-        if select_options.is_empty() {
-            print_select_usage();
-            process::exit(255);
+        "query-end" => {
+            let val = unescape_arg(&parser.get_longarg(inline).unwrap_or_default());
+            match val.as_bytes() {
+                &[b] => flags.query_end = Some(b),
+                _ => {
+                    eprintln!("--query-end option: need a single byte, got '{}'", val);
+                    process::exit(255);
+                }
+            }
         }
-    }
-
-    while parser.pos < parser.args.len() {
-        let arg = parser.args[parser.pos].clone();
-        if !arg.starts_with('-') || arg == "--" {
-            break;
+        "query-colors" => flags.query_colors = true,
+        "key-names" => flags.key_names = true,
+        "codepoint" => {
+            flags.codepoint = Some(match inline {
+                None | Some("dec") => CodepointStyle::Decimal,
+                Some("u") | Some("unicode") => CodepointStyle::Unicode,
+                Some(val) => {
+                    eprintln!("--codepoint option: unrecognized style '{}' (use dec or u)", val);
+                    process::exit(255);
+                }
+            });
         }
-
-        let chars: Vec<char> = arg[1..].chars().collect();
-        let mut i = 0;
-        while i < chars.len() {
-            let rest: String = chars[i + 1..].iter().collect();
-            match chars[i] {
-                'b' => flags.both = true,
-                'c' => {
+        "esc-delay" => {
+            let val = parser.get_longarg(inline).unwrap_or_default();
+            match val.parse::<i32>() {
+                Ok(ms) if (1..=10_000).contains(&ms) => input::set_esc_delay(ms),
+                _ => {
+                    eprintln!("--esc-delay option: need milliseconds (1-10000), got '{}'", val);
+                    process::exit(255);
+                }
+            }
+        }
+        "disable-edit-keys" => {
+            let val = parser.get_longarg(inline).unwrap_or_default();
+            if let Err(e) = input::disable_edit_keys(&val) {
+                eprintln!("--disable-edit-keys option: {} (use an editing key name such as kill-to-start, or its chord such as ctrl-u)", e);
+                process::exit(255);
+            }
+        }
+        "reprompt-on-invalid" => flags.reprompt_on_invalid = true,
+        "flash" => output::set_flash(true),
+        "col" => {
+            let val = parser.get_longarg(inline).unwrap_or_default();
+            match val.parse::<usize>() {
+                Ok(col) if col > 0 => output::set_origin_col(col),
+                _ => {
+                    eprintln!("--col option: need a column number (1 or more), got '{}'", val);
+                    process::exit(255);
+                }
+            }
+        }
+        "reprompt-text" => flags.reprompt_text = Some(inline.map(str::to_string)),
+        "graphemes" => flags.graphemes = true,
+        "reveal-last" => flags.reveal_last = true,
+        "strict" => flags.strict = true,
+        "beep-on-complete" => flags.beep_on_complete = true,
+        "echo-result" => flags.echo_result = true,
+        "copy" => flags.copy = true,
+        "edit-default" => flags.edit_default = true,
+        "paragraph" => flags.paragraph = true,
+        "no-auto-literal" => flags.no_auto_literal = true,
+        "literal-color" => {
+            let val = parser.get_longarg(inline).unwrap_or_default();
+            match val.parse::<u8>() {
+                Ok(n) => flags.literal_color = std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()).then_some(n),
+                Err(_) => {
+                    eprintln!("--literal-color option: need a color number (0-255), got '{}'", val);
+                    process::exit(255);
+                }
+            }
+        }
+        "hex-output" => flags.hex_output = true,
+        "on-cancel" => flags.on_cancel = Some(unescape_arg(&parser.get_longarg(inline).unwrap_or_default())),
+        "timeout-code" | "cancel-code" => {
+            let val = parser.get_longarg(inline).unwrap_or_default();
+            let n = match val.parse::<i32>() {
+                Ok(n) if (0..=255).contains(&n) => n,
+                _ => {
+                    eprintln!("--{} option: need an exit code 0-255, got '{}'", name, val);
+                    process::exit(255);
+                }
+            };
+            if name == "timeout-code" {
+                flags.timeout_code = n;
+            } else {
+                flags.cancel_code = Some(n);
+            }
+        }
+        "collect" => {
+            let val = parser.get_longarg(inline).unwrap_or_default();
+            let (set, count) = val.rsplit_once(':').unwrap_or_else(|| {
+                eprintln!("--collect option: expected <set>:<count>, e.g. aeiou:3");
+                process::exit(255);
+            });
+            let count = count.parse::<usize>().unwrap_or(0);
+            if set.is_empty() || count == 0 {
+                eprintln!("--collect option: need a character set and a count greater than zero");
+                process::exit(255);
+            }
+            let re = regex::Regex::new(&char_class_pattern(set)).unwrap_or_else(|e| {
+                eprintln!("--collect option: {}", e);
+                process::exit(255);
+            });
+            flags.collect = Some((re, count));
+        }
+        "collect-others" => {
+            let val = parser.get_longarg(inline).unwrap_or_default();
+            flags.collect_keep_others = match val.as_str() {
+                "keep" => true,
+                "drop" => false,
+                _ => {
+                    eprintln!("--collect-others option: unrecognized policy '{}' (use keep or drop)", val);
+                    process::exit(255);
+                }
+            };
+        }
+        "case-sensitive" => flags.case_sensitive = true,
+        "ignore-diacritics" => flags.ignore_diacritics = true,
+        "initials" => flags.initials = true,
+        "json-file" => flags.json_file = Some(parser.get_longarg(inline).unwrap_or_default()),
+        "require-unique" => flags.require_unique = true,
+        "no-filter" => flags.no_filter = true,
+        "tty" => flags.tty = true,
+        "tree" => flags.tree = true,
+        "dedup" => flags.dedup = true,
+        "confirm" => {
+            let marker = inline.map(str::to_string).unwrap_or_else(|| "!!".to_string());
+            if marker.is_empty() {
+                eprintln!("--confirm option: the marker cannot be empty");
+                process::exit(255);
+            }
+            flags.confirm_marker = Some(marker);
+        }
+        "spinner" => flags.spinner = true,
+        "preview" => {
+            let val = parser.get_longarg(inline).unwrap_or_default();
+            if val.is_empty() {
+                eprintln!("--preview option: need a command");
+                process::exit(255);
+            }
+            flags.preview = Some(val);
+        }
+        "watch-fd" => {
+            let val = parser.get_longarg(inline).unwrap_or_default();
+            match val.parse::<i32>() {
+                // F_GETFD fails on a descriptor that isn't open
+                Ok(fd) if fd >= 0 && unsafe { libc::fcntl(fd, libc::F_GETFD) } != -1 => flags.watch_fd = Some(fd),
+                _ => {
+                    eprintln!("--watch-fd option: need an open file descriptor, got '{}'", val);
+                    process::exit(255);
+                }
+            }
+        }
+        "timeout-dump" => {
+            let val = parser.get_longarg(inline).unwrap_or_default();
+            match val.parse::<i32>() {
+                Ok(fd) if fd >= 0 && unsafe { libc::fcntl(fd, libc::F_GETFD) } != -1 => flags.timeout_dump = Some(fd),
+                _ => {
+                    eprintln!("--timeout-dump option: need an open file descriptor, got '{}'", val);
+                    process::exit(255);
+                }
+            }
+        }
+        "ascii-only" => flags.ascii_only = true,
+        "mask-values-only" => flags.mask_values_only = true,
+        "raw-backspace-byte" => {
+            let val = parser.get_longarg(inline).unwrap_or_default();
+            flags.raw_backspace = match val.to_lowercase().as_str() {
+                "del" | "0x7f" => 0x7F,
+                "bs" | "0x08" | "0x8" => 0x08,
+                _ => {
+                    eprintln!("--raw-backspace-byte option: unrecognized byte '{}' (use del, 0x7f, bs, or 0x08)", val);
+                    process::exit(255);
+                }
+            };
+        }
+        "keep-widget" => flags.keep_widget = true,
+        "exit-map" => {
+            let val = parser.get_longarg(inline).unwrap_or_default();
+            let mut map = std::collections::HashMap::new();
+            for item in val.split(',').filter(|i| !i.is_empty()) {
+                let code = item.rsplit_once('=').and_then(|(opt, n)| Some((opt, n.parse::<u8>().ok()?)));
+                match code {
+                    Some((opt, n)) if !opt.is_empty() => {
+                        map.insert(opt.to_string(), n as i32);
+                    }
+                    _ => {
+                        eprintln!("--exit-map option: '{}' needs <option>=<code>, with a code from 0 to 255", item);
+                        process::exit(255);
+                    }
+                }
+            }
+            flags.exit_map = Some(map);
+        }
+        "list-matches" => flags.list_matches = Some(parser.get_longarg(inline).unwrap_or_default()),
+        "default-re" => {
+            let val = parser.get_longarg(inline).unwrap_or_default();
+            flags.default_re = Some(regex::Regex::new(&val).unwrap_or_else(|e| {
+                eprintln!("--default-re option: {}", e);
+                process::exit(255);
+            }));
+        }
+        "echo-fd" => {
+            let val = parser.get_longarg(inline).unwrap_or_default();
+            match val.parse::<i32>() {
+                Ok(fd) if fd >= 0 && unsafe { libc::fcntl(fd, libc::F_GETFD) } != -1 => output::set_echo_fd(fd),
+                _ => {
+                    eprintln!("--echo-fd option: need an open file descriptor, got '{}'", val);
+                    process::exit(255);
+                }
+            }
+            // A -p/-q before this was drawn before there was anywhere to copy it
+            if let Some((ref prompt, _)) = flags.prompt {
+                output::echo_copy(prompt.as_bytes());
+            }
+        }
+        "outcome-file" => {
+            let val = parser.get_longarg(inline).unwrap_or_default();
+            if val.is_empty() {
+                eprintln!("--outcome-file option: need a file path");
+                process::exit(255);
+            }
+            flags.outcome_file = Some(val);
+        }
+        "timings" => {
+            flags.timings = true;
+            if let Some(val) = inline {
+                match val.parse::<i32>() {
+                    Ok(fd) if fd >= 0 && unsafe { libc::fcntl(fd, libc::F_GETFD) } != -1 => flags.timings_fd = Some(fd),
+                    _ => {
+                        eprintln!("--timings option: need an open file descriptor, got '{}'", val);
+                        process::exit(255);
+                    }
+                }
+            }
+        }
+        "position-indicator" => flags.position_indicator = true,
+        "no-trailing-match-count" => flags.no_match_count = true,
+        "no-match-text" => flags.no_match_text = Some(parser.get_longarg(inline).unwrap_or_default()),
+        // https://no-color.org: a non-empty NO_COLOR turns color off
+        "no-match-color" => flags.no_match_color = std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()),
+        "tree-sep" => {
+            let val = parser.get_longarg(inline).unwrap_or_default();
+            if val.is_empty() {
+                eprintln!("--tree-sep option: need a separator");
+                process::exit(255);
+            }
+            flags.tree_sep = val;
+        }
+        "default-case-sensitive" => flags.default_case_sensitive = true,
+        "print" => {
+            let val = parser.get_longarg(inline).unwrap_or_default();
+            flags.print_field = match val.as_str() {
+                "value" => PrintField::Value,
+                "index" => PrintField::Index,
+                "both" => PrintField::Both,
+                _ => {
+                    eprintln!("--print option: unrecognized field '{}' (use value, index, or both)", val);
+                    process::exit(255);
+                }
+            };
+        }
+        "print-index" => flags.print_field = PrintField::Index,
+        // Value only in the --strip=<chars> form, so a bare --strip (the
+        // default CR/LF set) can't swallow the next argument
+        "strip" => {
+            let set = inline.map(unescape_arg).unwrap_or_else(|| "\n\r".to_string());
+            flags.strip = Some(set);
+        }
+        "field-width" => {
+            let val = parser.get_longarg(inline).unwrap_or_default();
+            let (n, fill) = match val.split_once(':') {
+                Some((n, fill)) => (n, fill),
+                None => (val.as_str(), "."),
+            };
+            let mut fill_chars = fill.chars();
+            let fill = match (fill_chars.next(), fill_chars.next()) {
+                (Some(c), None) if !c.is_control() => c,
+                _ => {
+                    eprintln!("--field-width option: fill must be one character");
+                    process::exit(255);
+                }
+            };
+            match n.parse::<usize>() {
+                Ok(n) if n > 0 => flags.field_width = Some((n, fill)),
+                _ => {
+                    eprintln!("--field-width option: requires a width of at least 1");
+                    process::exit(255);
+                }
+            }
+        }
+        "echo-map" => {
+            let val = parser.get_longarg(inline).unwrap_or_default();
+            flags.echo_map = Some(output::EchoMap::parse(&val).unwrap_or_else(|e| {
+                eprintln!("--echo-map option: {}", e);
+                process::exit(255);
+            }));
+        }
+        "post" => {
+            let val = parser.get_longarg(inline).unwrap_or_default();
+            flags.post.set_ops(&val).unwrap_or_else(|e| {
+                eprintln!("--post option: {}", e);
+                process::exit(255);
+            });
+        }
+        "prefix" => flags.post.prefix = unescape_arg(&parser.get_longarg(inline).unwrap_or_default()),
+        "suffix" => flags.post.suffix = unescape_arg(&parser.get_longarg(inline).unwrap_or_default()),
+        "ignore-keys" => {
+            let val = parser.get_longarg(inline).unwrap_or_default();
+            flags.ignore_keys = Some(input::KeySet::parse(&val).unwrap_or_else(|e| {
+                eprintln!("--ignore-keys option: {}", e);
+                process::exit(255);
+            }));
+        }
+        "accept-keys" => {
+            let val = parser.get_longarg(inline).unwrap_or_default();
+            flags.accept_keys = Some(input::KeySet::parse(&val).unwrap_or_else(|e| {
+                eprintln!("--accept-keys option: {}", e);
+                process::exit(255);
+            }));
+        }
+        "max-n" => {
+            let val = parser.get_longarg(inline).unwrap_or_else(|| {
+                eprintln!("--max-n option: need a number");
+                process::exit(255);
+            });
+            match val.parse::<usize>() {
+                Ok(n) if n > 0 => flags.max_n = n,
+                _ => {
+                    eprintln!("--max-n option: limit must be greater than zero");
+                    process::exit(255);
+                }
+            }
+        }
+        "max-input-bytes" => {
+            let val = parser.get_longarg(inline).unwrap_or_else(|| {
+                eprintln!("--max-input-bytes option: need a number");
+                process::exit(255);
+            });
+            match val.parse::<usize>() {
+                Ok(n) if n > 0 => flags.max_input_bytes = Some(n),
+                _ => {
+                    eprintln!("--max-input-bytes option: byte limit must be greater than zero");
+                    process::exit(255);
+                }
+            }
+        }
+        "range" => {
+            let val = parser.get_longarg(inline).unwrap_or_default();
+            flags.range = Some(NumRange::parse(&val).unwrap_or_else(|e| {
+                eprintln!("--range option: {}", e);
+                process::exit(255);
+            }));
+        }
+        "newline-to" => {
+            let val = parser.get_longarg(inline).unwrap_or_default();
+            flags.newline_channel = match val.as_str() {
+                "stderr" => NewlineChannel::Stderr,
+                "value" => NewlineChannel::Value,
+                _ => {
+                    eprintln!("--newline-to option: unrecognized channel '{}' (use stderr or value)", val);
+                    process::exit(255);
+                }
+            };
+        }
+        "sep" => {
+            let val = parser.get_longarg(inline).unwrap_or_default();
+            if val.is_empty() {
+                eprintln!("--sep option: need a separator (or 'nl' for newline)");
+                process::exit(255);
+            }
+            flags.option_sep = Some(if val == "nl" { "\n".to_string() } else { unescape_arg(&val) });
+        }
+        "print-sep" => {
+            let val = parser.get_longarg(inline).unwrap_or_else(|| {
+                eprintln!("--print-sep option: need a separator string");
+                process::exit(255);
+            });
+            flags.print_sep = unescape_arg(&val);
+        }
+        _ => return false,
+    }
+    true
+}
+
+/// `--no-<name>`: switch off a flag that takes no value, such as one set in
+/// the config file.  The short flags go by their config names (`--no-silent`
+/// for `-s`).  Returns `false` if `name` is not such a flag.
+fn clear_bool_option(flags: &mut Flags, name: &str) -> bool {
+    match name {
+        "both" => flags.both = false,
+        "flush" => flags.flush = false,
+        "return" => flags.ret_key = false,
+        "silent" => flags.silent = false,
+        "upper" => flags.upper = false,
+        "lower" => flags.lower = false,
+        "reprompt-on-invalid" => flags.reprompt_on_invalid = false,
+        "graphemes" => flags.graphemes = false,
+        "reveal-last" => flags.reveal_last = false,
+        "strict" => flags.strict = false,
+        "beep-on-complete" => flags.beep_on_complete = false,
+        "echo-result" => flags.echo_result = false,
+        "copy" => flags.copy = false,
+        "edit-default" => flags.edit_default = false,
+        "paragraph" => flags.paragraph = false,
+        "no-auto-literal" => flags.no_auto_literal = false,
+        "hex-output" => flags.hex_output = false,
+        "case-sensitive" => flags.case_sensitive = false,
+        "ignore-diacritics" => flags.ignore_diacritics = false,
+        "initials" => flags.initials = false,
+        "require-unique" => flags.require_unique = false,
+        "no-filter" => flags.no_filter = false,
+        "tty" => flags.tty = false,
+        "tree" => flags.tree = false,
+        "dedup" => flags.dedup = false,
+        "spinner" => flags.spinner = false,
+        "ascii-only" => flags.ascii_only = false,
+        "mask-values-only" => flags.mask_values_only = false,
+        "keep-widget" => flags.keep_widget = false,
+        "position-indicator" => flags.position_indicator = false,
+        "no-trailing-match-count" => flags.no_match_count = false,
+        "no-match-color" => flags.no_match_color = false,
+        "default-case-sensitive" => flags.default_case_sensitive = false,
+        _ => return false,
+    }
+    true
+}
+
+// ---------------------------------------------------------------------------
+// Signals
+// ---------------------------------------------------------------------------
+
+fn setup_signals() {
+    unsafe {
+        libc::signal(libc::SIGINT, signal_handler as *const () as libc::sighandler_t);
+        libc::signal(libc::SIGQUIT, signal_handler as *const () as libc::sighandler_t);
+        libc::signal(libc::SIGTSTP, signal_handler as *const () as libc::sighandler_t);
+    }
+}
+
+extern "C" fn signal_handler(_sig: libc::c_int) {
+    term::restore_saved();
+    unsafe {
+        libc::_exit(EXIT_STAT.load(Ordering::Relaxed));
+    }
+}
+
+// ---------------------------------------------------------------------------
+// JSON helpers
+// ---------------------------------------------------------------------------
+
+/// Hex-encode raw bytes for JSON value field in raw mode.
+fn raw_hex_value(buf: &[u8]) -> String {
+    buf.iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Whether line editing is on.  An explicit `-E`/`-E1`/`-E0` always
+/// decides; without one, editing is on for `-n` > 1 and for
+/// `--edit-default` and `--paragraph`, and off for a single-key read.
+///
+/// | flags       | -n1 | -n2 and up |
+/// |-------------|-----|------------|
+/// | (none)      | off | on         |
+/// | -E / -E1    | on  | on         |
+/// | -E0         | off | off        |
+fn resolve_erase(flags: &Flags, how_many: usize) -> bool {
+    match flags.erase {
+        Some(explicit) => explicit,
+        None => how_many > 1 || flags.edit_default || flags.paragraph || flags.range.is_some(),
+    }
+}
+
+/// The buffer as text for JSON and `--echo-result`: hex with
+/// `--hex-output`, otherwise the (lossily decoded) bytes, then `--post`.
+fn buffer_text(buffer: &[u8], flags: &Flags) -> String {
+    let text = if flags.hex_output {
+        raw_hex_value(buffer)
+    } else {
+        String::from_utf8_lossy(buffer).into_owned()
+    };
+    flags.post.apply(&text)
+}
+
+/// Write the captured buffer: as typed, or as `buffer_text` when
+/// `--hex-output` or `--post` changes it.
+fn output_buffer(buffer: &[u8], flags: &Flags, output_to_stderr: bool) {
+    if defers_output(flags) {
+        output::output_str(&buffer_text(buffer, flags), output_to_stderr, flags.both);
+    } else {
+        output::output_bytes(buffer, output_to_stderr, flags.both);
+    }
+}
+
+/// Whether the value can only be written once input is done, because
+/// `--hex-output` or `--post` rewrites it, instead of echoing each key.
+fn defers_output(flags: &Flags) -> bool {
+    flags.hex_output || !flags.post.is_empty()
+}
+
+// Writes --outcome-file, then with -J emits JSON and exits.  Without -J
+// it returns, and the caller goes on to write the plain value.
+
+#[allow(clippy::too_many_arguments)]
+fn report_outcome(
+    flags: &Flags,
+    value: &str,
+    exit_code: i32,
+    status: &'static str,
+    mode: &'static str,
+    timed_out: bool,
+    default_used: bool,
+    index: Option<i32>,
+    filter: Option<String>,
+    output_to_stderr: bool,
+    orig_termios: &libc::termios,
+) {
+    let payload = output::JsonPayload {
+        value: value.to_string(),
+        exit: exit_code,
+        status,
+        mode,
+        timed_out,
+        default_used,
+        index,
+        filter,
+        timeout_kind: timer::fired_name(),
+        timings: timer::timings(),
+    };
+    write_outcome_or_exit(flags, &payload, orig_termios);
+    let Some(style) = flags.json else {
+        return;
+    };
+    output::emit_json(&payload, style, output_to_stderr, flags.both);
+    output::trailing_newline_if(flags);
+    EXIT_STAT.store(exit_code, Ordering::Relaxed);
+    term::restore_term(orig_termios);
+    process::exit(exit_code);
+}
+
+/// `--outcome-file`: write the summary, or exit 255 if it can't be, so a
+/// script never reads a stale file as this run's result.
+fn write_outcome_or_exit(flags: &Flags, payload: &output::JsonPayload, orig_termios: &libc::termios) {
+    let Some(ref path) = flags.outcome_file else {
+        return;
+    };
+    if let Err(e) = output::write_outcome_file(path, payload) {
+        eprintln!("--outcome-file option: cannot write '{}': {}", path, e);
+        term::restore_term(orig_termios);
+        process::exit(255);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Main
+// ---------------------------------------------------------------------------
+
+// Nested `if`s in the key-dispatch arms mirror the original C structure and
+// read more clearly than let-chains or match guards there.
+#[allow(clippy::collapsible_if)]
+fn main() {
+    let mut flags = Flags::new();
+    let mut how_many: usize = 1;
+    let mut count_given = false;
+    let mut timeout_secs: f64 = 0.0;
+    let mut valid_pattern: Option<regex::Regex> = None;
+    let mut exclude_pattern: Option<regex::Regex> = None;
+    let mut whole_pattern: Option<regex::Regex> = None;
+    let mut default_string: Option<String> = None;
+    let mut output_to_stderr = false;
+    let mut mask_string: Option<String> = None;
+
+    // Detect select subcommand
+    let mut select_mode = false;
+    let mut select_lr_mode = false;
+    let mut select_options: Vec<String> = Vec::new();
+    let mut select_values: Vec<String> = Vec::new();
+    let mut select_confirm: Vec<bool> = Vec::new();
+    let mut pingkey_mode = false;
+    let mut anykey_mode = false;
+    let mut file_paths: Vec<String> = Vec::new();
+    let mut positional_opts: Option<String> = None;
+
+    let mut parser = ArgParser::new();
+
+    // --version flag
+    if parser.pos < parser.args.len() && parser.args[parser.pos] == "--version" {
+        eprintln!("grabchars {}", env!("CARGO_PKG_VERSION"));
+        process::exit(0);
+    }
+
+    // pingkey subcommand: time cursor-position round trips
+    if parser.pos < parser.args.len() && parser.args[parser.pos] == "pingkey" {
+        pingkey_mode = true;
+        how_many = 5;
+        parser.pos += 1;
+    }
+
+    // anykey subcommand: "press any key to continue"
+    if parser.pos < parser.args.len() && parser.args[parser.pos] == "anykey" {
+        anykey_mode = true;
+        parser.pos += 1;
+    }
+
+    if parser.pos < parser.args.len()
+        && (parser.args[parser.pos] == "select" || parser.args[parser.pos] == "select-lr")
+    {
+        select_lr_mode = parser.args[parser.pos] == "select-lr";
+        select_mode = true;
+        parser.pos += 1; // consume "select"
+
+        // Look for options source: --file or positional comma-separated string
+        // We need to scan for --file among the remaining args, or pick up the
+        // first non-flag arg as the comma-separated list.
+        // Pre-scan for --file (need to find it before normal flag parsing).
+        // Every --file is collected; their lines are joined in order.
+        let mut pre_pos = parser.pos;
+        while pre_pos < parser.args.len() {
+            if parser.args[pre_pos] == "--file" {
+                if pre_pos + 1 < parser.args.len() {
+                    file_paths.push(parser.args[pre_pos + 1].clone());
+                    // Remove --file and its argument from args so flag parser doesn't see them
+                    parser.args.remove(pre_pos);
+                    parser.args.remove(pre_pos);
+                } else {
+                    eprintln!("select: --file requires a filename");
+                    process::exit(255);
+                }
+                continue;
+            }
+            pre_pos += 1;
+        }
+
+        // If no --file, look for first non-flag arg as comma-separated options
+        if file_paths.is_empty() {
+            let mut pre_pos2 = parser.pos;
+            while pre_pos2 < parser.args.len() {
+                let a = &parser.args[pre_pos2];
+                if !a.starts_with('-') && a != "--" {
+                    positional_opts = Some(parser.args.remove(pre_pos2));
+                    break;
+                }
+                if let Some(long) = a.strip_prefix("--") {
+                    if !long.contains('=') && long_opt_takes_value(long) {
+                        pre_pos2 += 1; // skip the option's value
+                    }
+                }
+                pre_pos2 += 1;
+            }
+        }
+
+    }
+
+    // Config-file defaults go in front of the command-line flags so the
+    // command line wins (later settings override earlier ones).
+    let defaults = config::load();
+    let at = parser.pos;
+    parser.args.splice(at..at, defaults);
+
+    while parser.pos < parser.args.len() {
+        let arg = parser.args[parser.pos].clone();
+        if !arg.starts_with('-') || arg == "--" {
+            break;
+        }
+
+        if let Some(long) = arg.strip_prefix("--") {
+            let (name, inline) = match long.split_once('=') {
+                Some((n, v)) => (n, Some(v)),
+                None => (long, None),
+            };
+            let cleared = |flags: &mut Flags| inline.is_none() && name.strip_prefix("no-").is_some_and(|n| clear_bool_option(flags, n));
+            if !apply_long_option(&mut parser, &mut flags, name, inline) && !cleared(&mut flags) {
+                eprintln!("grabchars: unrecognized option '--{}'", name);
+                print_usage();
+                process::exit(255);
+            }
+            parser.pos += 1;
+            continue;
+        }
+
+        let chars: Vec<char> = arg[1..].chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            let rest: String = chars[i + 1..].iter().collect();
+            match chars[i] {
+                'b' => flags.both = true,
+                'c' => {
                     let val = parser.get_optarg(&rest).unwrap_or_else(|| {
                         eprintln!("-c option: must have at least one valid character");
                         process::exit(255);
@@ -444,11 +1626,7 @@ fn main() {
                         process::exit(255);
                     }
                     flags.check = true;
-                    let pattern = if val.starts_with('[') && val.ends_with(']') {
-                        format!("^{}$", val)
-                    } else {
-                        format!("^[{}]$", val)
-                    };
+                    let pattern = char_class_pattern(&val);
                     valid_pattern = Some(regex::Regex::new(&pattern).unwrap_or_else(|e| {
                         eprintln!("-c option: {}", e);
                         process::exit(255);
@@ -496,6 +1674,7 @@ fn main() {
                         process::exit(255);
                     });
                     how_many = val.parse::<usize>().unwrap_or(0);
+                    count_given = true;
                     if how_many == 0 {
                         eprintln!("-n option: number of characters to read must be greater than zero");
                         process::exit(255);
@@ -506,24 +1685,31 @@ fn main() {
                     let val = parser.get_optarg(&rest).unwrap_or_default();
                     print!("{}", val);
                     let _ = io::stdout().flush();
+                    output::echo_copy(val.as_bytes());
+                    flags.prompt = Some((val, false));
                     break;
                 }
                 'q' => {
                     let val = parser.get_optarg(&rest).unwrap_or_default();
-                    eprint!("{}", val);
-                    let _ = io::stderr().flush();
+                    let _ = write!(output::tty(), "{}", val);
+                    let _ = output::tty().flush();
+                    flags.prompt = Some((val, true));
                     break;
                 }
                 'r' => flags.ret_key = true,
                 'R' => flags.raw = true,
+                // -I = -Fc; as with repeated -F, the last style given wins
+                'I' => flags.match_strategy = MatchStrategy::Contains,
                 's' => flags.silent = true,
                 't' => {
                     let val = parser.get_optarg(&rest).unwrap_or_else(|| {
                         eprintln!("-t option: need a number");
                         process::exit(255);
                     });
-                    timeout_secs = val.parse::<u32>().unwrap_or(0);
-                    if timeout_secs == 0 {
+                    // Fractions are fine (-t0.5); the interval timer has
+                    // microsecond resolution
+                    timeout_secs = val.parse::<f64>().unwrap_or(0.0);
+                    if !(timeout_secs > 0.0 && std::time::Duration::try_from_secs_f64(timeout_secs).is_ok()) {
                         eprintln!("-t option: number of seconds to timeout must be greater than zero");
                         process::exit(255);
                     }
@@ -539,22 +1725,33 @@ fn main() {
                         process::exit(255);
                     }
                     flags.exclude = true;
-                    let pattern = if val.starts_with('[') && val.ends_with(']') {
-                        format!("^{}$", val)
-                    } else {
-                        format!("^[{}]$", val)
-                    };
+                    let pattern = char_class_pattern(&val);
                     exclude_pattern = Some(regex::Regex::new(&pattern).unwrap_or_else(|e| {
                         eprintln!("-C option: {}", e);
                         process::exit(255);
                     }));
                     break;
                 }
+                'V' => {
+                    let val = parser.get_optarg(&rest).unwrap_or_else(|| {
+                        eprintln!("-V option: must provide a pattern for the whole input");
+                        process::exit(255);
+                    });
+                    whole_pattern = Some(regex::Regex::new(&format!("^(?:{})$", val)).unwrap_or_else(|e| {
+                        eprintln!("-V option: {}", e);
+                        process::exit(255);
+                    }));
+                    break;
+                }
                 'E' => {
-                    if rest.starts_with('0') {
+                    // -E or -E1 = on, -E0 = off
+                    if rest.is_empty() || rest.starts_with('1') {
+                        flags.erase = Some(true);
+                    } else if rest.starts_with('0') {
                         flags.erase = Some(false);
                     } else {
-                        flags.erase = Some(true);
+                        eprintln!("-E option: unrecognized value '{}' (use -E, -E1, or -E0)", &rest[..1]);
+                        process::exit(255);
                     }
                     break;
                 }
@@ -583,11 +1780,11 @@ fn main() {
                 'F' => {
                     // -F or -Fp = prefix, -Ff = fuzzy, -Fc = contains
                     if rest.is_empty() || rest.starts_with('p') {
-                        flags.filter_style = FilterStyle::Prefix;
+                        flags.match_strategy = MatchStrategy::Prefix;
                     } else if rest.starts_with('f') {
-                        flags.filter_style = FilterStyle::Fuzzy;
+                        flags.match_strategy = MatchStrategy::Fuzzy;
                     } else if rest.starts_with('c') {
-                        flags.filter_style = FilterStyle::Contains;
+                        flags.match_strategy = MatchStrategy::Contains;
                     } else {
                         eprintln!("-F option: unrecognized style '{}' (use p, f, or c)", &rest[..1]);
                         process::exit(255);
@@ -607,10 +1804,6 @@ fn main() {
                         eprintln!("-B option: exit code must be 0-255");
                         process::exit(255);
                     }
-                    if n == 254 {
-                        eprintln!("-B254 conflicts with the timeout exit code (254); use a different value");
-                        process::exit(255);
-                    }
                     flags.esc_code = Some(n);
                     break;
                 }
@@ -628,11 +1821,7 @@ fn main() {
                 'Z' => {
                     // -Z0 = no trailing newline, -Z1 = trailing newline
                     // -Z alone is the same as -Z1
-                    if rest.starts_with('0') {
-                        flags.trailing_newline = false;
-                    } else {
-                        flags.trailing_newline = true;
-                    }
+                    flags.trailing_newline = !rest.starts_with('0');
                     break;
                 }
                 _ => {
@@ -645,32 +1834,357 @@ fn main() {
         parser.pos += 1;
     }
 
-    // Set up terminal raw mode
-    let orig_termios = term::init_term(flags.flush);
-
-    // Install cleanup on panic
-    let orig_for_panic = orig_termios;
-    let default_hook = std::panic::take_hook();
-    std::panic::set_hook(Box::new(move |info| {
-        term::restore_term(&orig_for_panic);
-        default_hook(info);
-    }));
-
-    // Signal handlers
-    setup_signals();
+    // Checked after parsing so --max-n can come after -n
+    if how_many > flags.max_n {
+        eprintln!("-n option: {} is more than the limit of {} (raise it with --max-n)", how_many, flags.max_n);
+        process::exit(255);
+    }
+
+    // Escape and timeout must stay distinguishable; checked here because
+    // -B/--cancel-code and --timeout-code can come in either order.  -B0 is
+    // a no-op, not an exit code, so it never conflicts
+    for (code, name) in [(flags.esc_code.filter(|&c| c != 0), "-B"), (flags.cancel_code, "--cancel-code")] {
+        if code == Some(flags.timeout_code) {
+            eprintln!(
+                "{} option: {} conflicts with the timeout exit code ({}); use a different value",
+                name, flags.timeout_code, flags.timeout_code
+            );
+            process::exit(255);
+        }
+    }
+    // Likewise a mapped choice must not look like a timeout or an error
+    if let Some(ref map) = flags.exit_map {
+        if let Some((opt, &code)) = map.iter().find(|&(_, &c)| c == flags.timeout_code || c == 255) {
+            let meaning = if code == 255 { "the error/cancel exit code" } else { "the timeout exit code" };
+            eprintln!("--exit-map option: '{}={}' conflicts with {} ({}); use a different value", opt, code, meaning, code);
+            process::exit(255);
+        }
+    }
+
+    // --prefix/--suffix dress up plain output; the -J "value" stays the
+    // result itself (--post ops still apply)
+    if flags.json.is_some() {
+        flags.post.prefix.clear();
+        flags.post.suffix.clear();
+    }
+
+    // With --empty=none nothing matches until something is typed, and
+    // under --no-filter typing never filters, so nothing could be picked
+    if flags.no_filter && flags.empty_matches_none {
+        eprintln!("--no-filter option: cannot be used with --empty=none (no option would ever be shown)");
+        process::exit(255);
+    }
+
+    // Load select options now that --sep is known
+    if select_mode {
+        if !file_paths.is_empty() {
+            // Try every file so each unreadable one is reported
+            let mut unreadable = false;
+            for fp in &file_paths {
+                match std::fs::read_to_string(fp) {
+                    Ok(contents) => {
+                        select_options.extend(
+                            contents.lines().filter(|l| !l.is_empty()).map(|l| l.to_string()),
+                        );
+                    }
+                    Err(e) => {
+                        eprintln!("select: cannot read file '{}': {}", fp, e);
+                        unreadable = true;
+                    }
+                }
+            }
+            if unreadable {
+                process::exit(255);
+            }
+        } else if let Some(ref opts_str) = positional_opts {
+            // An explicit --sep wins; otherwise a list containing newlines
+            // (e.g. "$(printf 'a\nb')") is split one option per line, and
+            // anything else on commas.
+            select_options = match flags.option_sep {
+                Some(ref sep) => opts_str.split(sep.as_str()).map(|s| s.trim().to_string()).collect(),
+                None if opts_str.contains('\n') => opts_str.lines().map(|l| l.trim().to_string()).collect(),
+                None => opts_str.split(',').map(|s| s.trim().to_string()).collect(),
+            };
+            // "a,,b", a trailing separator, or '' leave empty items behind;
+            // they are not options, and an all-empty list is no list
+            select_options.retain(|o| !o.is_empty());
+        }
+
+        // What each option returns: itself, unless --json-file gave it a
+        // separate label to show
+        select_values = select_options.clone();
+        if let Some(ref jf) = flags.json_file {
+            let items = std::fs::read_to_string(jf)
+                .map_err(|e| format!("cannot read file '{}': {}", jf, e))
+                .and_then(|text| select::json_options(&text).map_err(|e| format!("{}: {}", jf, e)));
+            match items {
+                Ok(items) => {
+                    for (label, value) in items {
+                        select_options.push(label);
+                        select_values.push(value);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("--json-file option: {}", e);
+                    process::exit(255);
+                }
+            }
+        }
+
+        // --confirm: a marked option needs a second Enter; the marker is
+        // not part of what is shown, matched, or returned
+        select_confirm = vec![false; select_options.len()];
+        if let Some(ref marker) = flags.confirm_marker {
+            for (i, opt) in select_options.iter_mut().enumerate() {
+                let Some(rest) = opt.strip_prefix(marker.as_str()).filter(|r| !r.is_empty()) else {
+                    continue;
+                };
+                *opt = rest.to_string();
+                if let Some(v) = select_values[i].strip_prefix(marker.as_str()).filter(|v| !v.is_empty()) {
+                    select_values[i] = v.to_string();
+                }
+                select_confirm[i] = true;
+            }
+        }
+
+        // --dedup: keep the first of any repeated option
+        if flags.dedup {
+            let mut seen = std::collections::HashSet::new();
+            let keep: Vec<bool> = select_options.iter().map(|o| seen.insert(o.clone())).collect();
+            let mut flag = keep.iter();
+            select_options.retain(|_| *flag.next().unwrap());
+            let mut flag = keep.iter();
+            select_values.retain(|_| *flag.next().unwrap());
+            let mut flag = keep.iter();
+            select_confirm.retain(|_| *flag.next().unwrap());
+        }
+
+        if select_options.is_empty() {
+            print_select_usage();
+            process::exit(255);
+        }
+    }
+
+    if flags.timeout_dump.is_some() && (!select_mode || flags.tree) {
+        eprintln!("--timeout-dump option: only for select and select-lr, without --tree");
+        process::exit(255);
+    }
+
+    // --list-matches: run the filter once and print, without the terminal
+    if let Some(ref filter) = flags.list_matches {
+        if !select_mode {
+            eprintln!("--list-matches option: only for select and select-lr");
+            process::exit(255);
+        }
+        process::exit(select::list_matches(&select_options, &select_values, filter, &flags, output_to_stderr));
+    }
+
+    // -R keeps the bytes as-is; select and mask ignore it
+    let raw_bytes = flags.raw && !select_mode && mask_string.is_none();
+    if raw_bytes && !flags.post.is_empty() {
+        eprintln!("--post, --prefix, --suffix: not available in raw mode (-R), which keeps the bytes as-is");
+        process::exit(255);
+    }
+    if raw_bytes && flags.ascii_only {
+        eprintln!("--ascii-only option: not available in raw mode (-R), which keeps the bytes as-is");
+        process::exit(255);
+    }
+
+    // Set up terminal raw mode, on /dev/tty if stdin is redirected
+    let stdin_fd = term::open_input(flags.tty);
+    let orig_termios = term::init_term(stdin_fd, flags.flush);
+    // --col: start the input at its column
+    output::to_origin(&mut output::tty(), 0);
+
+    // Install cleanup on panic
+    let orig_for_panic = orig_termios;
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        term::restore_term(&orig_for_panic);
+        default_hook(info);
+    }));
+
+    // Signal handlers
+    setup_signals();
+
+    // Timeout alarm: total (-t) and/or idle (--idle), whichever comes first
+    let total = (timeout_secs > 0.0).then(|| std::time::Duration::from_secs_f64(timeout_secs));
+    let idle = (flags.idle_secs > 0).then(|| std::time::Duration::from_secs(flags.idle_secs as u64));
+    timer::start(total, idle);
+    // --timings: into the JSON when there is JSON, else a line on stderr
+    if flags.timings {
+        let fd = flags.timings_fd.or(if flags.json.is_some() { None } else { Some(2) });
+        timer::start_timings(fd);
+    }
+
+    // Cursor position query: send DSR, print the terminal's "row col" reply
+    if flags.cursor_pos {
+        let _ = output::tty().write_all(output::QUERY_CURSOR_POS);
+        let _ = output::tty().flush();
+        let (val, ec, status) = match input::read_cursor_report(stdin_fd, 1000) {
+            Some((row, col)) => (format!("{} {}", row, col), 0, "ok"),
+            None => (String::new(), 255, "error"),
+        };
+        if flags.reports_outcome() {
+            report_outcome(&flags, &val, ec, status, "cursor-pos", false, false, None, None, output_to_stderr, &orig_termios);
+        }
+        if ec != 0 {
+            eprintln!("grabchars: no cursor position report from terminal");
+        } else if !flags.silent {
+            output::output_str(&val, output_to_stderr, flags.both);
+        }
+        output::trailing_newline_if(&flags);
+        EXIT_STAT.store(ec, Ordering::Relaxed);
+        term::restore_term(&orig_termios);
+        process::exit(ec);
+    }
+
+    // --query: send an arbitrary sequence and pass the reply through
+    if let Some(ref query) = flags.query {
+        let _ = output::tty().write_all(query.as_bytes());
+        let _ = output::tty().flush();
+        let (val, ec, status) = match input::read_reply(stdin_fd, flags.query_end, 1000) {
+            Some(reply) => (String::from_utf8_lossy(&reply).into_owned(), 0, "ok"),
+            None => (String::new(), 255, "error"),
+        };
+        if flags.reports_outcome() {
+            report_outcome(&flags, &val, ec, status, "query", false, false, None, None, output_to_stderr, &orig_termios);
+        }
+        if ec != 0 {
+            eprintln!("grabchars: no reply from terminal");
+        } else if !flags.silent {
+            output::output_str(&val, output_to_stderr, flags.both);
+        }
+        output::trailing_newline_if(&flags);
+        EXIT_STAT.store(ec, Ordering::Relaxed);
+        term::restore_term(&orig_termios);
+        process::exit(ec);
+    }
+
+    // pingkey: -n round trips of DSR query -> cursor report, in milliseconds
+    if pingkey_mode {
+        let mut times: Vec<f64> = Vec::new();
+        for _ in 0..how_many {
+            let start = std::time::Instant::now();
+            let _ = output::tty().write_all(output::QUERY_CURSOR_POS);
+            let _ = output::tty().flush();
+            if input::read_cursor_report(stdin_fd, 1000).is_none() {
+                break;
+            }
+            times.push(start.elapsed().as_secs_f64() * 1000.0);
+        }
+        let (val, ec, status) = if times.len() == how_many {
+            let min = times.iter().cloned().fold(f64::INFINITY, f64::min);
+            let max = times.iter().cloned().fold(0.0, f64::max);
+            let avg = times.iter().sum::<f64>() / times.len() as f64;
+            (format!("min/avg/max = {:.3}/{:.3}/{:.3} ms", min, avg, max), 0, "ok")
+        } else {
+            (String::new(), 255, "error")
+        };
+        if flags.reports_outcome() {
+            report_outcome(&flags, &val, ec, status, "pingkey", false, false, None, None, output_to_stderr, &orig_termios);
+        }
+        if ec != 0 {
+            eprintln!("grabchars: no cursor position report from terminal");
+        } else if !flags.silent {
+            output::output_str(&val, output_to_stderr, flags.both);
+        }
+        output::trailing_newline_if(&flags);
+        EXIT_STAT.store(ec, Ordering::Relaxed);
+        term::restore_term(&orig_termios);
+        process::exit(ec);
+    }
+
+    // anykey: one whole key of any kind, so an arrow or F-key's escape
+    // sequence is consumed with it rather than left for the next read
+    if anykey_mode {
+        let (ec, status) = match input::read_key_codepoint(stdin_fd) {
+            Ok(_) => (0, "ok"),
+            Err(_) if TIMED_OUT.load(Ordering::Relaxed) => (flags.timeout_code, "timeout"),
+            Err(_) => (255, "error"),
+        };
+        let timed_out = status == "timeout";
+        if flags.reports_outcome() {
+            report_outcome(&flags, "", ec, status, "anykey", timed_out, false, None, None, output_to_stderr, &orig_termios);
+        }
+        output::trailing_newline_if(&flags);
+        EXIT_STAT.store(ec, Ordering::Relaxed);
+        term::restore_term(&orig_termios);
+        process::exit(ec);
+    }
+
+    // Color capability query: $TERM/$COLORTERM plus a truecolor probe
+    if flags.query_colors {
+        let level = term::color_support(stdin_fd);
+        if flags.reports_outcome() {
+            report_outcome(&flags, level, 0, "ok", "query-colors", false, false, None, None, output_to_stderr, &orig_termios);
+        }
+        if !flags.silent {
+            output::output_str(level, output_to_stderr, flags.both);
+        }
+        output::trailing_newline_if(&flags);
+        EXIT_STAT.store(0, Ordering::Relaxed);
+        term::restore_term(&orig_termios);
+        process::exit(0);
+    }
+
+    // Key name: one key, named with its modifiers (Ctrl-Left, Alt-x, ...)
+    if flags.key_names {
+        let (val, ec, status) = match input::read_key_name(stdin_fd) {
+            Ok(name) => (name, 0, "ok"),
+            Err(_) if TIMED_OUT.load(Ordering::Relaxed) => (String::new(), flags.timeout_code, "timeout"),
+            Err(_) => (String::new(), 255, "error"),
+        };
+        let timed_out = status == "timeout";
+        if flags.reports_outcome() {
+            report_outcome(&flags, &val, ec, status, "key-names", timed_out, false, None, None, output_to_stderr, &orig_termios);
+        }
+        if !flags.silent {
+            output::output_str(&val, output_to_stderr, flags.both);
+        }
+        output::trailing_newline_if(&flags);
+        EXIT_STAT.store(ec, Ordering::Relaxed);
+        term::restore_term(&orig_termios);
+        process::exit(ec);
+    }
+
+    if let Some(style) = flags.codepoint {
+        let (val, ec, status) = match input::read_key_codepoint(stdin_fd) {
+            Ok(input::CodepointKey::Char(c)) => match style {
+                CodepointStyle::Decimal => ((c as u32).to_string(), 0, "ok"),
+                CodepointStyle::Unicode => (format!("U+{:04X}", c as u32), 0, "ok"),
+            },
+            Ok(input::CodepointKey::Named(name)) => (name, 0, "ok"),
+            Err(_) if TIMED_OUT.load(Ordering::Relaxed) => (String::new(), flags.timeout_code, "timeout"),
+            Err(_) => (String::new(), 255, "error"),
+        };
+        let timed_out = status == "timeout";
+        if flags.reports_outcome() {
+            report_outcome(&flags, &val, ec, status, "codepoint", timed_out, false, None, None, output_to_stderr, &orig_termios);
+        }
+        if !flags.silent {
+            output::output_str(&val, output_to_stderr, flags.both);
+        }
+        output::trailing_newline_if(&flags);
+        EXIT_STAT.store(ec, Ordering::Relaxed);
+        term::restore_term(&orig_termios);
+        process::exit(ec);
+    }
 
-    // Timeout alarm
-    if timeout_secs > 0 {
-        setup_alarm(timeout_secs);
+    // Bracketed paste for the modes that read keys; the one-key queries
+    // above and -R take the bytes as they come
+    if !flags.raw {
+        term::enable_paste();
     }
 
     // Select mode: branch to dedicated handler
     if select_mode {
-        let stdin_fd = io::stdin().as_raw_fd();
         let mode_str = if select_lr_mode { "select-lr" } else { "select" };
         let result = if select_lr_mode {
             select::run_select_lr_mode(
                 &select_options,
+                &select_values,
+                &select_confirm,
                 &flags,
                 &default_string,
                 output_to_stderr,
@@ -679,6 +2193,8 @@ fn main() {
         } else {
             select::run_select_mode(
                 &select_options,
+                &select_values,
+                &select_confirm,
                 &flags,
                 &default_string,
                 output_to_stderr,
@@ -686,7 +2202,7 @@ fn main() {
             )
         };
         let exit_code = result.exit_code;
-        if let Some(style) = flags.json {
+        if flags.reports_outcome() {
             let payload = output::JsonPayload {
                 value: result.value,
                 exit: exit_code,
@@ -696,8 +2212,13 @@ fn main() {
                 default_used: result.default_used,
                 index: result.index,
                 filter: Some(result.filter),
+                timeout_kind: timer::fired_name(),
+                timings: timer::timings(),
             };
-            output::emit_json(&payload, style, output_to_stderr, flags.both);
+            write_outcome_or_exit(&flags, &payload, &orig_termios);
+            if let Some(style) = flags.json {
+                output::emit_json(&payload, style, output_to_stderr, flags.both);
+            }
         }
         output::trailing_newline_if(&flags);
         term::restore_term(&orig_termios);
@@ -706,19 +2227,35 @@ fn main() {
 
     // Mask mode: branch to dedicated handler
     if let Some(ref ms) = mask_string {
-        let parsed_mask = mask::parse_mask(ms);
+        let parsed_mask = mask::parse_mask(ms).unwrap_or_else(|e| {
+            eprintln!("-m option: {}", e);
+            term::restore_term(&orig_termios);
+            process::exit(255);
+        });
         if parsed_mask.is_empty() {
             eprintln!("-m option: mask is empty");
             term::restore_term(&orig_termios);
             process::exit(255);
         }
-        let stdin_fd = io::stdin().as_raw_fd();
+        // A mask the filters make impossible would just never finish
+        let conflicts = mask::find_conflicts(&parsed_mask, &flags, &valid_pattern, &exclude_pattern);
+        for c in &conflicts {
+            if flags.strict {
+                eprintln!("-m option: {}", c);
+            } else {
+                eprintln!("-m option: warning: {}", c);
+            }
+        }
+        if flags.strict && !conflicts.is_empty() {
+            term::restore_term(&orig_termios);
+            process::exit(255);
+        }
         let result = mask::run_mask_mode(
             &parsed_mask, &flags, &default_string,
             &valid_pattern, &exclude_pattern, output_to_stderr, stdin_fd,
         );
         let exit_code = result.exit_code;
-        if let Some(style) = flags.json {
+        if flags.reports_outcome() {
             let payload = output::JsonPayload {
                 value: result.value,
                 exit: exit_code,
@@ -728,8 +2265,13 @@ fn main() {
                 default_used: result.default_used,
                 index: None,
                 filter: None,
+                timeout_kind: timer::fired_name(),
+                timings: timer::timings(),
             };
-            output::emit_json(&payload, style, output_to_stderr, flags.both);
+            write_outcome_or_exit(&flags, &payload, &orig_termios);
+            if let Some(style) = flags.json {
+                output::emit_json(&payload, style, output_to_stderr, flags.both);
+            }
         }
         output::trailing_newline_if(&flags);
         term::restore_term(&orig_termios);
@@ -738,9 +2280,8 @@ fn main() {
 
     // Raw mode: bypass escape sequence parser, collect bytes as-is
     if flags.raw {
-        let stdin_fd = io::stdin().as_raw_fd();
         let mut num_read: usize = 0;
-        let mut buffer: Vec<u8> = Vec::new();
+        let mut buffer: Vec<u8> = Vec::with_capacity(how_many.min(INITIAL_BUFFER));
 
         'raw: loop {
             if num_read >= how_many {
@@ -749,9 +2290,9 @@ fn main() {
             if TIMED_OUT.load(Ordering::Relaxed) {
                 if flags.dflt && num_read == 0 {
                     if let Some(ref ds) = default_string {
-                        if flags.json.is_some() {
+                        if flags.reports_outcome() {
                             let ec = ds.len() as i32;
-                            emit_json_and_exit(&flags, ds, ec, "default", "raw", true, true, None, None, output_to_stderr, &orig_termios);
+                            report_outcome(&flags, ds, ec, "default", "raw", true, true, None, None, output_to_stderr, &orig_termios);
                         }
                         output::handle_default(ds, &flags, output_to_stderr);
                         output::trailing_newline_if(&flags);
@@ -759,30 +2300,32 @@ fn main() {
                         process::exit(EXIT_STAT.load(Ordering::Relaxed));
                     }
                 }
-                if flags.json.is_some() {
+                if flags.reports_outcome() {
                     let val = raw_hex_value(&buffer);
-                    emit_json_and_exit(&flags, &val, 254, "timeout", "raw", true, false, None, None, output_to_stderr, &orig_termios);
+                    report_outcome(&flags, &val, flags.timeout_code, "timeout", "raw", true, false, None, None, output_to_stderr, &orig_termios);
                 }
                 if !flags.silent && !buffer.is_empty() {
-                    output::output_bytes(&buffer, output_to_stderr, flags.both);
+                    output_buffer(&buffer, &flags, output_to_stderr);
                 }
+                output::echo_result(&buffer_text(&buffer, &flags), &flags);
                 output::trailing_newline_if(&flags);
-                EXIT_STAT.store(-2, Ordering::Relaxed);
+                EXIT_STAT.store(flags.timeout_code, Ordering::Relaxed);
                 term::restore_term(&orig_termios);
-                process::exit(-2);
+                process::exit(flags.timeout_code);
             }
             let b = match input::read_byte(stdin_fd) {
                 Ok(b) => b,
                 Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
                 Err(_) => break,
             };
+            timer::touch();
             // -r: Enter (0x0A or 0x0D) exits the loop; byte is NOT added to buffer
             if (b == 0x0A || b == 0x0D) && flags.ret_key {
                 if flags.dflt && num_read == 0 {
                     if let Some(ref ds) = default_string {
-                        if flags.json.is_some() {
+                        if flags.reports_outcome() {
                             let ec = ds.len() as i32;
-                            emit_json_and_exit(&flags, ds, ec, "default", "raw", false, true, None, None, output_to_stderr, &orig_termios);
+                            report_outcome(&flags, ds, ec, "default", "raw", false, true, None, None, output_to_stderr, &orig_termios);
                         }
                         output::handle_default(ds, &flags, output_to_stderr);
                         output::trailing_newline_if(&flags);
@@ -792,308 +2335,834 @@ fn main() {
                 }
                 break 'raw;
             }
+            if input_cap_reached(&flags, buffer.len(), 1) {
+                continue;
+            }
             buffer.push(b);
             num_read += 1;
         }
 
         let ec = num_read as i32;
-        if flags.json.is_some() {
+        if flags.reports_outcome() {
             let val = raw_hex_value(&buffer);
-            emit_json_and_exit(&flags, &val, ec, "ok", "raw", false, false, None, None, output_to_stderr, &orig_termios);
+            report_outcome(&flags, &val, ec, "ok", "raw", false, false, None, None, output_to_stderr, &orig_termios);
         }
         if !flags.silent && !buffer.is_empty() {
-            output::output_bytes(&buffer, output_to_stderr, flags.both);
+            output_buffer(&buffer, &flags, output_to_stderr);
         }
+        output::echo_result(&buffer_text(&buffer, &flags), &flags);
         output::trailing_newline_if(&flags);
         EXIT_STAT.store(ec, Ordering::Relaxed);
         term::restore_term(&orig_termios);
         process::exit(ec);
     }
 
-    // Resolve erase mode: if unset, default to on when how_many > 1
-    let erase_active = match flags.erase {
-        Some(v) => v,
-        None => how_many > 1,
-    };
+    // --collect: read until <count> keys from the set have been seen.  Only
+    // those count; other keys are dropped or (--collect-others=keep) kept in
+    // the buffer uncounted.  -c/-C and -U/-L apply first, as usual.
+    if let Some((ref set, target)) = flags.collect {
+        let mut buffer: Vec<u8> = Vec::new();
+        let mut collected: usize = 0;
 
-    // Main character-reading loop
-    let mut num_read: usize = 0;
-    let mut buffer: Vec<u8> = Vec::new();
-    let mut cursor_pos: usize = 0;
-    let stdin_fd = io::stdin().as_raw_fd();
-
-    'outer: while num_read < how_many {
-        if TIMED_OUT.load(Ordering::Relaxed) {
-            if flags.dflt && num_read == 0 {
-                if let Some(ref ds) = default_string {
-                    if flags.json.is_some() {
-                        let ec = ds.len() as i32;
-                        emit_json_and_exit(&flags, ds, ec, "default", "normal", true, true, None, None, output_to_stderr, &orig_termios);
+        while collected < target {
+            if TIMED_OUT.load(Ordering::Relaxed) {
+                if flags.dflt && buffer.is_empty() {
+                    if let Some(ref ds) = default_string {
+                        if flags.reports_outcome() {
+                            let ec = ds.len() as i32;
+                            report_outcome(&flags, &flags.post.apply(ds), ec, "default", "normal", true, true, None, None, output_to_stderr, &orig_termios);
+                        }
+                        output::handle_default(ds, &flags, output_to_stderr);
+                        output::trailing_newline_if(&flags);
+                        term::restore_term(&orig_termios);
+                        process::exit(EXIT_STAT.load(Ordering::Relaxed));
                     }
-                    output::handle_default(ds, &flags, output_to_stderr);
-                    output::trailing_newline_if(&flags);
-                    term::restore_term(&orig_termios);
-                    process::exit(EXIT_STAT.load(Ordering::Relaxed));
                 }
+                if flags.reports_outcome() {
+                    report_outcome(&flags, "", flags.timeout_code, "timeout", "normal", true, false, None, None, output_to_stderr, &orig_termios);
+                }
+                output::trailing_newline_if(&flags);
+                EXIT_STAT.store(flags.timeout_code, Ordering::Relaxed);
+                term::restore_term(&orig_termios);
+                process::exit(flags.timeout_code);
             }
-            if flags.json.is_some() {
-                emit_json_and_exit(&flags, "", 254, "timeout", "normal", true, false, None, None, output_to_stderr, &orig_termios);
-            }
-            output::trailing_newline_if(&flags);
-            EXIT_STAT.store(-2, Ordering::Relaxed);
-            term::restore_term(&orig_termios);
-            process::exit(-2);
-        }
-
-        let key = match input::read_key(stdin_fd) {
-            Ok(k) => k,
-            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
-            Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
-            Err(_) => break,
-        };
-
-        if erase_active {
+            let key = match input::read_key(stdin_fd) {
+                Ok(KeyInput::CtrlD) => match ctrl_d_key(&flags, buffer.is_empty()) {
+                    Some(k) => k,
+                    None => break,
+                },
+                Ok(k) => k,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            };
             match key {
                 KeyInput::Char(b) => {
-                    let ch = match apply_char_filters(b as char, &flags, &valid_pattern, &exclude_pattern) {
-                        Some(c) => c,
-                        None => continue,
+                    if non_ascii_rejected(&flags, stdin_fd, b) {
+                        continue;
+                    }
+                    let Some(ch) = apply_char_filters(b as char, &flags, &valid_pattern, &exclude_pattern) else {
+                        continue;
                     };
-                    buffer.insert(cursor_pos, ch as u8);
-                    cursor_pos += 1;
-                    num_read += 1;
-                    if !flags.silent {
-                        output::redraw_input(&buffer, cursor_pos, cursor_pos - 1);
+                    let in_set = set.is_match(&ch.to_string());
+                    if !in_set && !flags.collect_keep_others {
+                        continue;
+                    }
+                    if input_cap_reached(&flags, buffer.len(), 1) {
+                        continue;
+                    }
+                    buffer.push(ch as u8);
+                    if in_set {
+                        collected += 1;
+                    }
+                    if !flags.silent && !defers_output(&flags) {
+                        output::output_char(ch, output_to_stderr, flags.both);
                     }
                 }
-                KeyInput::Backspace => {
-                    if cursor_pos > 0 {
-                        buffer.remove(cursor_pos - 1);
-                        cursor_pos -= 1;
-                        num_read -= 1;
-                        if !flags.silent {
-                            output::redraw_input(&buffer, cursor_pos, cursor_pos + 1);
+                KeyInput::Enter if flags.ret_key => break,
+                KeyInput::Paste(text) => input::type_paste(&text),
+                KeyInput::Escape => {
+                    if let Some(n) = escape_exit(&flags, None) {
+                        if flags.reports_outcome() {
+                            report_outcome(&flags, "", n, "cancelled", "normal", false, false, None, None, output_to_stderr, &orig_termios);
                         }
+                        output::emit_cancel(&flags, output_to_stderr);
+                        output::trailing_newline_if(&flags);
+                        term::restore_term(&orig_termios);
+                        process::exit(n);
                     }
                 }
-                KeyInput::Delete => {
-                    if cursor_pos < buffer.len() {
-                        buffer.remove(cursor_pos);
-                        num_read -= 1;
-                        if !flags.silent {
-                            output::redraw_input(&buffer, cursor_pos, cursor_pos);
+                _ => {}
+            }
+        }
+
+        let ec = collected as i32;
+        output::beep_on_complete(&flags);
+        let val = buffer_text(&buffer, &flags);
+        if flags.reports_outcome() {
+            report_outcome(&flags, &val, ec, "ok", "normal", false, false, None, None, output_to_stderr, &orig_termios);
+        }
+        // --hex-output, --post: nothing was echoed as it was typed
+        if defers_output(&flags) && !flags.silent && !buffer.is_empty() {
+            output_buffer(&buffer, &flags, output_to_stderr);
+        }
+        output::echo_result(&val, &flags);
+        output::trailing_newline_if(&flags);
+        EXIT_STAT.store(ec, Ordering::Relaxed);
+        term::restore_term(&orig_termios);
+        process::exit(ec);
+    }
+
+    // --paragraph and --edit-default end on Enter; without -n the line is
+    // unbounded (the prefilled default would otherwise already fill it)
+    if (flags.paragraph || flags.edit_default) && !count_given {
+        how_many = usize::MAX;
+    }
+    // --range: room for the widest bound unless -n says otherwise, and
+    // Enter ends a shorter number
+    if let Some(range) = flags.range {
+        if !count_given {
+            how_many = range.width();
+        }
+        flags.ret_key = true;
+    }
+    let erase_active = resolve_erase(&flags, how_many);
+
+    // -V checks the finished buffer; without editing, characters are already
+    // written to the output as they are typed, so there is nothing to check.
+    if whole_pattern.is_some() && !erase_active {
+        eprintln!("-V option: whole-input validation requires line editing (-E or -n > 1)");
+        term::restore_term(&orig_termios);
+        process::exit(255);
+    }
+    if flags.edit_default && (!erase_active || default_string.is_none()) {
+        eprintln!("--edit-default option: needs -d and line editing");
+        term::restore_term(&orig_termios);
+        process::exit(255);
+    }
+    // Without editing each character is written to the output as typed, so
+    // there is no separate echo for --echo-map to change.
+    if flags.echo_map.is_some() && !erase_active {
+        eprintln!("--echo-map option: requires line editing (-E or -n > 1)");
+        term::restore_term(&orig_termios);
+        process::exit(255);
+    }
+    if flags.reveal_last && flags.echo_map.is_none() {
+        eprintln!("--reveal-last option: requires --echo-map to mask the other characters");
+        term::restore_term(&orig_termios);
+        process::exit(255);
+    }
+    if flags.strip.is_some() && !erase_active {
+        eprintln!("--strip option: requires line editing (-E or -n > 1)");
+        term::restore_term(&orig_termios);
+        process::exit(255);
+    }
+    if flags.reprompt_text.is_some() && !flags.reprompt_on_invalid {
+        eprintln!("--reprompt-text option: requires --reprompt-on-invalid");
+        term::restore_term(&orig_termios);
+        process::exit(255);
+    }
+    if flags.paragraph && !erase_active {
+        eprintln!("--paragraph option: requires line editing (-E or -n > 1)");
+        term::restore_term(&orig_termios);
+        process::exit(255);
+    }
+    if flags.range.is_some() && (!erase_active || flags.graphemes) {
+        eprintln!("--range option: requires line editing (not -E0), and not --graphemes");
+        term::restore_term(&orig_termios);
+        process::exit(255);
+    }
+    if flags.field_width.is_some() && (!erase_active || flags.paragraph) {
+        eprintln!("--field-width option: requires line editing (-E or -n > 1), and not --paragraph");
+        term::restore_term(&orig_termios);
+        process::exit(255);
+    }
+
+    // Main character-reading loop
+    let mut num_read: usize = 0;
+    let mut buffer: Vec<u8> = Vec::with_capacity(how_many.min(INITIAL_BUFFER));
+    let mut cursor_pos: usize = 0;
+    // cursor_pos is a byte offset into buffer; num_read counts units (bytes,
+    // or clusters with --graphemes) and screen moves use their column width.
+    let units = grapheme::Units { graphemes: flags.graphemes };
+    // What the editor draws: the buffer, or its --echo-map rendering, with
+    // the unit at `reveal` left as typed
+    let shown_revealing = |buf: &[u8], reveal: Option<usize>| -> Vec<u8> {
+        let mut out = match flags.echo_map {
+            Some(ref map) => map.apply(buf, units, reveal),
+            None => buf.to_vec(),
+        };
+        // A tab is drawn as the one column the editor counts for it, other
+        // control bytes (quoted with Ctrl-V) as ^X in two
+        for b in out.iter_mut().filter(|b| **b == b'\t') {
+            *b = b' ';
+        }
+        output::caret_notation(&out)
+    };
+    let shown = |buf: &[u8]| shown_revealing(buf, None);
+    // --reveal-last: byte offset of the character currently shown as typed
+    let mut revealed: Option<usize> = None;
+    // --field-width: the editor is redrawn whole into the field after each
+    // key, instead of by the keys' own cursor moves
+    let mut field = flags.field_width.map(|(width, fill)| output::Field::new(width, fill));
+    let draw_inline = !flags.silent && field.is_none();
+    let draw_field = |field: &mut output::Field, buf: &[u8], cursor_pos: usize, reveal: Option<usize>| {
+        field.draw(buf, cursor_pos, units, |part, offset| shown_revealing(part, reveal.and_then(|r| r.checked_sub(offset))));
+    };
+
+    // -V: whole-string validation when the read completes.  With
+    // --reprompt-on-invalid a failed check resumes editing the same buffer.
+    let mut revalidate = false;
+    let mut invalid_shown = false;
+    let mut input_closed = false;
+    // Numeric argument (Alt-digits) for the next editing key, as in readline
+    let mut count: Option<usize> = None;
+    // --paragraph: lines already ended with Enter, each with its '\n'.  The
+    // editor works on the current line only; the two join when the read ends.
+    let mut paragraph: Vec<u8> = Vec::new();
+
+    // --edit-default: start with the default in the buffer, cursor at the
+    // end.  The read then finishes on Enter rather than after -n characters.
+    if flags.edit_default {
+        if let Some(ref ds) = default_string {
+            buffer = ds.as_bytes().to_vec();
+            cursor_pos = buffer.len();
+            num_read = units.count(&buffer);
+            if draw_inline {
+                output::redraw_input(&shown(&buffer), 0, 0);
+            }
+        }
+    }
+    if let Some(ref mut f) = field {
+        if !flags.silent {
+            draw_field(f, &buffer, cursor_pos, None);
+        }
+    }
+
+    'edit: loop {
+        'outer: while num_read < how_many || revalidate || flags.edit_default || flags.paragraph {
+            if TIMED_OUT.load(Ordering::Relaxed) {
+                // --edit-default: a timeout keeps the user's edits, as long
+                // as they would pass -V/--range; otherwise the default stands
+                if flags.edit_default && paragraph.is_empty() && default_string.as_deref().is_some_and(|ds| ds.as_bytes() != buffer) {
+                    let kept = strip_trailing(&buffer, &flags);
+                    let s = String::from_utf8_lossy(kept);
+                    let valid = whole_pattern.as_ref().is_none_or(|re| re.is_match(&s))
+                        && flags.range.is_none_or(|range| range.contains(kept));
+                    if valid {
+                        buffer.truncate(kept.len());
+                        let ec = units.count(&buffer) as i32;
+                        if flags.reports_outcome() {
+                            let val = buffer_text(&buffer, &flags);
+                            report_outcome(&flags, &val, ec, "timeout", "normal", true, false, None, None, output_to_stderr, &orig_termios);
                         }
+                        if !flags.silent && !buffer.is_empty() {
+                            output_buffer(&buffer, &flags, output_to_stderr);
+                        }
+                        output::echo_result(&buffer_text(&buffer, &flags), &flags);
+                        output::trailing_newline_if(&flags);
+                        EXIT_STAT.store(ec, Ordering::Relaxed);
+                        term::restore_term(&orig_termios);
+                        process::exit(ec);
                     }
                 }
-                KeyInput::Left => {
-                    if cursor_pos > 0 {
-                        cursor_pos -= 1;
-                        if !flags.silent {
-                            let _ = io::stderr().write_all(CURSOR_LEFT);
-                            let _ = io::stderr().flush();
+                // With --edit-default the buffer is never "empty" to begin
+                // with; an unedited (or invalid) one falls back to the default.
+                if flags.dflt && ((num_read == 0 && paragraph.is_empty()) || flags.edit_default) {
+                    if let Some(ref ds) = default_string {
+                        if flags.reports_outcome() {
+                            let ec = ds.len() as i32;
+                            report_outcome(&flags, &flags.post.apply(ds), ec, "default", "normal", true, true, None, None, output_to_stderr, &orig_termios);
                         }
+                        output::handle_default(ds, &flags, output_to_stderr);
+                        output::trailing_newline_if(&flags);
+                        term::restore_term(&orig_termios);
+                        process::exit(EXIT_STAT.load(Ordering::Relaxed));
                     }
                 }
-                KeyInput::Right => {
-                    if cursor_pos < buffer.len() {
-                        cursor_pos += 1;
-                        if !flags.silent {
-                            let _ = io::stderr().write_all(CURSOR_RIGHT);
-                            let _ = io::stderr().flush();
-                        }
+                if flags.reports_outcome() {
+                    report_outcome(&flags, "", flags.timeout_code, "timeout", "normal", true, false, None, None, output_to_stderr, &orig_termios);
+                }
+                output::trailing_newline_if(&flags);
+                EXIT_STAT.store(flags.timeout_code, Ordering::Relaxed);
+                term::restore_term(&orig_termios);
+                process::exit(flags.timeout_code);
+            }
+
+            // --reveal-last: mask the revealed character after a quiet second
+            if revealed.is_some() && !input::byte_available(stdin_fd, REVEAL_MS) {
+                revealed = None;
+                if let Some(ref mut f) = field {
+                    if !flags.silent {
+                        draw_field(f, &buffer, cursor_pos, None);
                     }
+                } else if !flags.silent {
+                    output::redraw_input(&shown(&buffer), units.width(&buffer[cursor_pos..]), units.width(&buffer[..cursor_pos]));
+                }
+                continue;
+            }
+
+            let key = match input::read_key(stdin_fd) {
+                // --ignore-keys names Ctrl-D itself, not what --ctrl-d maps it to
+                Ok(KeyInput::CtrlD) if flags.ignore_keys.as_ref().is_some_and(|k| k.contains(&KeyInput::CtrlD)) => continue,
+                Ok(KeyInput::CtrlD) => match ctrl_d_key(&flags, buffer.is_empty() && paragraph.is_empty()) {
+                    Some(k) => k,
+                    None => break,
+                },
+                Ok(k) => k,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => { input_closed = true; break; }
+                Err(_) => { input_closed = true; break; }
+            };
+
+            // ...or as soon as the next key arrives
+            if revealed.take().is_some() && !flags.silent {
+                match field {
+                    Some(ref mut f) => draw_field(f, &buffer, cursor_pos, None),
+                    None => output::redraw_input(&shown(&buffer), units.width(&buffer[cursor_pos..]), units.width(&buffer[..cursor_pos])),
+                }
+            }
+
+            if invalid_shown {
+                // Cursor is still where the hint was drawn: just erase it
+                if draw_inline {
+                    let _ = output::tty().write_all(CLEAR_TO_EOL);
+                    let _ = output::tty().flush();
                 }
-                KeyInput::Home => {
-                    if cursor_pos > 0 {
-                        if !flags.silent {
-                            let mut stderr = io::stderr();
-                            output::cursor_left_n(&mut stderr, cursor_pos);
-                            let _ = stderr.flush();
+                invalid_shown = false;
+            }
+
+            if flags.ignore_keys.as_ref().is_some_and(|k| k.contains(&key)) {
+                continue;
+            }
+
+            // --tab: with `insert` Tab is typed like any other key
+            let key = match key {
+                KeyInput::Tab => match flags.tab {
+                    TabKey::Ignore => continue,
+                    TabKey::Insert => KeyInput::Char(b'\t'),
+                    TabKey::Submit => break 'outer,
+                    TabKey::Complete => {
+                        let rest = default_string.as_deref().and_then(|ds| ds.as_bytes().strip_prefix(buffer.as_slice()));
+                        match rest {
+                            Some(rest) if !rest.is_empty() && (!erase_active || cursor_pos == buffer.len()) => {
+                                input::type_paste(&String::from_utf8_lossy(rest));
+                            }
+                            _ => output::alert(),
                         }
-                        cursor_pos = 0;
+                        continue;
                     }
+                },
+                k => k,
+            };
+
+            if erase_active {
+                // Alt-digit: build a count that repeats the next editing key
+                if let KeyInput::Count(d) = key {
+                    count = Some((count.unwrap_or(0) * 10 + d as usize).min(MAX_REPEAT));
+                    continue;
                 }
-                KeyInput::End => {
-                    if cursor_pos < buffer.len() {
-                        let delta = buffer.len() - cursor_pos;
-                        if !flags.silent {
-                            let mut stderr = io::stderr();
-                            output::cursor_right_n(&mut stderr, delta);
-                            let _ = stderr.flush();
+                let repeat = count.take().unwrap_or(1);
+                match key {
+                    KeyInput::Char(_) if num_read >= how_many => {} // full: edit before typing more
+                    KeyInput::Char(b) => {
+                        if non_ascii_rejected(&flags, stdin_fd, b) {
+                            continue;
+                        }
+                        let unit = if flags.graphemes {
+                            let cluster = input::read_cluster(stdin_fd, b);
+                            match apply_cluster_filters(cluster, &flags, &valid_pattern, &exclude_pattern) {
+                                Some(u) => u,
+                                None => continue,
+                            }
+                        } else {
+                            match apply_char_filters(b as char, &flags, &valid_pattern, &exclude_pattern) {
+                                Some(c) => vec![c as u8],
+                                None => continue,
+                            }
+                        };
+                        // --range: only keys that keep the number reachable
+                        if let Some(range) = flags.range {
+                            let mut next = buffer.clone();
+                            next.splice(cursor_pos..cursor_pos, unit.iter().copied());
+                            if !range.allows_prefix(&next, how_many) {
+                                output::alert();
+                                continue;
+                            }
+                        }
+                        if input_cap_reached(&flags, buffer.len(), unit.len()) {
+                            continue;
+                        }
+                        let prev_col = units.width(&buffer[..cursor_pos]);
+                        let len = unit.len();
+                        buffer.splice(cursor_pos..cursor_pos, unit);
+                        if flags.reveal_last {
+                            revealed = Some(cursor_pos);
+                        }
+                        cursor_pos += len;
+                        num_read += 1;
+                        if draw_inline {
+                            output::redraw_input(&shown_revealing(&buffer, revealed), units.width(&buffer[cursor_pos..]), prev_col);
                         }
-                        cursor_pos = buffer.len();
                     }
-                }
-                KeyInput::KillToEnd => {
-                    let removed = buffer.len() - cursor_pos;
-                    if removed > 0 {
-                        buffer.truncate(cursor_pos);
-                        num_read -= removed;
-                        if !flags.silent {
-                            let _ = io::stderr().write_all(CLEAR_TO_EOL);
-                            let _ = io::stderr().flush();
+                    KeyInput::Backspace => {
+                        for _ in 0..repeat {
+                            if cursor_pos > 0 {
+                                let prev_col = units.width(&buffer[..cursor_pos]);
+                                let start = units.prev(&buffer, cursor_pos);
+                                buffer.drain(start..cursor_pos);
+                                cursor_pos = start;
+                                num_read -= 1;
+                                if draw_inline {
+                                    output::redraw_input(&shown(&buffer), units.width(&buffer[cursor_pos..]), prev_col);
+                                }
+                            }
                         }
                     }
-                }
-                KeyInput::KillToStart => {
-                    if cursor_pos > 0 {
-                        let old_cursor = cursor_pos;
-                        buffer.drain(..cursor_pos);
-                        num_read -= old_cursor;
-                        cursor_pos = 0;
-                        if !flags.silent {
-                            output::redraw_input(&buffer, cursor_pos, old_cursor);
+                    KeyInput::Delete => {
+                        for _ in 0..repeat {
+                            if cursor_pos < buffer.len() {
+                                let end = units.next(&buffer, cursor_pos);
+                                buffer.drain(cursor_pos..end);
+                                num_read -= 1;
+                                if draw_inline {
+                                    let col = units.width(&buffer[..cursor_pos]);
+                                    output::redraw_input(&shown(&buffer), units.width(&buffer[cursor_pos..]), col);
+                                }
+                            }
                         }
                     }
-                }
-                KeyInput::KillWordBack => {
-                    if cursor_pos > 0 {
-                        let old_cursor = cursor_pos;
-                        // Skip whitespace backward
-                        let mut new_pos = cursor_pos;
-                        while new_pos > 0 && buffer[new_pos - 1] == b' ' {
-                            new_pos -= 1;
+                    KeyInput::Left | KeyInput::AltLeft => {
+                        for _ in 0..repeat {
+                            if cursor_pos > 0 {
+                                let start = units.prev(&buffer, cursor_pos);
+                                let cols = units.width(&buffer[start..cursor_pos]);
+                                cursor_pos = start;
+                                if draw_inline {
+                                    let mut stderr = output::tty();
+                                    if cols == 1 {
+                                        let _ = stderr.write_all(CURSOR_LEFT);
+                                    } else {
+                                        output::cursor_left_n(&mut stderr, cols);
+                                    }
+                                    let _ = stderr.flush();
+                                }
+                            }
                         }
-                        // Skip non-whitespace backward
-                        while new_pos > 0 && buffer[new_pos - 1] != b' ' {
-                            new_pos -= 1;
+                    }
+                    KeyInput::Right | KeyInput::AltRight => {
+                        for _ in 0..repeat {
+                            if cursor_pos < buffer.len() {
+                                let end = units.next(&buffer, cursor_pos);
+                                let cols = units.width(&buffer[cursor_pos..end]);
+                                cursor_pos = end;
+                                if draw_inline {
+                                    let mut stderr = output::tty();
+                                    if cols == 1 {
+                                        let _ = stderr.write_all(CURSOR_RIGHT);
+                                    } else {
+                                        output::cursor_right_n(&mut stderr, cols);
+                                    }
+                                    let _ = stderr.flush();
+                                }
+                            }
                         }
-                        let removed = old_cursor - new_pos;
-                        buffer.drain(new_pos..old_cursor);
-                        cursor_pos = new_pos;
-                        num_read -= removed;
-                        if !flags.silent {
-                            output::redraw_input(&buffer, cursor_pos, old_cursor);
+                    }
+                    KeyInput::Home => {
+                        if cursor_pos > 0 {
+                            if draw_inline {
+                                let mut stderr = output::tty();
+                                output::to_origin(&mut stderr, units.width(&buffer[..cursor_pos]));
+                                let _ = stderr.flush();
+                            }
+                            cursor_pos = 0;
                         }
                     }
-                }
-                KeyInput::Enter => {
-                    // Default on Enter as first input
-                    if flags.dflt && num_read == 0 {
-                        if let Some(ref ds) = default_string {
-                            if flags.json.is_some() {
-                                let ec = ds.len() as i32;
-                                emit_json_and_exit(&flags, ds, ec, "default", "normal", false, true, None, None, output_to_stderr, &orig_termios);
+                    KeyInput::End => {
+                        if cursor_pos < buffer.len() {
+                            let delta = units.width(&buffer[cursor_pos..]);
+                            if draw_inline {
+                                let mut stderr = output::tty();
+                                output::cursor_right_n(&mut stderr, delta);
+                                let _ = stderr.flush();
                             }
-                            output::handle_default(ds, &flags, output_to_stderr);
-                            output::trailing_newline_if(&flags);
-                            term::restore_term(&orig_termios);
-                            process::exit(EXIT_STAT.load(Ordering::Relaxed));
+                            cursor_pos = buffer.len();
                         }
                     }
-                    if flags.ret_key {
-                        break 'outer;
+                    KeyInput::KillToEnd => {
+                        let removed = units.count(&buffer[cursor_pos..]);
+                        if removed > 0 {
+                            buffer.truncate(cursor_pos);
+                            num_read -= removed;
+                            if draw_inline {
+                                let _ = output::tty().write_all(CLEAR_TO_EOL);
+                                let _ = output::tty().flush();
+                            }
+                        }
                     }
-                    // Treat newline as a regular char subject to -c/-C filtering
-                    if apply_char_filters('\n', &flags, &valid_pattern, &exclude_pattern).is_none() {
-                        continue;
+                    KeyInput::KillToStart => {
+                        if cursor_pos > 0 {
+                            let prev_col = units.width(&buffer[..cursor_pos]);
+                            num_read -= units.count(&buffer[..cursor_pos]);
+                            buffer.drain(..cursor_pos);
+                            cursor_pos = 0;
+                            if draw_inline {
+                                output::redraw_input(&shown(&buffer), units.width(&buffer), prev_col);
+                            }
+                        }
                     }
-                    buffer.insert(cursor_pos, b'\n');
-                    cursor_pos += 1;
-                    num_read += 1;
-                    if !flags.silent {
-                        output::redraw_input(&buffer, cursor_pos, cursor_pos - 1);
+                    KeyInput::KillWordBack => {
+                        for _ in 0..repeat {
+                            if cursor_pos > 0 {
+                                let old_cursor = cursor_pos;
+                                // Skip whitespace backward
+                                let mut new_pos = cursor_pos;
+                                while new_pos > 0 && buffer[new_pos - 1] == b' ' {
+                                    new_pos -= 1;
+                                }
+                                // Skip non-whitespace backward
+                                while new_pos > 0 && buffer[new_pos - 1] != b' ' {
+                                    new_pos -= 1;
+                                }
+                                let prev_col = units.width(&buffer[..old_cursor]);
+                                num_read -= units.count(&buffer[new_pos..old_cursor]);
+                                buffer.drain(new_pos..old_cursor);
+                                cursor_pos = new_pos;
+                                if draw_inline {
+                                    output::redraw_input(&shown(&buffer), units.width(&buffer[cursor_pos..]), prev_col);
+                                }
+                            }
+                        }
                     }
-                }
-                KeyInput::Escape => {
-                    if let Some(n) = flags.esc_code {
-                        if n > 0 {
-                            if flags.json.is_some() {
-                                emit_json_and_exit(&flags, "", n, "cancelled", "normal", false, false, None, None, output_to_stderr, &orig_termios);
+                    KeyInput::KillWordForward => {
+                        for _ in 0..repeat {
+                            if cursor_pos < buffer.len() {
+                                // Skip whitespace, then the word, forward
+                                let mut end = cursor_pos;
+                                while end < buffer.len() && buffer[end] == b' ' {
+                                    end += 1;
+                                }
+                                while end < buffer.len() && buffer[end] != b' ' {
+                                    end += 1;
+                                }
+                                num_read -= units.count(&buffer[cursor_pos..end]);
+                                buffer.drain(cursor_pos..end);
+                                if draw_inline {
+                                    let col = units.width(&buffer[..cursor_pos]);
+                                    output::redraw_input(&shown(&buffer), units.width(&buffer[cursor_pos..]), col);
+                                }
                             }
-                            output::trailing_newline_if(&flags);
-                            term::restore_term(&orig_termios);
-                            process::exit(n);
                         }
-                        // Some(0) = no-op; fall through
                     }
-                    // None = original behavior: no-op in normal mode
-                }
-                KeyInput::Up | KeyInput::Down | KeyInput::Tab | KeyInput::Unknown => {}
-            }
-        } else {
-            // Non-edit mode: Char, Backspace (raw), and Enter
-            match key {
-                KeyInput::Char(b) => {
-                    let ch = b as char;
-                    // Default on Enter as first char
-                    if ch == '\n' && flags.dflt && num_read == 0 {
-                        if let Some(ref ds) = default_string {
-                            if flags.json.is_some() {
-                                let ec = ds.len() as i32;
-                                emit_json_and_exit(&flags, ds, ec, "default", "normal", false, true, None, None, output_to_stderr, &orig_termios);
+                    KeyInput::Enter => {
+                        // Default on Enter as first input (with --edit-default
+                        // the default is already in the buffer, so Enter just accepts it)
+                        if flags.dflt && num_read == 0 && paragraph.is_empty() && !flags.edit_default {
+                            if let Some(ref ds) = default_string {
+                                if flags.reports_outcome() {
+                                    let ec = ds.len() as i32;
+                                    report_outcome(&flags, &flags.post.apply(ds), ec, "default", "normal", false, true, None, None, output_to_stderr, &orig_termios);
+                                }
+                                output::handle_default(ds, &flags, output_to_stderr);
+                                output::trailing_newline_if(&flags);
+                                term::restore_term(&orig_termios);
+                                process::exit(EXIT_STAT.load(Ordering::Relaxed));
+                            }
+                        }
+                        if flags.paragraph {
+                            // Enter on an empty line is the second newline in a row
+                            if buffer.is_empty() {
+                                break 'outer;
+                            }
+                            if draw_inline {
+                                let mut stderr = output::tty();
+                                if cursor_pos < buffer.len() {
+                                    output::cursor_right_n(&mut stderr, units.width(&buffer[cursor_pos..]));
+                                }
+                                let _ = stderr.write_all(b"\r\n");
+                                let _ = stderr.flush();
+                            }
+                            paragraph.append(&mut buffer);
+                            paragraph.push(b'\n');
+                            cursor_pos = 0;
+                            num_read = 0;
+                            continue;
+                        }
+                        if flags.ret_key || flags.edit_default {
+                            break 'outer;
+                        }
+                        if num_read >= how_many {
+                            continue;
+                        }
+                        // Treat newline as a regular char subject to -c/-C filtering
+                        if apply_char_filters('\n', &flags, &valid_pattern, &exclude_pattern).is_none() {
+                            continue;
+                        }
+                        if input_cap_reached(&flags, buffer.len(), 1) {
+                            continue;
+                        }
+                        let prev_col = units.width(&buffer[..cursor_pos]);
+                        buffer.insert(cursor_pos, b'\n');
+                        cursor_pos += 1;
+                        num_read += 1;
+                        if draw_inline {
+                            output::redraw_input(&shown(&buffer), units.width(&buffer[cursor_pos..]), prev_col);
+                        }
+                    }
+                    KeyInput::Escape => {
+                        if let Some(n) = escape_exit(&flags, None) {
+                            if flags.reports_outcome() {
+                                report_outcome(&flags, "", n, "cancelled", "normal", false, false, None, None, output_to_stderr, &orig_termios);
                             }
-                            output::handle_default(ds, &flags, output_to_stderr);
+                            output::emit_cancel(&flags, output_to_stderr);
                             output::trailing_newline_if(&flags);
                             term::restore_term(&orig_termios);
-                            process::exit(EXIT_STAT.load(Ordering::Relaxed));
+                            process::exit(n);
                         }
+                        // -B0, or no -B: no-op in normal mode (original behavior)
                     }
-                    if ch == '\n' && flags.ret_key {
-                        break 'outer;
-                    }
-                    let ch = match apply_char_filters(ch, &flags, &valid_pattern, &exclude_pattern) {
-                        Some(c) => c,
-                        None => continue,
-                    };
-                    buffer.push(ch as u8);
-                    num_read += 1;
+                    // A paste is typed key by key, up to what -n has room for
+                    KeyInput::Paste(text) => input::type_paste(&text),
+                    KeyInput::Up | KeyInput::Down | KeyInput::Tab | KeyInput::Count(_) | KeyInput::CtrlD | KeyInput::Function(_) | KeyInput::Unknown => {}
+                }
+                if let Some(ref mut f) = field {
                     if !flags.silent {
-                        output::output_char(ch, output_to_stderr, flags.both);
+                        draw_field(f, &buffer, cursor_pos, revealed);
                     }
                 }
-                KeyInput::Backspace => {
-                    // -E0: no editing — backspace is a raw byte (0x7F), not an erase
-                    buffer.push(0x7F);
-                    num_read += 1;
-                }
-                KeyInput::Enter => {
-                    if flags.dflt && num_read == 0 {
-                        if let Some(ref ds) = default_string {
-                            if flags.json.is_some() {
-                                let ec = ds.len() as i32;
-                                emit_json_and_exit(&flags, ds, ec, "default", "normal", false, true, None, None, output_to_stderr, &orig_termios);
+            } else {
+                // Non-edit mode: Char, Backspace (raw), and Enter
+                match key {
+                    KeyInput::Char(b) => {
+                        if non_ascii_rejected(&flags, stdin_fd, b) {
+                            continue;
+                        }
+                        let ch = b as char;
+                        // Default on Enter as first char
+                        if ch == '\n' && flags.dflt && num_read == 0 {
+                            if let Some(ref ds) = default_string {
+                                if flags.reports_outcome() {
+                                    let ec = ds.len() as i32;
+                                    report_outcome(&flags, &flags.post.apply(ds), ec, "default", "normal", false, true, None, None, output_to_stderr, &orig_termios);
+                                }
+                                output::handle_default(ds, &flags, output_to_stderr);
+                                output::trailing_newline_if(&flags);
+                                term::restore_term(&orig_termios);
+                                process::exit(EXIT_STAT.load(Ordering::Relaxed));
                             }
-                            output::handle_default(ds, &flags, output_to_stderr);
-                            output::trailing_newline_if(&flags);
-                            term::restore_term(&orig_termios);
-                            process::exit(EXIT_STAT.load(Ordering::Relaxed));
+                        }
+                        if ch == '\n' && flags.ret_key {
+                            break 'outer;
+                        }
+                        if flags.graphemes {
+                            let cluster = input::read_cluster(stdin_fd, b);
+                            let unit = match apply_cluster_filters(cluster, &flags, &valid_pattern, &exclude_pattern) {
+                                Some(u) => u,
+                                None => continue,
+                            };
+                            if input_cap_reached(&flags, buffer.len(), unit.len()) {
+                                continue;
+                            }
+                            if !flags.silent && !defers_output(&flags) {
+                                output::output_str(&String::from_utf8_lossy(&unit), output_to_stderr, flags.both);
+                            }
+                            buffer.extend(unit);
+                            num_read += 1;
+                            continue;
+                        }
+                        let ch = match apply_char_filters(ch, &flags, &valid_pattern, &exclude_pattern) {
+                            Some(c) => c,
+                            None => continue,
+                        };
+                        if input_cap_reached(&flags, buffer.len(), 1) {
+                            continue;
+                        }
+                        buffer.push(ch as u8);
+                        num_read += 1;
+                        if !flags.silent && !defers_output(&flags) {
+                            output::output_char(ch, output_to_stderr, flags.both);
                         }
                     }
-                    if flags.ret_key {
-                        break 'outer;
+                    KeyInput::Backspace => {
+                        // -E0: no editing — backspace is a raw byte (0x7F, or
+                        // --raw-backspace-byte), not an erase
+                        if input_cap_reached(&flags, buffer.len(), 1) {
+                            continue;
+                        }
+                        buffer.push(flags.raw_backspace);
+                        num_read += 1;
                     }
-                }
-                KeyInput::Escape => {
-                    if let Some(n) = flags.esc_code {
-                        if n > 0 {
-                            if flags.json.is_some() {
-                                emit_json_and_exit(&flags, "", n, "cancelled", "normal", false, false, None, None, output_to_stderr, &orig_termios);
+                    KeyInput::Enter => {
+                        if flags.dflt && num_read == 0 {
+                            if let Some(ref ds) = default_string {
+                                if flags.reports_outcome() {
+                                    let ec = ds.len() as i32;
+                                    report_outcome(&flags, &flags.post.apply(ds), ec, "default", "normal", false, true, None, None, output_to_stderr, &orig_termios);
+                                }
+                                output::handle_default(ds, &flags, output_to_stderr);
+                                output::trailing_newline_if(&flags);
+                                term::restore_term(&orig_termios);
+                                process::exit(EXIT_STAT.load(Ordering::Relaxed));
+                            }
+                        }
+                        if flags.ret_key {
+                            break 'outer;
+                        }
+                    }
+                    KeyInput::Escape => {
+                        if let Some(n) = escape_exit(&flags, None) {
+                            if flags.reports_outcome() {
+                                report_outcome(&flags, "", n, "cancelled", "normal", false, false, None, None, output_to_stderr, &orig_termios);
                             }
+                            output::emit_cancel(&flags, output_to_stderr);
                             output::trailing_newline_if(&flags);
                             term::restore_term(&orig_termios);
                             process::exit(n);
                         }
-                        // Some(0) = no-op; fall through
+                        // -B0, or no -B: no-op in normal mode (original behavior)
+                    }
+                    KeyInput::Paste(text) => input::type_paste(&text),
+                    _ => {} // Arrow keys etc. silently ignored
+                }
+            }
+
+            if revalidate && num_read < how_many {
+                revalidate = false;
+            }
+        }
+
+        // --paragraph: join the finished lines and the last one, dropping
+        // the newline that ended the final non-empty line
+        if !paragraph.is_empty() {
+            if buffer.is_empty() {
+                paragraph.pop();
+            }
+            paragraph.append(&mut buffer);
+            buffer = std::mem::take(&mut paragraph);
+            cursor_pos = buffer.len();
+            num_read = units.count(&buffer);
+        }
+
+        if whole_pattern.is_some() || flags.range.is_some() {
+            let kept = strip_trailing(&buffer, &flags);
+            let s = String::from_utf8_lossy(kept).into_owned();
+            let valid = whole_pattern.as_ref().is_none_or(|re| re.is_match(&s))
+                && flags.range.is_none_or(|range| range.contains(kept));
+            if !valid {
+                // --reprompt-text: leave the failed attempt and a hint above,
+                // and start over on a new line with the input kept
+                if let Some((text, prompt_on_stderr)) = flags.reprompt().filter(|_| !input_closed) {
+                    if !flags.silent {
+                        let mut stderr = output::tty();
+                        match field {
+                            Some(ref mut f) => f.leave(),
+                            None if cursor_pos < buffer.len() => output::cursor_right_n(&mut stderr, units.width(&buffer[cursor_pos..])),
+                            None => {}
+                        }
+                        let _ = stderr.write_all(b" (invalid)\r\n");
+                        let _ = stderr.flush();
+                        output::output_str(text, prompt_on_stderr, false);
+                        cursor_pos = buffer.len();
+                        match field {
+                            Some(ref mut f) => draw_field(f, &buffer, cursor_pos, None),
+                            None => output::redraw_input(&shown(&buffer), 0, 0),
+                        }
+                    }
+                    cursor_pos = buffer.len();
+                    revalidate = true;
+                    continue 'edit;
+                }
+                if flags.reprompt_on_invalid && !input_closed {
+                    // Move to end of buffer and show a hint until the next key
+                    if draw_inline {
+                        let mut stderr = output::tty();
+                        if cursor_pos < buffer.len() {
+                            output::cursor_right_n(&mut stderr, units.width(&buffer[cursor_pos..]));
+                        }
+                        output::show_hint(&mut stderr, " (invalid)");
                     }
-                    // None = original behavior: no-op in normal mode
+                    cursor_pos = buffer.len();
+                    invalid_shown = true;
+                    revalidate = true;
+                    continue 'edit;
+                }
+                if draw_inline && !buffer.is_empty() {
+                    let mut stderr = output::tty();
+                    output::to_origin(&mut stderr, units.width(&buffer[..cursor_pos]));
+                    let _ = stderr.write_all(CLEAR_TO_EOL);
+                    let _ = stderr.flush();
                 }
-                _ => {} // Arrow keys etc. silently ignored
+                if flags.reports_outcome() {
+                    report_outcome(&flags, &s, 255, "invalid", "normal", false, false, None, None, output_to_stderr, &orig_termios);
+                }
+                output::trailing_newline_if(&flags);
+                EXIT_STAT.store(255, Ordering::Relaxed);
+                term::restore_term(&orig_termios);
+                process::exit(255);
             }
         }
+        break;
+    }
+
+    if flags.strip.is_some() {
+        let kept = strip_trailing(&buffer, &flags).len();
+        buffer.truncate(kept);
+        num_read = units.count(&buffer);
     }
 
     let ec = num_read as i32;
-    if flags.json.is_some() {
-        let val = String::from_utf8_lossy(&buffer).into_owned();
-        emit_json_and_exit(&flags, &val, ec, "ok", "normal", false, false, None, None, output_to_stderr, &orig_termios);
+    output::beep_on_complete(&flags);
+    if flags.reports_outcome() {
+        let val = buffer_text(&buffer, &flags);
+        report_outcome(&flags, &val, ec, "ok", "normal", false, false, None, None, output_to_stderr, &orig_termios);
     }
 
-    // In erase mode, write the final buffer to primary output
-    if erase_active && !flags.silent {
-        let s = String::from_utf8_lossy(&buffer);
-        output::output_str(&s, output_to_stderr, flags.both);
+    // In erase mode, write the final buffer to primary output.  With
+    // --hex-output or --post nothing was written as it was typed, so
+    // always write it.
+    if (erase_active || defers_output(&flags)) && !flags.silent && !buffer.is_empty() {
+        output_buffer(&buffer, &flags, output_to_stderr);
     }
+    output::echo_result(&buffer_text(&buffer, &flags), &flags);
 
     output::trailing_newline_if(&flags);
     EXIT_STAT.store(ec, Ordering::Relaxed);
@@ -14,14 +14,15 @@
 
 //! Mask mode: positional input validation via `-m <mask>`.
 
+use std::fmt;
 use std::io::{self, Write};
-use std::process;
 use std::sync::atomic::Ordering;
 
 use crate::input::{self, KeyInput};
-use crate::output::{self, CURSOR_LEFT, CLEAR_TO_EOL};
-use crate::{apply_char_filters, Flags, TIMED_OUT};
+use crate::output::{self, CURSOR_LEFT, CLEAR_TO_EOL, COLOR_OFF};
+use crate::{apply_char_filters, ctrl_d_key, escape_exit, input_cap_reached, non_ascii_rejected, Flags, TIMED_OUT};
 
+#[derive(Debug)]
 pub enum MaskClass {
     Upper,       // U - uppercase letter
     Lower,       // l - lowercase letter
@@ -35,20 +36,75 @@ pub enum MaskClass {
     Literal(char),        // literal character (auto-inserted)
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Quantifier {
     One,      // exactly one (default, current behavior)
     Star,     // * — zero or more
     Plus,     // + — one or more
     Optional, // ? — zero or one
+    Range { min: usize, max: Option<usize> }, // {n}, {n,}, {n,m}
 }
 
+impl Quantifier {
+    /// The fewest characters the element needs.
+    fn min(self) -> usize {
+        match self {
+            Quantifier::One | Quantifier::Plus => 1,
+            Quantifier::Star | Quantifier::Optional => 0,
+            Quantifier::Range { min, .. } => min,
+        }
+    }
+
+    /// The most characters the element takes; `None` when unbounded.
+    fn max(self) -> Option<usize> {
+        match self {
+            Quantifier::One | Quantifier::Optional => Some(1),
+            Quantifier::Star | Quantifier::Plus => None,
+            Quantifier::Range { max, .. } => max,
+        }
+    }
+}
+
+#[derive(Debug)]
 pub struct MaskElement {
     pub class: MaskClass,
     pub quantifier: Quantifier,
 }
 
-pub fn parse_mask(mask_str: &str) -> Vec<MaskElement> {
+/// Why a mask failed to parse, and where: `pos` is the character offset of
+/// the offending quantifier or of the `[` or `{` that opens the bad text.
+#[derive(Debug)]
+pub struct MaskError {
+    pub pos: usize,
+    pub kind: MaskErrorKind,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MaskErrorKind {
+    UnexpectedQuantifier(char),        // at the start or after another quantifier
+    QuantifiedLiteral(char),           // `-*`: literals can't repeat
+    UnclosedBracket,                   // `[` with no `]`
+    UnclosedBrace,                     // `{` with no `}`
+    InvalidRepeat(String),             // `{3,1}`, `{x}`: the braced text
+    InvalidClass(String, regex::Error), // the `[...]` text and why regex rejected it
+}
+
+impl fmt::Display for MaskError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            MaskErrorKind::UnexpectedQuantifier(q) => write!(f, "unexpected quantifier '{}' at position {} in mask", q, self.pos),
+            MaskErrorKind::QuantifiedLiteral(q) => write!(f, "quantifier '{}' cannot be applied to a literal character", q),
+            MaskErrorKind::UnclosedBracket => write!(f, "unclosed '[' in mask"),
+            MaskErrorKind::UnclosedBrace => write!(f, "unclosed '{{' at position {} in mask", self.pos),
+            MaskErrorKind::InvalidRepeat(text) => write!(f, "invalid repeat count '{}' at position {} in mask", text, self.pos),
+            MaskErrorKind::InvalidClass(expr, e) => write!(f, "invalid character class '{}': {}", expr, e),
+        }
+    }
+}
+
+/// Parse a `-m` mask into its elements.  Errors are returned rather than
+/// reported, so the caller decides how to show them.
+pub fn parse_mask(mask_str: &str) -> Result<Vec<MaskElement>, MaskError> {
     let mut elements = Vec::new();
     let chars: Vec<char> = mask_str.chars().collect();
     let mut i = 0;
@@ -56,8 +112,7 @@ pub fn parse_mask(mask_str: &str) -> Vec<MaskElement> {
         let ch = chars[i];
         // Quantifiers at the start or after another quantifier are invalid
         if ch == '*' || ch == '+' || ch == '?' {
-            eprintln!("-m option: unexpected quantifier '{}' at position {} in mask", ch, i);
-            process::exit(255);
+            return Err(MaskError { pos: i, kind: MaskErrorKind::UnexpectedQuantifier(ch) });
         }
         let is_literal;
         if ch == '\\' {
@@ -79,15 +134,14 @@ pub fn parse_mask(mask_str: &str) -> Vec<MaskElement> {
                 i += 1;
             }
             if i >= chars.len() {
-                eprintln!("-m option: unclosed '[' in mask");
-                process::exit(255);
+                return Err(MaskError { pos: start, kind: MaskErrorKind::UnclosedBracket });
             }
             let bracket_expr: String = chars[start..=i].iter().collect();
             let pattern = format!("^{}$", bracket_expr);
-            let re = regex::Regex::new(&pattern).unwrap_or_else(|e| {
-                eprintln!("-m option: invalid character class '{}': {}", bracket_expr, e);
-                process::exit(255);
-            });
+            let re = match regex::Regex::new(&pattern) {
+                Ok(re) => re,
+                Err(e) => return Err(MaskError { pos: start, kind: MaskErrorKind::InvalidClass(bracket_expr, e) }),
+            };
             elements.push(MaskElement { class: MaskClass::Custom(re), quantifier: Quantifier::One });
             is_literal = false;
         } else {
@@ -109,8 +163,7 @@ pub fn parse_mask(mask_str: &str) -> Vec<MaskElement> {
         // Check for quantifier suffix
         if i < chars.len() && (chars[i] == '*' || chars[i] == '+' || chars[i] == '?') {
             if is_literal {
-                eprintln!("-m option: quantifier '{}' cannot be applied to a literal character", chars[i]);
-                process::exit(255);
+                return Err(MaskError { pos: i, kind: MaskErrorKind::QuantifiedLiteral(chars[i]) });
             }
             let q = match chars[i] {
                 '*' => Quantifier::Star,
@@ -120,9 +173,43 @@ pub fn parse_mask(mask_str: &str) -> Vec<MaskElement> {
             };
             elements.last_mut().unwrap().quantifier = q;
             i += 1;
+        } else if !is_literal && i < chars.len() && chars[i] == '{' {
+            // Repeat count; after a literal, `{` stays a literal itself
+            let start = i;
+            let Some(len) = chars[i..].iter().position(|&c| c == '}') else {
+                return Err(MaskError { pos: start, kind: MaskErrorKind::UnclosedBrace });
+            };
+            let text: String = chars[start..=start + len].iter().collect();
+            let q = parse_repeat(&text[1..text.len() - 1])
+                .ok_or_else(|| MaskError { pos: start, kind: MaskErrorKind::InvalidRepeat(text.clone()) })?;
+            elements.last_mut().unwrap().quantifier = q;
+            i = start + len + 1;
+        }
+        // A second quantifier, braced or not, is an error too; `n*{2}`
+        // would otherwise read `{2}` as three literals
+        if i < chars.len() && chars[i] == '{' && elements.last().is_some_and(|e| e.quantifier != Quantifier::One) {
+            return Err(MaskError { pos: i, kind: MaskErrorKind::UnexpectedQuantifier('{') });
+        }
+    }
+    Ok(elements)
+}
+
+/// The inside of `{n}`, `{n,}`, or `{n,m}`.  `None` unless the counts are
+/// digits with `n <= m`, and something can be typed.
+fn parse_repeat(inner: &str) -> Option<Quantifier> {
+    let count = |s: &str| if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) { s.parse::<usize>().ok() } else { None };
+    let (min, max) = match inner.split_once(',') {
+        None => {
+            let n = count(inner)?;
+            (n, Some(n))
         }
+        Some((lo, "")) => (count(lo)?, None),
+        Some((lo, hi)) => (count(lo)?, Some(count(hi)?)),
+    };
+    match max {
+        Some(m) if m < min || m == 0 => None,
+        _ => Some(Quantifier::Range { min, max }),
     }
-    elements
 }
 
 fn mask_char_matches(class: &MaskClass, ch: char) -> bool {
@@ -140,6 +227,58 @@ fn mask_char_matches(class: &MaskClass, ch: char) -> bool {
     }
 }
 
+/// The mask syntax for one element, for messages.
+fn class_text(class: &MaskClass) -> String {
+    match class {
+        MaskClass::Upper => "U".to_string(),
+        MaskClass::Lower => "l".to_string(),
+        MaskClass::Alpha => "c".to_string(),
+        MaskClass::Digit => "n".to_string(),
+        MaskClass::Hex => "x".to_string(),
+        MaskClass::Punct => "p".to_string(),
+        MaskClass::Whitespace => "W".to_string(),
+        MaskClass::Any => ".".to_string(),
+        MaskClass::Custom(re) => re.as_str().trim_start_matches('^').trim_end_matches('$').to_string(),
+        MaskClass::Literal(l) => l.to_string(),
+    }
+}
+
+/// Mask elements that no keystroke can fill once `-c`/`-C` and `-U`/`-L`
+/// have had their say, e.g. `n` under `-c '[a-z]'`.  Each printable ASCII
+/// character is tried against the filters and then the element; a custom
+/// class that matches no ASCII character at all can't be probed this way
+/// and is left alone.  Literals only count with `--no-auto-literal`, when
+/// the user has to type them.  Returns one message per element.
+pub fn find_conflicts(
+    mask: &[MaskElement],
+    flags: &Flags,
+    valid_pattern: &Option<regex::Regex>,
+    exclude_pattern: &Option<regex::Regex>,
+) -> Vec<String> {
+    let probe = || (0x20u8..=0x7E).map(char::from);
+    let mut conflicts = Vec::new();
+    for (pos, element) in mask.iter().enumerate() {
+        if matches!(element.class, MaskClass::Literal(_)) && !flags.no_auto_literal {
+            continue;
+        }
+        if !probe().any(|c| mask_char_matches(&element.class, c)) {
+            continue;
+        }
+        let typeable = probe().any(|c| {
+            apply_char_filters(c, flags, valid_pattern, exclude_pattern)
+                .is_some_and(|m| mask_char_matches(&element.class, m))
+        });
+        if !typeable {
+            conflicts.push(format!(
+                "element {} ('{}') accepts no character that -c/-C/-U/-L allow",
+                pos + 1,
+                class_text(&element.class)
+            ));
+        }
+    }
+    conflicts
+}
+
 /// Get the current mask element index and how many chars have been consumed at that index.
 fn current_mask_state(_mask: &[MaskElement], mask_map: &[usize]) -> (usize, usize) {
     if mask_map.is_empty() {
@@ -153,12 +292,13 @@ fn current_mask_state(_mask: &[MaskElement], mask_map: &[usize]) -> (usize, usiz
 
 /// Try to advance from `from_idx` to find a mask element that accepts `ch`.
 /// Skips literals (they're auto-inserted) and zero-minimum elements that don't match.
+/// With `--no-auto-literal` a literal is typed like any required element.
 /// Returns the mask element index that accepts `ch`, or None.
-fn try_advance(mask: &[MaskElement], from_idx: usize, ch: char) -> Option<usize> {
+fn try_advance(mask: &[MaskElement], from_idx: usize, ch: char, auto_literal: bool) -> Option<usize> {
     let mut idx = from_idx;
     while idx < mask.len() {
         // Skip literals — they get auto-inserted, not typed
-        if let MaskClass::Literal(_) = mask[idx].class {
+        if let (MaskClass::Literal(_), true) = (&mask[idx].class, auto_literal) {
             idx += 1;
             continue;
         }
@@ -166,7 +306,7 @@ fn try_advance(mask: &[MaskElement], from_idx: usize, ch: char) -> Option<usize>
             return Some(idx);
         }
         // Can we skip this element? Only if min is 0
-        let can_skip = matches!(mask[idx].quantifier, Quantifier::Star | Quantifier::Optional);
+        let can_skip = mask[idx].quantifier.min() == 0;
         if can_skip {
             idx += 1;
         } else {
@@ -177,40 +317,59 @@ fn try_advance(mask: &[MaskElement], from_idx: usize, ch: char) -> Option<usize>
 }
 
 /// Check if the mask is satisfied — all required elements have their minimums met.
-fn mask_satisfied(mask: &[MaskElement], mask_map: &[usize]) -> bool {
+/// Literals count only with `--no-auto-literal`, where the user types them.
+fn mask_satisfied(mask: &[MaskElement], mask_map: &[usize], auto_literal: bool) -> bool {
     for (idx, elem) in mask.iter().enumerate() {
-        if let MaskClass::Literal(_) = elem.class {
+        if let (MaskClass::Literal(_), true) = (&elem.class, auto_literal) {
             continue; // literals are auto-inserted
         }
         let count = mask_map.iter().filter(|&&x| x == idx).count();
-        let min = match elem.quantifier {
-            Quantifier::One => 1,
-            Quantifier::Plus => 1,
-            Quantifier::Star => 0,
-            Quantifier::Optional => 0,
-        };
-        if count < min {
+        if count < elem.quantifier.min() {
             return false;
         }
     }
     true
 }
 
-/// Check if the mask has any unbounded quantifiers (Star or Plus).
+/// Check if the mask has any element that needs Enter to finish: an
+/// unbounded one (`*`, `+`, `{n,}`) or a range like `{2,4}`.
 fn mask_has_unbounded(mask: &[MaskElement]) -> bool {
-    mask.iter().any(|e| matches!(e.quantifier, Quantifier::Star | Quantifier::Plus))
+    mask.iter().any(|e| match e.quantifier {
+        Quantifier::Range { min, max } => max != Some(min),
+        q => q.max().is_none(),
+    })
 }
 
 
+/// Draw an inserted literal, in the `--literal-color` if there is one.
+/// The color codes take no columns, so Backspace still steps back one
+/// column per literal.
+fn write_literal(l: char, flags: &Flags) {
+    let mut stderr = output::tty();
+    match flags.literal_color {
+        Some(n) => {
+            let _ = write!(stderr, "\x1b[38;5;{}m{}", n, l);
+            let _ = stderr.write_all(COLOR_OFF);
+        }
+        None => {
+            let _ = write!(stderr, "{}", l);
+        }
+    }
+}
+
 /// Auto-insert consecutive literal elements starting from `from_idx`.
-/// Returns number of literals inserted.
+/// Returns number of literals inserted (always 0 with `--no-auto-literal`).
 fn mask_auto_insert_literals(
     mask: &[MaskElement],
     buffer: &mut Vec<u8>,
     mask_map: &mut Vec<usize>,
     from_idx: usize,
-    silent: bool,
+    flags: &Flags,
 ) -> usize {
+    if flags.no_auto_literal {
+        return 0;
+    }
+    let silent = flags.silent;
     let mut count = 0;
     let mut idx = from_idx;
     while idx < mask.len() {
@@ -219,7 +378,7 @@ fn mask_auto_insert_literals(
             mask_map.push(idx);
             count += 1;
             if !silent {
-                eprint!("{}", l);
+                write_literal(l, flags);
             }
             idx += 1;
         } else {
@@ -227,11 +386,27 @@ fn mask_auto_insert_literals(
         }
     }
     if count > 0 && !silent {
-        let _ = io::stderr().flush();
+        let _ = output::tty().flush();
     }
     count
 }
 
+/// The buffer as output: everything, or with `--mask-values-only` just
+/// the characters typed into non-literal elements.  The literals were
+/// only for display.
+fn output_value(mask: &[MaskElement], buffer: &[u8], mask_map: &[usize], flags: &Flags) -> String {
+    if !flags.mask_values_only {
+        return String::from_utf8_lossy(buffer).into_owned();
+    }
+    let kept: Vec<u8> = buffer
+        .iter()
+        .zip(mask_map)
+        .filter(|&(_, &idx)| !matches!(mask[idx].class, MaskClass::Literal(_)))
+        .map(|(&b, _)| b)
+        .collect();
+    String::from_utf8_lossy(&kept).into_owned()
+}
+
 pub struct MaskResult {
     pub exit_code: i32,
     pub value: String,
@@ -240,6 +415,7 @@ pub struct MaskResult {
     pub default_used: bool,
 }
 
+#[allow(clippy::collapsible_if, clippy::collapsible_match)]
 pub fn run_mask_mode(
     mask: &[MaskElement],
     flags: &Flags,
@@ -252,11 +428,24 @@ pub fn run_mask_mode(
     let mut buffer: Vec<u8> = Vec::new();
     let mut mask_map: Vec<usize> = Vec::new();
     let has_unbounded = mask_has_unbounded(mask);
+    let auto_literal = !flags.no_auto_literal;
 
     // Auto-insert any leading literals
-    mask_auto_insert_literals(mask, &mut buffer, &mut mask_map, 0, flags.silent);
+    mask_auto_insert_literals(mask, &mut buffer, &mut mask_map, 0, flags);
 
     loop {
+        // A repeat count ending the mask completes it once full, as `c{2,8}`
+        // does on the eighth letter, whatever the quantifiers before it
+        if let Some(&idx) = mask_map.last() {
+            let (_, count) = current_mask_state(mask, &mask_map);
+            if idx == mask.len() - 1
+                && matches!(mask[idx].quantifier, Quantifier::Range { max: Some(max), .. } if max == count)
+                && mask_satisfied(mask, &mask_map, auto_literal)
+            {
+                break;
+            }
+        }
+
         // Check if mask is complete (all fixed-length elements filled, no unbounded)
         if !has_unbounded {
             // For fixed masks: check if we've reached the end
@@ -266,18 +455,22 @@ pub fn run_mask_mode(
                 mask.is_empty() || mask.iter().all(|e| matches!(e.class, MaskClass::Literal(_)))
             } else {
                 // Current element is past the last mask element
-                idx >= mask.len() - 1 && count >= 1 && mask[idx].quantifier == Quantifier::One
-                    && idx == mask.len() - 1
+                idx == mask.len() - 1
+                    && mask[idx].quantifier != Quantifier::Optional
+                    && mask[idx].quantifier.max() == Some(count)
             };
-            // More precise: are ALL elements at their exact-one count?
-            if !has_unbounded && buffer.len() >= mask.len()
-                && mask.iter().all(|e| e.quantifier == Quantifier::One)
-            {
+            // More precise: are ALL elements at their exact count?
+            let fixed_len: Option<usize> = mask.iter().map(|e| match e.quantifier {
+                Quantifier::One => Some(1),
+                Quantifier::Range { min, max } if max == Some(min) => Some(min),
+                _ => None,
+            }).sum();
+            if fixed_len.is_some_and(|n| mask_map.len() >= n) {
                 break;
             }
             if past_end && !has_unbounded {
                 // Verify all elements are satisfied
-                if mask_satisfied(mask, &mask_map) {
+                if mask_satisfied(mask, &mask_map, auto_literal) {
                     break;
                 }
             }
@@ -291,18 +484,19 @@ pub fn run_mask_mode(
                         output::handle_default(ds, flags, output_to_stderr);
                     }
                     let ec = ds.len() as i32;
-                    return MaskResult { exit_code: ec, value: ds.clone(), status: "default", timed_out: true, default_used: true };
+                    return MaskResult { exit_code: ec, value: flags.post.apply(ds), status: "default", timed_out: true, default_used: true };
                 }
             }
             // Output partial buffer
             if !buffer.is_empty() && flags.json.is_none() {
-                let s = String::from_utf8_lossy(&buffer);
+                let s = flags.post.apply(&output_value(mask, &buffer, &mask_map, flags));
                 output::output_str(&s, output_to_stderr, flags.both);
             }
-            return MaskResult { exit_code: 254, value: String::new(), status: "timeout", timed_out: true, default_used: false };
+            return MaskResult { exit_code: flags.timeout_code, value: String::new(), status: "timeout", timed_out: true, default_used: false };
         }
 
         let key = match input::read_key(stdin_fd) {
+            Ok(KeyInput::CtrlD) => ctrl_d_key(flags, buffer.is_empty()).unwrap_or(KeyInput::Enter),
             Ok(k) => k,
             Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
             Err(_) => break,
@@ -310,22 +504,23 @@ pub fn run_mask_mode(
 
         match key {
             KeyInput::Char(b) => {
+                if non_ascii_rejected(flags, stdin_fd, b) {
+                    continue;
+                }
                 let ch = match apply_char_filters(b as char, flags, valid_pattern, exclude_pattern) {
                     Some(c) => c,
                     None => continue,
                 };
+                if input_cap_reached(flags, buffer.len(), 1) {
+                    continue;
+                }
 
                 // Quantifier-aware character acceptance
                 let (idx, count) = current_mask_state(mask, &mask_map);
 
                 // Can the current element accept more characters?
                 let can_accept_more = if idx < mask.len() {
-                    match mask[idx].quantifier {
-                        Quantifier::One => count < 1,
-                        Quantifier::Star => true,
-                        Quantifier::Plus => true,
-                        Quantifier::Optional => count < 1,
-                    }
+                    mask[idx].quantifier.max().is_none_or(|max| count < max)
                 } else {
                     false
                 };
@@ -336,12 +531,7 @@ pub fn run_mask_mode(
 
                 // Has the current element met its minimum?
                 let min_satisfied = if idx < mask.len() {
-                    match mask[idx].quantifier {
-                        Quantifier::One => count >= 1,
-                        Quantifier::Star => true,
-                        Quantifier::Plus => count >= 1,
-                        Quantifier::Optional => true,
-                    }
+                    count >= mask[idx].quantifier.min()
                 } else {
                     true
                 };
@@ -351,25 +541,21 @@ pub fn run_mask_mode(
                     buffer.push(ch as u8);
                     mask_map.push(idx);
                     if !flags.silent {
-                        eprint!("{}", ch);
-                        let _ = io::stderr().flush();
+                        let _ = write!(output::tty(), "{}", ch);
+                        let _ = output::tty().flush();
                     }
-                    // If current element is One or Optional (now full), auto-insert literals after it
-                    let now_count = count + 1;
-                    let is_full = match mask[idx].quantifier {
-                        Quantifier::One => now_count >= 1,
-                        Quantifier::Optional => now_count >= 1,
-                        _ => false, // unbounded elements don't auto-advance
-                    };
+                    // If current element is now full, auto-insert literals after it
+                    // (unbounded elements don't auto-advance)
+                    let is_full = mask[idx].quantifier.max() == Some(count + 1);
                     if is_full {
-                        mask_auto_insert_literals(mask, &mut buffer, &mut mask_map, idx + 1, flags.silent);
+                        mask_auto_insert_literals(mask, &mut buffer, &mut mask_map, idx + 1, flags);
                     }
                 } else if min_satisfied {
                     // Try to advance to a later element
                     let advance_from = if idx < mask.len() { idx + 1 } else { mask.len() };
-                    if let Some(new_idx) = try_advance(mask, advance_from, ch) {
+                    if let Some(new_idx) = try_advance(mask, advance_from, ch, auto_literal) {
                         // Auto-insert any literals between current and new position
-                        mask_auto_insert_literals(mask, &mut buffer, &mut mask_map, advance_from, flags.silent);
+                        mask_auto_insert_literals(mask, &mut buffer, &mut mask_map, advance_from, flags);
                         // Now accept the character at new_idx
                         // (literals between advance_from and new_idx were already inserted;
                         //  but try_advance skips literals, so we may need to insert up to new_idx)
@@ -378,30 +564,28 @@ pub fn run_mask_mode(
                         if last_map < new_idx {
                             // Insert any remaining literals between last inserted and new_idx
                             let start = if mask_map.is_empty() { 0 } else { last_map + 1 };
-                            for li in start..new_idx {
-                                if let MaskClass::Literal(l) = mask[li].class {
+                            for (li, elem) in mask.iter().enumerate().take(new_idx).skip(start) {
+                                if let MaskClass::Literal(l) = elem.class {
                                     buffer.push(l as u8);
                                     mask_map.push(li);
                                     if !flags.silent {
-                                        eprint!("{}", l);
+                                        write_literal(l, flags);
                                     }
                                 }
                             }
                             if !flags.silent {
-                                let _ = io::stderr().flush();
+                                let _ = output::tty().flush();
                             }
                         }
                         buffer.push(ch as u8);
                         mask_map.push(new_idx);
                         if !flags.silent {
-                            eprint!("{}", ch);
-                            let _ = io::stderr().flush();
+                            let _ = write!(output::tty(), "{}", ch);
+                            let _ = output::tty().flush();
                         }
                         // Auto-insert literals after the newly accepted position
-                        if mask[new_idx].quantifier == Quantifier::One
-                            || mask[new_idx].quantifier == Quantifier::Optional
-                        {
-                            mask_auto_insert_literals(mask, &mut buffer, &mut mask_map, new_idx + 1, flags.silent);
+                        if mask[new_idx].quantifier.max() == Some(1) {
+                            mask_auto_insert_literals(mask, &mut buffer, &mut mask_map, new_idx + 1, flags);
                         }
                     }
                     // else: reject (ignore keystroke)
@@ -413,12 +597,13 @@ pub fn run_mask_mode(
                     buffer.pop();
                     mask_map.pop();
                     if !flags.silent {
-                        let _ = io::stderr().write_all(CURSOR_LEFT);
-                        let _ = io::stderr().write_all(CLEAR_TO_EOL);
-                        let _ = io::stderr().flush();
+                        let _ = output::tty().write_all(CURSOR_LEFT);
+                        let _ = output::tty().write_all(CLEAR_TO_EOL);
+                        let _ = output::tty().flush();
                     }
-                    // Chain-delete backwards over literals
-                    while !buffer.is_empty() {
+                    // Chain-delete backwards over literals (unless the user
+                    // typed them, with --no-auto-literal)
+                    while auto_literal && !buffer.is_empty() {
                         let prev_mask_idx = *mask_map.last().unwrap();
                         if matches!(mask[prev_mask_idx].class, MaskClass::Literal(_)) {
                             // Check if everything remaining is literals (leading-literals case)
@@ -428,9 +613,9 @@ pub fn run_mask_mode(
                             buffer.pop();
                             mask_map.pop();
                             if !flags.silent {
-                                let _ = io::stderr().write_all(CURSOR_LEFT);
-                                let _ = io::stderr().write_all(CLEAR_TO_EOL);
-                                let _ = io::stderr().flush();
+                                let _ = output::tty().write_all(CURSOR_LEFT);
+                                let _ = output::tty().write_all(CLEAR_TO_EOL);
+                                let _ = output::tty().flush();
                             }
                             if all_literals {
                                 // Keep going — clear all leading literals
@@ -457,12 +642,12 @@ pub fn run_mask_mode(
                             output::handle_default(ds, flags, output_to_stderr);
                         }
                         let ec = ds.len() as i32;
-                        return MaskResult { exit_code: ec, value: ds.clone(), status: "default", timed_out: false, default_used: true };
+                        return MaskResult { exit_code: ec, value: flags.post.apply(ds), status: "default", timed_out: false, default_used: true };
                     }
                 }
                 if flags.ret_key {
                     // With -r: accept if mask is satisfied (or buffer non-empty for compat)
-                    if mask_satisfied(mask, &mask_map) || buffer.is_empty() {
+                    if mask_satisfied(mask, &mask_map, auto_literal) || buffer.is_empty() {
                         break;
                     }
                 } else if !has_unbounded {
@@ -470,37 +655,108 @@ pub fn run_mask_mode(
                     // Enter does nothing (same as phase 1)
                 } else {
                     // Has unbounded quantifiers: Enter accepts if satisfied
-                    if mask_satisfied(mask, &mask_map) && !buffer.is_empty() {
+                    if mask_satisfied(mask, &mask_map, auto_literal) && !buffer.is_empty() {
                         break;
                     }
                 }
             }
             KeyInput::Escape => {
-                let esc_exit = match flags.esc_code {
-                    Some(0) => { continue; } // no-op
-                    Some(n) => n,
-                    None => 255,
+                let Some(esc_exit) = escape_exit(flags, Some(255)) else {
+                    continue; // -B0: no-op
                 };
                 // Erase displayed buffer
                 if !flags.silent && !buffer.is_empty() {
-                    let mut stderr = io::stderr();
-                    output::cursor_left_n(&mut stderr, buffer.len());
+                    let mut stderr = output::tty();
+                    output::to_origin(&mut stderr, buffer.len());
                     let _ = stderr.write_all(CLEAR_TO_EOL);
                     let _ = stderr.flush();
                 }
+                output::emit_cancel(flags, output_to_stderr);
                 return MaskResult { exit_code: esc_exit, value: String::new(), status: "cancelled", timed_out: false, default_used: false };
             }
+            // A paste is typed through the mask, literals and all
+            KeyInput::Paste(text) => input::type_paste(&text),
             // All other keys ignored in mask mode
             _ => {}
         }
     }
 
     // Output the buffer
-    let val = String::from_utf8_lossy(&buffer).into_owned();
-    let ec = buffer.len() as i32;
+    let raw = output_value(mask, &buffer, &mask_map, flags);
+    let ec = raw.len() as i32;
+    let val = flags.post.apply(&raw);
+    output::beep_on_complete(flags);
     if flags.json.is_none() && !val.is_empty() {
         output::output_str(&val, output_to_stderr, flags.both);
+        output::echo_result(&val, flags);
     }
 
     MaskResult { exit_code: ec, value: val, status: "ok", timed_out: false, default_used: false }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(mask: &str) -> (usize, MaskErrorKind) {
+        let e = parse_mask(mask).unwrap_err();
+        (e.pos, e.kind)
+    }
+
+    #[test]
+    fn parses_classes_literals_and_quantifiers() {
+        let mask = parse_mask("Un*-x{2,4}").unwrap();
+        assert_eq!(mask.len(), 4);
+        assert!(matches!(mask[0].class, MaskClass::Upper));
+        assert_eq!(mask[0].quantifier, Quantifier::One);
+        assert!(matches!(mask[1].class, MaskClass::Digit));
+        assert_eq!(mask[1].quantifier, Quantifier::Star);
+        assert!(matches!(mask[2].class, MaskClass::Literal('-')));
+        assert_eq!(mask[3].quantifier, Quantifier::Range { min: 2, max: Some(4) });
+    }
+
+    #[test]
+    fn brace_after_literal_stays_literal() {
+        let mask = parse_mask("-{n").unwrap();
+        assert!(matches!(mask[1].class, MaskClass::Literal('{')));
+    }
+
+    #[test]
+    fn unclosed_bracket() {
+        assert_eq!(error("nn[a-z"), (2, MaskErrorKind::UnclosedBracket));
+    }
+
+    #[test]
+    fn leading_quantifier() {
+        assert_eq!(error("*n"), (0, MaskErrorKind::UnexpectedQuantifier('*')));
+        assert_eq!(error("n+?"), (2, MaskErrorKind::UnexpectedQuantifier('?')));
+    }
+
+    #[test]
+    fn quantified_literal() {
+        assert_eq!(error("n-+"), (2, MaskErrorKind::QuantifiedLiteral('+')));
+    }
+
+    #[test]
+    fn bad_class_range() {
+        let (pos, kind) = error("n[z-a]");
+        assert_eq!(pos, 1);
+        assert!(matches!(kind, MaskErrorKind::InvalidClass(ref text, _) if text == "[z-a]"));
+    }
+
+    #[test]
+    fn reversed_repeat() {
+        assert_eq!(error("n{3,1}"), (1, MaskErrorKind::InvalidRepeat("{3,1}".to_string())));
+    }
+
+    #[test]
+    fn unclosed_brace() {
+        assert_eq!(error("xn{3"), (2, MaskErrorKind::UnclosedBrace));
+    }
+
+    #[test]
+    fn repeat_after_quantifier() {
+        assert_eq!(error("n*{2}"), (2, MaskErrorKind::UnexpectedQuantifier('{')));
+        assert_eq!(error("n{2}{3}"), (4, MaskErrorKind::UnexpectedQuantifier('{')));
+    }
+}
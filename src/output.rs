@@ -14,10 +14,15 @@
 
 //! ANSI escape sequences, cursor helpers, and output functions.
 
+use std::fs::{File, OpenOptions};
 use std::io::{self, Write};
-use std::sync::atomic::Ordering;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU8, AtomicUsize, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use crate::{Flags, JsonStyle, EXIT_STAT};
+use crate::grapheme::{self, Units};
+use crate::{Flags, JsonStyle, NewlineChannel, EXIT_STAT};
 
 // ---------------------------------------------------------------------------
 // ANSI escape sequences
@@ -29,6 +34,57 @@ pub const CURSOR_RIGHT: &[u8] = b"\x1b[C";
 pub const CLEAR_TO_EOL: &[u8] = b"\x1b[K";
 pub const REVERSE_ON: &[u8] = b"\x1b[7m";
 pub const REVERSE_OFF: &[u8] = b"\x1b[27m";
+pub const RED_ON: &[u8] = b"\x1b[31m";
+pub const COLOR_OFF: &[u8] = b"\x1b[39m";
+pub const QUERY_CURSOR_POS: &[u8] = b"\x1b[6n";
+
+// ---------------------------------------------------------------------------
+// Terminal writer
+// ---------------------------------------------------------------------------
+
+// `--echo-fd`: where a copy of everything drawn goes (-1 = off).
+static ECHO_FD: AtomicI32 = AtomicI32::new(-1);
+
+pub fn set_echo_fd(fd: i32) {
+    ECHO_FD.store(fd, Ordering::Relaxed);
+}
+
+/// Copy `bytes` to the `--echo-fd`, if there is one.  A reader that goes
+/// away is ignored; the read carries on.
+pub fn echo_copy(bytes: &[u8]) {
+    let fd = ECHO_FD.load(Ordering::Relaxed);
+    if fd < 0 {
+        return;
+    }
+    let mut written = 0;
+    while written < bytes.len() {
+        let n = unsafe { libc::write(fd, bytes[written..].as_ptr() as *const libc::c_void, bytes.len() - written) };
+        if n <= 0 {
+            break;
+        }
+        written += n as usize;
+    }
+}
+
+/// Everything grabchars draws goes through this: stderr, teed byte for
+/// byte to `--echo-fd` so a recorder sees exactly what the terminal did.
+pub struct Tty(io::Stderr);
+
+pub fn tty() -> Tty {
+    Tty(io::stderr())
+}
+
+impl Write for Tty {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.0.write(buf)?;
+        echo_copy(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
 
 /// Move cursor left by `n` columns.
 pub fn cursor_left_n(stderr: &mut impl Write, n: usize) {
@@ -40,40 +96,445 @@ pub fn cursor_right_n(stderr: &mut impl Write, n: usize) {
     let _ = write!(stderr, "{}{}C", CSI, n);
 }
 
-/// Redraw the entire editing buffer on stderr and position the cursor.
-/// `prev_cursor_pos` is where the cursor was before the edit (used to back up).
-pub fn redraw_input(buffer: &[u8], cursor_pos: usize, prev_cursor_pos: usize) {
-    let mut stderr = io::stderr();
-    if prev_cursor_pos > 0 {
-        cursor_left_n(&mut stderr, prev_cursor_pos);
+// `--col`: the screen column (1-based) input starts at; 0 when not given,
+// and the start is wherever the cursor was.
+static ORIGIN_COL: AtomicUsize = AtomicUsize::new(0);
+
+pub fn set_origin_col(col: usize) {
+    ORIGIN_COL.store(col, Ordering::Relaxed);
+}
+
+/// Move from `offset` columns into the input back to its start.  With
+/// `--col` that is an absolute move to the column, so a miscounted offset
+/// can't reach back into whatever is left of the input.
+pub fn to_origin(stderr: &mut impl Write, offset: usize) {
+    match ORIGIN_COL.load(Ordering::Relaxed) {
+        0 if offset > 0 => cursor_left_n(stderr, offset),
+        0 => {}
+        col => {
+            let _ = write!(stderr, "{}{}G", CSI, col);
+        }
+    }
+}
+
+/// `buf` with its control bytes in caret notation (ESC as `^[`, DEL as
+/// `^?`), so a byte quoted with Ctrl-V is shown instead of being sent to
+/// the terminal.  Each takes the two columns `Units::width` counts for it.
+pub fn caret_notation(buf: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(buf.len());
+    for &b in buf {
+        if grapheme::is_caret_control(b) {
+            out.extend_from_slice(&[b'^', b ^ 0x40]);
+        } else {
+            out.push(b);
+        }
     }
+    out
+}
+
+/// Redraw the entire editing buffer on stderr and position the cursor.
+/// `prev_col` is the cursor column before the edit (used to back up) and
+/// `tail` the number of columns from the new cursor to the end of the buffer.
+pub fn redraw_input(buffer: &[u8], tail: usize, prev_col: usize) {
+    let mut stderr = tty();
+    to_origin(&mut stderr, prev_col);
     let _ = stderr.write_all(CLEAR_TO_EOL);
     let _ = stderr.write_all(buffer);
-    let tail = buffer.len() - cursor_pos;
     if tail > 0 {
         cursor_left_n(&mut stderr, tail);
     }
     let _ = stderr.flush();
 }
 
+/// `--echo-map`: display substitutes for ranges of typed characters.  Only
+/// the line editor's echo changes; the buffer keeps what was typed.
+pub struct EchoMap {
+    rules: Vec<(char, char, char)>, // first, last, shown as
+}
+
+impl EchoMap {
+    /// Parse comma-separated `<chars>:<substitute>` rules such as
+    /// `0-9:*,a-f:#`.  `<chars>` is single characters and `x-y` ranges; the
+    /// substitute is one single-column character.  The first match wins.
+    pub fn parse(spec: &str) -> Result<EchoMap, String> {
+        let mut rules = Vec::new();
+        for rule in spec.split(',').filter(|r| !r.is_empty()) {
+            let Some((chars, shown)) = rule.rsplit_once(':') else {
+                return Err(format!("rule '{}' needs <chars>:<substitute>", rule));
+            };
+            let mut shown_chars = shown.chars();
+            let shown = match (shown_chars.next(), shown_chars.next()) {
+                (Some(c), None) if !c.is_control() => c,
+                _ => return Err(format!("rule '{}': substitute must be one character", rule)),
+            };
+            let chars: Vec<char> = chars.chars().collect();
+            if chars.is_empty() {
+                return Err(format!("rule '{}' has no characters to map", rule));
+            }
+            let mut i = 0;
+            while i < chars.len() {
+                if i + 2 < chars.len() && chars[i + 1] == '-' {
+                    if chars[i] > chars[i + 2] {
+                        return Err(format!("rule '{}': range {}-{} is backwards", rule, chars[i], chars[i + 2]));
+                    }
+                    rules.push((chars[i], chars[i + 2], shown));
+                    i += 3;
+                } else {
+                    rules.push((chars[i], chars[i], shown));
+                    i += 1;
+                }
+            }
+        }
+        Ok(EchoMap { rules })
+    }
+
+    fn lookup(&self, c: char) -> Option<char> {
+        self.rules
+            .iter()
+            .find(|(first, last, _)| (*first..=*last).contains(&c))
+            .map(|(_, _, shown)| *shown)
+    }
+
+    /// `buf` as it should appear on screen.  A mapped unit is drawn as its
+    /// substitute repeated over the unit's width, so the cursor arithmetic
+    /// done on the real buffer still lines up.  Without `--graphemes` units
+    /// are bytes, so only ASCII characters can be mapped.  The unit starting
+    /// at byte offset `reveal` (`--reveal-last`) is drawn as typed.
+    pub fn apply(&self, buf: &[u8], units: Units, reveal: Option<usize>) -> Vec<u8> {
+        let mut out = Vec::with_capacity(buf.len());
+        let mut pos = 0;
+        while pos < buf.len() {
+            let end = units.next(buf, pos);
+            let unit = &buf[pos..end];
+            let mapped = if reveal == Some(pos) { None } else { grapheme::decode(unit).0.and_then(|c| self.lookup(c)) };
+            match mapped {
+                Some(shown) => {
+                    let mut tmp = [0u8; 4];
+                    let shown = shown.encode_utf8(&mut tmp).as_bytes();
+                    for _ in 0..units.width(unit) {
+                        out.extend_from_slice(shown);
+                    }
+                }
+                None => out.extend_from_slice(unit),
+            }
+            pos = end;
+        }
+        out
+    }
+}
+
+/// `--field-width`: the line editor drawn in a field of exactly `width`
+/// columns, the unused part padded with `fill`.  Nothing after the field
+/// is cleared, so prompt text that follows it stays put.  A buffer wider
+/// than the field scrolls to keep the cursor inside.
+pub struct Field {
+    width: usize,
+    fill: char,
+    scroll: usize, // byte offset of the first unit shown
+    col: usize,    // cursor column within the field, as last drawn
+}
+
+impl Field {
+    pub fn new(width: usize, fill: char) -> Field {
+        Field { width, fill, scroll: 0, col: 0 }
+    }
+
+    /// Redraw the field for `buffer` with the cursor at byte offset
+    /// `cursor_pos`.  `render` turns a slice of the buffer, and its byte
+    /// offset, into what is drawn (the `--echo-map` rendering).
+    pub fn draw(&mut self, buffer: &[u8], cursor_pos: usize, units: Units, render: impl Fn(&[u8], usize) -> Vec<u8>) {
+        // The cursor needs a column of its own, so at most width - 1 before it
+        self.scroll = self.scroll.min(cursor_pos);
+        while self.scroll < cursor_pos && units.width(&buffer[self.scroll..cursor_pos]) >= self.width {
+            self.scroll = units.next(buffer, self.scroll);
+        }
+        let mut end = self.scroll;
+        while end < buffer.len() {
+            let next = units.next(buffer, end);
+            if units.width(&buffer[self.scroll..next]) > self.width {
+                break;
+            }
+            end = next;
+        }
+        let used = units.width(&buffer[self.scroll..end]);
+        let col = units.width(&buffer[self.scroll..cursor_pos]);
+
+        let mut stderr = tty();
+        to_origin(&mut stderr, self.col);
+        let _ = stderr.write_all(&render(&buffer[self.scroll..end], self.scroll));
+        let pad: String = std::iter::repeat_n(self.fill, self.width - used).collect();
+        let _ = stderr.write_all(pad.as_bytes());
+        if self.width > col {
+            cursor_left_n(&mut stderr, self.width - col);
+        }
+        let _ = stderr.flush();
+        self.col = col;
+    }
+
+    /// Move the cursor past the end of the field.  The next `draw` starts a
+    /// new field where the cursor then is.
+    pub fn leave(&mut self) {
+        let mut stderr = tty();
+        if self.width > self.col {
+            cursor_right_n(&mut stderr, self.width - self.col);
+        }
+        let _ = stderr.flush();
+        self.scroll = 0;
+        self.col = 0;
+    }
+}
+
+/// `--spinner`: frames of an activity indicator shown while waiting on a
+/// child process.  The caller decides where to draw it; `tick` says when.
+pub struct Spinner {
+    frame: usize,
+    next_at: Instant,
+}
+
+impl Spinner {
+    const FRAMES: [char; 4] = ['|', '/', '-', '\\'];
+    const INTERVAL: Duration = Duration::from_millis(100);
+
+    /// A spinner if `enabled` (`--spinner`) and stderr is a terminal.  The
+    /// first frame is due after one interval, so quick commands show nothing.
+    pub fn new(enabled: bool) -> Option<Spinner> {
+        if !enabled || unsafe { libc::isatty(2) } == 0 {
+            return None;
+        }
+        Some(Spinner { frame: 0, next_at: Instant::now() + Self::INTERVAL })
+    }
+
+    /// The frame to draw now, or `None` if the current one is still fresh.
+    pub fn tick(&mut self) -> Option<char> {
+        let now = Instant::now();
+        if now < self.next_at {
+            return None;
+        }
+        self.next_at = now + Self::INTERVAL;
+        let c = Self::FRAMES[self.frame % Self::FRAMES.len()];
+        self.frame += 1;
+        Some(c)
+    }
+}
+
+/// Standard base64 (RFC 4648) with padding.
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// `--copy`: put `text` on the system clipboard with OSC 52, which the
+/// terminal handles itself, so it works over SSH.  Written to stderr, and
+/// only when that is a terminal.
+pub fn copy_to_clipboard(text: &str) {
+    if unsafe { libc::isatty(2) } == 0 {
+        return;
+    }
+    let mut stderr = tty();
+    let _ = write!(stderr, "\x1b]52;c;{}\x07", base64(text.as_bytes()));
+    let _ = stderr.flush();
+}
+
+// `--flash`: rejected keys also blink the terminal, for anyone who can't
+// hear (or has muted) the bell.
+static FLASH: AtomicBool = AtomicBool::new(false);
+
+pub fn set_flash(on: bool) {
+    FLASH.store(on, Ordering::Relaxed);
+}
+
+/// How long `--flash` holds the reverse video.
+const FLASH_MS: u64 = 80;
+
+/// Ring the terminal bell on stderr, and with `--flash` blink the screen in
+/// reverse video (DECSCNM, the terminfo visible bell).  Skipped when stderr
+/// is not a tty so redirected logs don't collect BEL bytes.
+pub fn alert() {
+    if unsafe { libc::isatty(2) } == 0 {
+        return;
+    }
+    let mut stderr = tty();
+    let _ = stderr.write_all(b"\x07");
+    if FLASH.load(Ordering::Relaxed) {
+        let _ = stderr.write_all(b"\x1b[?5h");
+        let _ = stderr.flush();
+        std::thread::sleep(Duration::from_millis(FLASH_MS));
+        let _ = stderr.write_all(b"\x1b[?5l");
+    }
+    let _ = stderr.flush();
+}
+
+/// `--beep-on-complete`: audible confirmation that input was accepted.
+pub fn beep_on_complete(flags: &Flags) {
+    if flags.beep_on_complete {
+        alert();
+    }
+}
+
+/// Show a transient hint at the cursor and move back to where it started.
+/// The caller erases it later with `CLEAR_TO_EOL` from the same position.
+pub fn show_hint(stderr: &mut impl Write, text: &str) {
+    let _ = stderr.write_all(text.as_bytes());
+    cursor_left_n(stderr, text.chars().count());
+    let _ = stderr.flush();
+}
+
+/// Write the `-Z` trailing newline: to stderr, or with `--newline-to=value`
+/// to whichever channel(s) the value went to, so a redirected value is
+/// followed by its own newline.
 pub fn trailing_newline_if(flags: &Flags) {
-    if flags.trailing_newline {
-        let _ = io::stderr().write_all(b"\n");
-        let _ = io::stderr().flush();
+    if !flags.trailing_newline {
+        return;
+    }
+    match flags.newline_channel {
+        NewlineChannel::Stderr => {
+            let _ = tty().write_all(b"\n");
+            let _ = tty().flush();
+        }
+        // Only after a value: with nothing on the value channel (-s, Escape)
+        // the newline just ends the line on the terminal
+        NewlineChannel::Value => match VALUE_SENT.load(Ordering::Relaxed) {
+            0 => {
+                let _ = tty().write_all(b"\n");
+                let _ = tty().flush();
+            }
+            sent => write_routed(sent & SENT_STDOUT == 0, sent == SENT_STDOUT | SENT_STDERR, |w| { let _ = w.write_all(b"\n"); }),
+        },
+    }
+}
+
+/// `--echo-result`: show the accepted value on stderr (the terminal) after
+/// input is done, so the user sees it even with `-s` or a captured stdout.
+/// Skipped when the value itself was already written to stderr.
+pub fn echo_result(value: &str, flags: &Flags) {
+    if !flags.echo_result || value.is_empty() {
+        return;
+    }
+    if VALUE_SENT.load(Ordering::Relaxed) & SENT_STDERR != 0 {
+        return;
+    }
+    let mut stderr = tty();
+    let _ = stderr.write_all(&caret_notation(value.as_bytes()));
+    let _ = stderr.flush();
+}
+
+/// `--post`: the transforms applied to the final value, in order, and
+/// then the `--prefix`/`--suffix` wrapped around the result.
+#[derive(Default)]
+pub struct Post {
+    ops: Vec<PostOp>,
+    pub prefix: String,
+    pub suffix: String,
+}
+
+#[derive(Clone, Copy)]
+enum PostOp {
+    Trim,  // strip leading and trailing whitespace
+    Upper, // upper-case
+    Lower, // lower-case
+    Title, // upper-case the first letter of each space-separated word, lower-case the rest
+    Quote, // single-quote for the shell: it's -> 'it'\''s'
+}
+
+impl Post {
+    /// Set the ops from a comma-separated list such as `trim,upper,quote`.
+    pub fn set_ops(&mut self, spec: &str) -> Result<(), String> {
+        let mut ops = Vec::new();
+        for name in spec.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            ops.push(match name {
+                "trim" => PostOp::Trim,
+                "upper" => PostOp::Upper,
+                "lower" => PostOp::Lower,
+                "title" => PostOp::Title,
+                "quote" => PostOp::Quote,
+                _ => return Err(format!("unknown op '{}' (use trim, upper, lower, title, quote)", name)),
+            });
+        }
+        if ops.is_empty() {
+            return Err("need at least one op".to_string());
+        }
+        self.ops = ops;
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty() && self.prefix.is_empty() && self.suffix.is_empty()
+    }
+
+    /// `value` after every op, first to last, inside the prefix and suffix.
+    pub fn apply(&self, value: &str) -> String {
+        let mut out = value.to_string();
+        for op in &self.ops {
+            out = match op {
+                PostOp::Trim => out.trim().to_string(),
+                PostOp::Upper => out.to_uppercase(),
+                PostOp::Lower => out.to_lowercase(),
+                PostOp::Title => title_case(&out),
+                PostOp::Quote => format!("'{}'", out.replace('\'', "'\\''")),
+            };
+        }
+        format!("{}{}{}", self.prefix, out, self.suffix)
+    }
+}
+
+fn title_case(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut at_word_start = true;
+    for c in s.chars() {
+        if at_word_start {
+            out.extend(c.to_uppercase());
+        } else {
+            out.extend(c.to_lowercase());
+        }
+        // Words are split on whitespace only, so "it's" stays one word
+        at_word_start = c.is_whitespace();
+    }
+    out
+}
+
+/// `--on-cancel`: write the cancel string where the value would have gone,
+/// so a reader downstream still gets a line when the user presses Escape.
+pub fn emit_cancel(flags: &Flags, output_to_stderr: bool) {
+    if flags.silent || flags.json.is_some() {
+        return;
+    }
+    if let Some(ref text) = flags.on_cancel {
+        output_str(text, output_to_stderr, flags.both);
     }
 }
 
 pub fn handle_default(default_string: &str, flags: &Flags, output_to_stderr: bool) {
+    let value = flags.post.apply(default_string);
     if !flags.silent {
-        output_str(default_string, output_to_stderr, flags.both || flags.ret_key);
+        output_str(&value, output_to_stderr, flags.both || flags.ret_key);
     }
+    echo_result(&value, flags);
     EXIT_STAT.store(default_string.len() as i32, Ordering::Relaxed);
 }
 
+// Which channels a value has been written to, for `--newline-to=value`
+// and `--echo-result`.
+static VALUE_SENT: AtomicU8 = AtomicU8::new(0);
+const SENT_STDOUT: u8 = 1;
+const SENT_STDERR: u8 = 2;
+
 fn write_routed(to_stderr: bool, both: bool, emit: impl Fn(&mut dyn Write)) {
+    let sent = if both { SENT_STDOUT | SENT_STDERR } else if to_stderr { SENT_STDERR } else { SENT_STDOUT };
+    VALUE_SENT.fetch_or(sent, Ordering::Relaxed);
     if to_stderr {
-        emit(&mut io::stderr());
-        let _ = io::stderr().flush();
+        emit(&mut tty());
+        let _ = tty().flush();
         if both {
             emit(&mut io::stdout());
             let _ = io::stdout().flush();
@@ -82,8 +543,8 @@ fn write_routed(to_stderr: bool, both: bool, emit: impl Fn(&mut dyn Write)) {
         emit(&mut io::stdout());
         let _ = io::stdout().flush();
         if both {
-            emit(&mut io::stderr());
-            let _ = io::stderr().flush();
+            emit(&mut tty());
+            let _ = tty().flush();
         }
     }
 }
@@ -113,6 +574,8 @@ pub struct JsonPayload {
     pub default_used: bool,
     pub index: Option<i32>,
     pub filter: Option<String>,
+    pub timeout_kind: Option<&'static str>, // "total" (-t) or "idle" (--idle) when timed out
+    pub timings: Option<(u128, usize)>, // --timings: (elapsed ms, keystrokes)
 }
 
 fn json_escape(s: &str) -> String {
@@ -142,17 +605,82 @@ pub fn emit_json(payload: &JsonPayload, style: JsonStyle, to_stderr: bool, both:
         Some(s) => format!("\"{}\"", json_escape(s)),
         None => "null".to_string(),
     };
+    // timeout_kind only when a timeout happened, so other results keep the
+    // original field set
+    let mut extra = match (payload.timeout_kind.filter(|_| payload.timed_out), style) {
+        (None, _) => String::new(),
+        (Some(k), JsonStyle::Compact) => format!(",\"timeout_kind\":\"{}\"", k),
+        (Some(k), JsonStyle::Pretty) => format!(",\n  \"timeout_kind\": \"{}\"", k),
+    };
+    // --timings: two extra fields, only when asked for
+    extra.push_str(&match (payload.timings, style) {
+        (None, _) => String::new(),
+        (Some((ms, keys)), JsonStyle::Compact) => format!(",\"elapsed_ms\":{},\"keystrokes\":{}", ms, keys),
+        (Some((ms, keys)), JsonStyle::Pretty) => format!(",\n  \"elapsed_ms\": {},\n  \"keystrokes\": {}", ms, keys),
+    });
     let json = match style {
         JsonStyle::Compact => format!(
-            "{{\"value\":\"{}\",\"exit\":{},\"status\":\"{}\",\"mode\":\"{}\",\"timed_out\":{},\"default_used\":{},\"index\":{},\"filter\":{}}}",
+            "{{\"value\":\"{}\",\"exit\":{},\"status\":\"{}\",\"mode\":\"{}\",\"timed_out\":{},\"default_used\":{},\"index\":{},\"filter\":{}{}}}",
             json_escape(&payload.value), payload.exit, payload.status, payload.mode,
-            payload.timed_out, payload.default_used, idx, flt
+            payload.timed_out, payload.default_used, idx, flt, extra
         ),
         JsonStyle::Pretty => format!(
-            "{{\n  \"value\": \"{}\",\n  \"exit\": {},\n  \"status\": \"{}\",\n  \"mode\": \"{}\",\n  \"timed_out\": {},\n  \"default_used\": {},\n  \"index\": {},\n  \"filter\": {}\n}}",
+            "{{\n  \"value\": \"{}\",\n  \"exit\": {},\n  \"status\": \"{}\",\n  \"mode\": \"{}\",\n  \"timed_out\": {},\n  \"default_used\": {},\n  \"index\": {},\n  \"filter\": {}{}\n}}",
             json_escape(&payload.value), payload.exit, payload.status, payload.mode,
-            payload.timed_out, payload.default_used, idx, flt
+            payload.timed_out, payload.default_used, idx, flt, extra
         ),
     };
     write_routed(to_stderr, both, |w| { let _ = write!(w, "{}", json); });
 }
+
+// ---------------------------------------------------------------------------
+// Outcome file (--outcome-file)
+// ---------------------------------------------------------------------------
+
+/// `s` in single quotes, so the file can be sourced by a POSIX shell.
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// Write the result to `path` as `key=value` lines a shell can source:
+/// `value`, `status` (as in `-J`), `exit`, `count` (characters in the
+/// value), and `index` (select's position, empty otherwise).  The text
+/// goes to a new temporary file beside `path` that is then renamed over
+/// it, so a reader sees the old file or the new one, never half of one.
+pub fn write_outcome_file(path: &str, payload: &JsonPayload) -> io::Result<()> {
+    let text = format!(
+        "value={}\nstatus={}\nexit={}\ncount={}\nindex={}\n",
+        shell_quote(&payload.value),
+        payload.status,
+        payload.exit,
+        payload.value.chars().count(),
+        payload.index.map(|i| i.to_string()).unwrap_or_default(),
+    );
+    let (tmp, mut file) = create_beside(path)?;
+    let written = file.write_all(text.as_bytes()).and_then(|()| std::fs::rename(&tmp, path));
+    if written.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+    written
+}
+
+/// A fresh file in `path`'s directory, so the rename stays on one
+/// filesystem.  It is created exclusively, never through a symlink or over
+/// an existing file, under a name that is hard to guess and readable only
+/// by the user; a name that is already taken is retried with another.
+fn create_beside(path: &str) -> io::Result<(PathBuf, File)> {
+    let target = Path::new(path);
+    let dir = target.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let name = target.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let seed = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or(0);
+    for attempt in 0..100u32 {
+        let salt = seed.wrapping_add(attempt.wrapping_mul(0x9E37_79B9));
+        let tmp = dir.join(format!(".{}.tmp{}.{:08x}", name, std::process::id(), salt));
+        match OpenOptions::new().write(true).create_new(true).mode(0o600).open(&tmp) {
+            Ok(file) => return Ok((tmp, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::AlreadyExists, "no free temporary file name"))
+}
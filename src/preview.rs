@@ -0,0 +1,148 @@
+// Copyright 2026 Daniel Smith
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! `select --preview`: run a command for the highlighted option and show
+//! its output in rows reserved below the widget.
+//!
+//! The rows are reserved once, up front, with IND (which scrolls at the
+//! bottom of the screen but keeps the column), so every later redraw can
+//! save the cursor, write below the widget line, and restore it without
+//! anything moving.  The command runs only once keys stop arriving for a
+//! moment, so holding an arrow key doesn't start one process per option.
+
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::output::{self, Spinner};
+use crate::{input, term};
+
+/// Rows reserved for the preview.
+const PREVIEW_LINES: usize = 10;
+/// Quiet time after the last key before the command runs.
+const DEBOUNCE_MS: i32 = 100;
+/// A command still running after this long is killed.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(2);
+
+const SAVE_CURSOR: &[u8] = b"\x1b7";
+const RESTORE_CURSOR: &[u8] = b"\x1b8";
+const CLEAR_TO_EOS: &[u8] = b"\x1b[J";
+
+pub struct Preview {
+    cmd: String,
+    spinner: bool, // --spinner: show activity while the command runs
+    shown: Option<Option<usize>>, // option index on screen; None = nothing drawn yet
+}
+
+impl Preview {
+    /// Reserve the rows below the widget line and return the preview.
+    pub fn open(cmd: &str, spinner: bool) -> Preview {
+        let mut stderr = output::tty();
+        for _ in 0..PREVIEW_LINES {
+            let _ = stderr.write_all(b"\x1bD");
+        }
+        let _ = write!(stderr, "\x1b[{}A", PREVIEW_LINES);
+        let _ = stderr.flush();
+        Preview { cmd: cmd.to_string(), spinner, shown: None }
+    }
+
+    /// Show the preview for `current` (an index into `options`, or `None`
+    /// when nothing matches) unless it is already shown or more keys are
+    /// waiting on `fd`.
+    pub fn update(&mut self, fd: i32, options: &[String], current: Option<usize>) {
+        if self.shown == Some(current) || input::byte_available(fd, DEBOUNCE_MS) {
+            return;
+        }
+        let lines = match current {
+            Some(i) => run(&self.cmd, &options[i], Spinner::new(self.spinner)),
+            None => Vec::new(),
+        };
+        draw(&lines);
+        self.shown = Some(current);
+    }
+}
+
+impl Drop for Preview {
+    fn drop(&mut self) {
+        draw(&[]);
+    }
+}
+
+/// Replace the preview rows with `lines` and put the cursor back.
+fn draw(lines: &[String]) {
+    let mut stderr = output::tty();
+    let _ = stderr.write_all(SAVE_CURSOR);
+    let _ = stderr.write_all(b"\n");
+    let _ = stderr.write_all(CLEAR_TO_EOS);
+    let _ = stderr.write_all(lines.join("\n").as_bytes());
+    let _ = stderr.write_all(RESTORE_CURSOR);
+    let _ = stderr.flush();
+}
+
+/// Run `cmd` with `{}` replaced by the quoted option; return the first
+/// `PREVIEW_LINES` lines of its stdout, cut to the terminal width.  A
+/// `spinner` takes over the preview rows while the command is running.
+fn run(cmd: &str, option: &str, mut spinner: Option<Spinner>) -> Vec<String> {
+    let quoted = format!("'{}'", option.replace('\'', "'\\''"));
+    let child = Command::new("sh")
+        .arg("-c")
+        .arg(cmd.replace("{}", &quoted))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn();
+    let Ok(mut child) = child else {
+        return vec!["(preview command failed to start)".to_string()];
+    };
+
+    // Read on a thread so a chatty command can't fill the pipe and stall
+    let mut stdout = child.stdout.take().unwrap();
+    let reader = thread::spawn(move || {
+        let mut out = Vec::new();
+        let _ = stdout.read_to_end(&mut out);
+        out
+    });
+    let start = Instant::now();
+    loop {
+        match child.try_wait() {
+            Ok(Some(_)) => {
+                term::apply_raw(term::input_fd());
+                break;
+            }
+            Ok(None) if start.elapsed() < COMMAND_TIMEOUT => {
+                if let Some(frame) = spinner.as_mut().and_then(Spinner::tick) {
+                    draw(&[frame.to_string()]);
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                term::apply_raw(term::input_fd());
+                // Anything the command started may still hold the pipe, so
+                // leave the reader behind rather than wait on it
+                return vec!["(preview command timed out)".to_string()];
+            }
+        }
+    }
+    let out = reader.join().unwrap_or_default();
+
+    let width = term::columns(2);
+    String::from_utf8_lossy(&out)
+        .lines()
+        .take(PREVIEW_LINES)
+        .map(|l| l.replace('\t', "    ").chars().filter(|c| !c.is_control()).take(width).collect())
+        .collect()
+}
@@ -17,9 +17,12 @@
 use std::io::{self, Write};
 use std::sync::atomic::Ordering;
 
+use crate::grapheme;
 use crate::input::{self, KeyInput};
-use crate::output::{self, CURSOR_LEFT, CURSOR_RIGHT, CLEAR_TO_EOL, REVERSE_ON, REVERSE_OFF};
-use crate::{apply_char_filters, FilterStyle, Flags, HighlightStyle, TIMED_OUT};
+use crate::json;
+use crate::output::{self, CURSOR_LEFT, CURSOR_RIGHT, CLEAR_TO_EOL, REVERSE_ON, REVERSE_OFF, RED_ON, COLOR_OFF};
+use crate::preview::Preview;
+use crate::{apply_char_filters, ctrl_d_key, escape_exit, input_cap_reached, non_ascii_rejected, EnterEmpty, Flags, MatchStrategy, HighlightStyle, PrintField, SelectSort, TimeoutSelect, TIMED_OUT};
 
 /// Result from a select operation, carrying all info needed for JSON output.
 pub struct SelectResult {
@@ -48,24 +51,150 @@ fn fuzzy_match(opt: &str, filter: &str) -> bool {
     true
 }
 
-/// Return indices of options that match `filter` according to `style`.
-fn compute_matches(options: &[String], filter: &str, style: &FilterStyle) -> Vec<usize> {
-    let filter_lower = filter.to_lowercase();
-    options
-        .iter()
-        .enumerate()
-        .filter(|(_, opt)| {
-            let opt_lower = opt.to_lowercase();
-            match style {
-                FilterStyle::Prefix   => opt_lower.starts_with(&filter_lower),
-                FilterStyle::Fuzzy    => fuzzy_match(&opt_lower, &filter_lower),
-                FilterStyle::Contains => opt_lower.contains(&filter_lower),
+/// Rank of a fuzzy match, lower first.  The group comes first: 0 when the
+/// option starts with the filter, 1 when the filter appears contiguously at
+/// the start of a word (after a non-alphanumeric character), 2 for any other
+/// subsequence.  Within a group, tighter matches (fewer characters skipped
+/// between the first and last filter character) and then earlier ones win.
+fn fuzzy_rank(opt: &str, filter: &str) -> Score {
+    let (gaps, start) = fuzzy_span(opt, filter);
+    if opt.starts_with(filter) {
+        return (0, gaps, start);
+    }
+    let at_word = opt.match_indices(filter).any(|(i, _)| {
+        opt[..i].chars().next_back().is_none_or(|c| !c.is_alphanumeric())
+    });
+    (if at_word { 1 } else { 2 }, gaps, start)
+}
+
+/// The tightest placement of `filter` in `opt` as (characters skipped,
+/// start position), trying each place the first filter character appears
+/// and matching the rest greedily from there.  Only called on a match.
+fn fuzzy_span(opt: &str, filter: &str) -> (usize, usize) {
+    let opt: Vec<char> = opt.chars().collect();
+    let filter: Vec<char> = filter.chars().collect();
+    let Some(&first) = filter.first() else {
+        return (0, 0);
+    };
+    let mut best = (usize::MAX, usize::MAX);
+    for start in (0..opt.len()).filter(|&i| opt[i] == first) {
+        let mut pos = start;
+        let mut rest = filter[1..].iter();
+        let mut next = rest.next();
+        while let Some(&fc) = next {
+            pos += 1;
+            match opt.get(pos) {
+                Some(&c) if c == fc => next = rest.next(),
+                Some(_) => {}
+                None => break,
             }
+        }
+        if next.is_none() {
+            best = best.min((pos + 1 - start - filter.len(), start));
+        }
+    }
+    best
+}
+
+/// Accented Latin letters and the base letter each folds to under
+/// `--ignore-diacritics`.  Covers Latin-1 and Latin Extended-A, which is
+/// what typed and listed data mostly carries; decomposed text (a letter
+/// followed by a combining mark) is handled by dropping the marks.
+const DIACRITICS: &[(&str, char)] = &[
+    ("àáâãäåāăą", 'a'), ("ÀÁÂÃÄÅĀĂĄ", 'A'),
+    ("çćĉċč", 'c'), ("ÇĆĈĊČ", 'C'),
+    ("ďđ", 'd'), ("ĎĐ", 'D'),
+    ("èéêëēĕėęě", 'e'), ("ÈÉÊËĒĔĖĘĚ", 'E'),
+    ("ĝğġģ", 'g'), ("ĜĞĠĢ", 'G'),
+    ("ĥħ", 'h'), ("ĤĦ", 'H'),
+    ("ìíîïĩīĭįı", 'i'), ("ÌÍÎÏĨĪĬĮİ", 'I'),
+    ("ĵ", 'j'), ("Ĵ", 'J'),
+    ("ķ", 'k'), ("Ķ", 'K'),
+    ("ĺļľŀł", 'l'), ("ĹĻĽĿŁ", 'L'),
+    ("ñńņňŉ", 'n'), ("ÑŃŅŇ", 'N'),
+    ("òóôõöøōŏő", 'o'), ("ÒÓÔÕÖØŌŎŐ", 'O'),
+    ("ŕŗř", 'r'), ("ŔŖŘ", 'R'),
+    ("śŝşš", 's'), ("ŚŜŞŠ", 'S'),
+    ("ţťŧ", 't'), ("ŢŤŦ", 'T'),
+    ("ùúûüũūŭůűų", 'u'), ("ÙÚÛÜŨŪŬŮŰŲ", 'U'),
+    ("ŵ", 'w'), ("Ŵ", 'W'),
+    ("ýÿŷ", 'y'), ("ÝŸŶ", 'Y'),
+    ("źżž", 'z'), ("ŹŻŽ", 'Z'),
+];
+
+/// `s` with accents removed: `café` and `cafe\u{301}` both become `cafe`.
+fn strip_diacritics(s: &str) -> String {
+    s.chars()
+        .filter(|c| !('\u{0300}'..='\u{036F}').contains(c)) // combining marks
+        .map(|c| {
+            if c.is_ascii() {
+                return c;
+            }
+            DIACRITICS
+                .iter()
+                .find(|(accented, _)| accented.contains(c))
+                .map_or(c, |&(_, base)| base)
         })
-        .map(|(i, _)| i)
         .collect()
 }
 
+/// Rank of a match, lower first: (group, characters skipped, start).
+/// Matches of equal score keep list order.
+type Score = (u8, usize, usize);
+
+impl MatchStrategy {
+    /// Score `option` against `filter` (both already case-folded), or
+    /// `None` if it doesn't match.  Prefix and contains matches all score
+    /// the same; fuzzy matches are ranked by `fuzzy_rank`.
+    fn matches(&self, option: &str, filter: &str) -> Option<Score> {
+        match self {
+            MatchStrategy::Prefix => option.starts_with(filter).then_some((0, 0, 0)),
+            MatchStrategy::Contains => option.contains(filter).then_some((0, 0, 0)),
+            MatchStrategy::Fuzzy => fuzzy_match(option, filter).then(|| fuzzy_rank(option, filter)),
+        }
+    }
+}
+
+/// Return indices of options that match `filter` according to the `-F`
+/// strategy, best score first.  An empty filter matches everything unless
+/// `--empty=none` is set.  Matching ignores case unless `--case-sensitive`
+/// is set, and accents with `--ignore-diacritics`.
+fn compute_matches(options: &[String], filter: &str, flags: &Flags) -> Vec<usize> {
+    if filter.is_empty() && flags.empty_matches_none {
+        return Vec::new();
+    }
+    let fold = |s: &str| {
+        let s = if flags.case_sensitive { s.to_string() } else { s.to_lowercase() };
+        if flags.ignore_diacritics { strip_diacritics(&s) } else { s }
+    };
+    let filter_folded = fold(filter);
+    let mut scored: Vec<(Score, usize)> = options
+        .iter()
+        .enumerate()
+        .filter_map(|(i, opt)| flags.match_strategy.matches(&fold(opt), &filter_folded).map(|score| (score, i)))
+        .collect();
+    // sort_by_key is stable, so equal scores keep list order
+    scored.sort_by_key(|&(score, _)| score);
+    scored.into_iter().map(|(_, i)| i).collect()
+}
+
+/// Filter text after Tab-completing to `option`.  The completion goes
+/// through the same `-U`/`-L` mapping as typed characters, so the filter
+/// never changes case under the user; with `--case-sensitive` the option's
+/// own text is kept, since a remapped filter would no longer match it.
+fn completed_filter(option: &str, flags: &Flags) -> Vec<u8> {
+    let text = if flags.case_sensitive {
+        option.to_string()
+    } else if flags.upper {
+        option.to_uppercase()
+    } else if flags.lower {
+        option.to_lowercase()
+    } else {
+        option.to_string()
+    };
+    text.into_bytes()
+}
+
 /// Recompute filtered matches, clamp match_idx, and re-render.
 fn recompute_and_render(
     filter: &[u8],
@@ -76,7 +205,7 @@ fn recompute_and_render(
     render: impl FnOnce(&[u8], &[String], &[usize], usize),
 ) {
     let filter_str = String::from_utf8_lossy(filter);
-    *matches = compute_matches(options, &filter_str, &flags.filter_style);
+    *matches = compute_matches(options, &filter_str, flags);
     if *match_idx >= matches.len() {
         *match_idx = 0;
     }
@@ -85,59 +214,129 @@ fn recompute_and_render(
     }
 }
 
-/// Find the index within `matches` whose option matches `default` (case-insensitive).
-/// Returns 0 if not found.
-fn find_default_match(default: &str, options: &[String], matches: &[usize]) -> usize {
-    let d = default.to_lowercase();
-    for (i, &idx) in matches.iter().enumerate() {
-        if options[idx].to_lowercase() == d {
-            return i;
-        }
+/// Whether `opt` is the `-d` default.  Case-insensitive unless
+/// `--default-case-sensitive`; deliberately independent of `--case-sensitive`,
+/// which only governs filtering.
+fn is_default(opt: &str, default: &str, flags: &Flags) -> bool {
+    if flags.default_case_sensitive {
+        opt == default
+    } else {
+        opt.to_lowercase() == default.to_lowercase()
     }
-    0
 }
 
-/// Find the index within `options` whose value matches `default` (case-insensitive).
+/// Find the index within `matches` whose option matches `default`.
+/// Returns 0 if not found.
+fn find_default_match(default: &str, options: &[String], matches: &[usize], flags: &Flags) -> usize {
+    matches
+        .iter()
+        .position(|&idx| is_default(&options[idx], default, flags))
+        .unwrap_or(0)
+}
+
+/// Find the index within `options` whose value matches `default`.
 /// Returns `None` if not found.
-fn find_default_option(default: &str, options: &[String]) -> Option<usize> {
-    let d = default.to_lowercase();
-    options.iter().position(|opt| opt.to_lowercase() == d)
+fn find_default_option(default: &str, options: &[String], flags: &Flags) -> Option<usize> {
+    options.iter().position(|opt| is_default(opt, default, flags))
+}
+
+/// An option as written per `--print`: its text, its original index, or
+/// `INDEX<sep>VALUE`.
+fn choice_text(value: &str, idx: usize, flags: &Flags) -> String {
+    match flags.print_field {
+        PrintField::Value => value.to_string(),
+        PrintField::Index => idx.to_string(),
+        PrintField::Both => format!("{}{}{}", idx, flags.print_sep, value),
+    }
+}
+
+/// Write the chosen option to the data channel per `--print`.
+fn emit_choice(value: &str, idx: usize, flags: &Flags, output_to_stderr: bool) {
+    output::output_str(&choice_text(value, idx, flags), output_to_stderr, flags.both);
+}
+
+/// Pick an option for Enter on an empty filter per `--enter-empty` (not
+/// used for `current`, the plain Enter behavior).  Returns the original
+/// option index, or `None` to ignore the key.
+fn enter_empty_pick(flags: &Flags, options: &[String], matches: &[usize], default_string: &Option<String>) -> Option<usize> {
+    match flags.enter_empty {
+        EnterEmpty::First => matches.first().copied(),
+        EnterEmpty::Default => default_string.as_ref().and_then(|ds| find_default_option(ds, options, flags)),
+        EnterEmpty::Current | EnterEmpty::None => None,
+    }
+}
+
+/// Pick an option on timeout per `--timeout-select` (used when no `-d` applies).
+/// Returns the original option index, or `None` to keep the plain timeout.
+fn timeout_pick(flags: &Flags, matches: &[usize], match_idx: usize) -> Option<usize> {
+    match flags.timeout_select {
+        TimeoutSelect::None => None,
+        TimeoutSelect::First => matches.first().copied(),
+        TimeoutSelect::Current => matches.get(match_idx).copied(),
+    }
+}
+
+/// Placeholder shown when nothing matches.  An empty filter only has no
+/// matches under `--empty=none`, so prompt the user to start typing.
+fn no_match_text<'a>(filter: &[u8], flags: &'a Flags) -> &'a str {
+    if filter.is_empty() {
+        "(type to filter)"
+    } else {
+        flags.no_match_text.as_deref().unwrap_or("(no matches)")
+    }
+}
+
+/// Write the widget line, in red when nothing matches and
+/// `--no-match-color` is on.  The color codes take no columns.
+fn write_line(stderr: &mut impl Write, line: &str, no_match: bool, flags: &Flags) {
+    let red = no_match && flags.no_match_color;
+    if red {
+        let _ = stderr.write_all(RED_ON);
+    }
+    let _ = stderr.write_all(line.as_bytes());
+    if red {
+        let _ = stderr.write_all(COLOR_OFF);
+    }
 }
 
 /// Render the select widget on stderr.
-/// Layout: `<filter_text> → <matched_option> (N matches) ↑↓`
+/// Layout: `<filter_text> → <matched_option> (N matches) ↑↓`, or just
+/// `<filter_text> → <matched_option>` with `--no-trailing-match-count`
 fn render_select_line(
     filter: &[u8],
     cursor_pos: usize,
     options: &[String],
     matches: &[usize],
     match_idx: usize,
+    flags: &Flags,
     prev_total_width: &mut usize,
 ) {
-    let mut stderr = io::stderr();
+    let mut stderr = output::tty();
 
     // Move back to start of widget
-    if *prev_total_width > 0 {
-        output::cursor_left_n(&mut stderr, *prev_total_width);
-    }
+    output::to_origin(&mut stderr, *prev_total_width);
     let _ = stderr.write_all(CLEAR_TO_EOL);
 
     // Build the display line
     let filter_str = String::from_utf8_lossy(filter);
     let match_display = if matches.is_empty() {
-        "(no matches)".to_string()
+        no_match_text(filter, flags).to_string()
     } else {
         options[matches[match_idx]].clone()
     };
-    let hint = format!(
-        "{} \u{2192} {} ({} match{}) \u{2191}\u{2193}",
-        filter_str,
-        match_display,
-        matches.len(),
-        if matches.len() == 1 { "" } else { "es" }
-    );
+    let hint = if flags.no_match_count {
+        format!("{} \u{2192} {}", filter_str, match_display)
+    } else {
+        format!(
+            "{} \u{2192} {} ({} match{}) \u{2191}\u{2193}",
+            filter_str,
+            match_display,
+            matches.len(),
+            if matches.len() == 1 { "" } else { "es" }
+        )
+    };
 
-    let _ = stderr.write_all(hint.as_bytes());
+    write_line(&mut stderr, &hint, matches.is_empty(), flags);
 
     // Calculate total display width (approximate: count chars)
     let total_width = hint.chars().count();
@@ -155,232 +354,360 @@ fn render_select_line(
 /// Clear the select widget from stderr.
 fn clear_select_line(prev_total_width: &mut usize) {
     if *prev_total_width > 0 {
-        let mut stderr = io::stderr();
-        output::cursor_left_n(&mut stderr, *prev_total_width);
+        let mut stderr = output::tty();
+        output::to_origin(&mut stderr, *prev_total_width);
         let _ = stderr.write_all(CLEAR_TO_EOL);
         let _ = stderr.flush();
         *prev_total_width = 0;
     }
 }
 
-pub fn run_select_mode(
+/// `--preview`: reserve the preview rows (not in silent mode).  The
+/// preview clears itself when the loop returns and it is dropped.
+fn open_preview(flags: &Flags) -> Option<Preview> {
+    match flags.preview {
+        Some(ref cmd) if !flags.silent => Some(Preview::open(cmd, flags.spinner)),
+        _ => None,
+    }
+}
+
+/// `--watch-fd`: the highlighted option as last written to the watch fd.
+struct Watch {
+    fd: i32,
+    sent: Option<(Option<usize>, Vec<u8>)>, // (highlighted option, filter); None = nothing sent yet
+}
+
+impl Watch {
+    fn open(flags: &Flags) -> Option<Watch> {
+        flags.watch_fd.map(|fd| Watch { fd, sent: None })
+    }
+
+    /// Write the highlighted option (an empty line when nothing matches)
+    /// if the highlight or the filter changed since the last write.
+    fn update(&mut self, options: &[String], current: Option<usize>, filter: &[u8]) {
+        if self.sent.as_ref().is_some_and(|(c, f)| *c == current && f == filter) {
+            return;
+        }
+        let mut line = current.map(|i| options[i].clone()).unwrap_or_default().into_bytes();
+        line.push(b'\n');
+        write_fd(self.fd, &line);
+        self.sent = Some((current, filter.to_vec()));
+    }
+}
+
+/// Write all of `bytes` to `fd`, stopping quietly if the reader went away;
+/// the picker carries on regardless.
+fn write_fd(fd: i32, bytes: &[u8]) {
+    let mut written = 0;
+    while written < bytes.len() {
+        let n = unsafe { libc::write(fd, bytes[written..].as_ptr() as *const libc::c_void, bytes.len() - written) };
+        if n <= 0 {
+            break;
+        }
+        written += n as usize;
+    }
+}
+
+/// `--timeout-dump`: the exit code when a timeout lists the matches.
+const TIMEOUT_DUMP_CODE: i32 = 253;
+
+/// Write the outcome of a select: beep on `--beep-on-complete` for a real
+/// choice, then the chosen option (unless silent or in JSON mode).
+fn finish(result: &SelectResult, flags: &Flags, output_to_stderr: bool) {
+    if result.status == "cancelled" {
+        output::emit_cancel(flags, output_to_stderr);
+    }
+    let Some(idx) = result.index else {
+        return;
+    };
+    if !result.timed_out {
+        // Chosen with Enter (or an accept key), possibly the -d option
+        output::beep_on_complete(flags);
+        if flags.copy {
+            output::copy_to_clipboard(&result.value);
+        }
+    }
+    if !flags.silent && flags.json.is_none() {
+        emit_choice(&result.value, idx as usize, flags, output_to_stderr);
+    }
+    if flags.json.is_none() {
+        output::echo_result(&result.value, flags);
+    }
+}
+
+/// Original indices of `options` in display order.  The sort is stable,
+/// so options that compare equal keep their list order.
+fn display_order(options: &[String], sort: SelectSort) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..options.len()).collect();
+    match sort {
+        SelectSort::None => {}
+        SelectSort::Alpha => order.sort_by(|&a, &b| options[a].cmp(&options[b])),
+        SelectSort::AlphaCi => order.sort_by_cached_key(|&i| options[i].to_lowercase()),
+        SelectSort::Len => order.sort_by_key(|&i| options[i].chars().count()),
+    }
+    order
+}
+
+/// `--initials`: each option's hotkey as (byte offset in the option,
+/// lowercase key).  The key is the first letter or digit no earlier option
+/// took, so duplicates fall through to their next distinct letter; an
+/// option with none left has no hotkey.
+fn initials(options: &[String]) -> Vec<Option<(usize, u8)>> {
+    let mut taken = [false; 128];
+    options
+        .iter()
+        .map(|opt| {
+            let (at, c) = opt
+                .char_indices()
+                .find(|&(_, c)| c.is_ascii_alphanumeric() && !taken[c.to_ascii_lowercase() as usize])?;
+            let key = c.to_ascii_lowercase() as u8;
+            taken[key as usize] = true;
+            Some((at, key))
+        })
+        .collect()
+}
+
+/// An option as drawn with `--initials`: the hotkey in parentheses.
+fn initials_label(opt: &str, hotkey: Option<(usize, u8)>) -> String {
+    match hotkey {
+        Some((at, _)) => format!("{}({}){}", &opt[..at], &opt[at..at + 1], &opt[at + 1..]),
+        None => opt.to_string(),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run(
     options: &[String],
+    values: &[String],
+    confirm: &[bool],
     flags: &Flags,
     default_string: &Option<String>,
     output_to_stderr: bool,
     stdin_fd: i32,
+    mut layout: Layout,
 ) -> SelectResult {
-    let mut filter: Vec<u8> = Vec::new();
-    let mut cursor_pos: usize = 0;
-    let mut matches = compute_matches(options, "", &flags.filter_style);
-    let mut match_idx: usize = 0;
-    let mut prev_width: usize = 0;
-
-    // If -d is set, find and highlight that option initially
-    if let Some(ds) = default_string {
-        match_idx = find_default_match(ds, options, &matches);
+    // --sort: the widget works on the sorted view; map its pick back to
+    // the original position, which is what the exit code reports
+    let order = display_order(options, flags.sort);
+    let view: Vec<String> = order.iter().map(|&i| options[i].clone()).collect();
+    let view_confirm: Vec<bool> = order.iter().map(|&i| confirm[i]).collect();
+    let mut result = if flags.tree {
+        run_tree(&view, &view_confirm, flags, default_string, stdin_fd, &mut layout)
+    } else {
+        select_loop(&view, &view_confirm, flags, default_string, stdin_fd, &mut layout)
+    };
+    // --timeout-dump: what was listed when the user walked away, by the
+    // filter they left, with each option's original index
+    if let Some(fd) = flags.timeout_dump.filter(|_| result.timed_out) {
+        let mut dump = String::new();
+        for i in compute_matches(&view, &result.filter, flags) {
+            dump.push_str(&format!("{}\t{}\n", order[i], values[order[i]]));
+        }
+        write_fd(fd, dump.as_bytes());
+    }
+    if let Some(i) = result.index {
+        let original = order[i as usize] as i32;
+        result.index = Some(original);
+        result.exit_code = original;
+    }
+    // --keep-widget: the widget has been cleared; leave the choice in its
+    // place for the scrollback.  Not when the value goes to the terminal.
+    if flags.keep_widget && !flags.silent && !output_to_stderr && !flags.both
+        && result.index.is_some() && !result.timed_out
+    {
+        let mut stderr = output::tty();
+        let _ = write!(stderr, "{} \u{2192} {}", result.filter, result.value);
+        let _ = stderr.flush();
+    }
+    if let Some(i) = result.index {
+        result.value = values[i as usize].clone();
+        // --exit-map: by value, else by label; unmapped keeps the index
+        let mapped = flags.exit_map.as_ref().and_then(|map| map.get(&result.value).or_else(|| map.get(&options[i as usize])));
+        if let Some(&code) = mapped {
+            result.exit_code = code;
+        }
+    }
+    if result.index.is_some() {
+        result.value = flags.post.apply(&result.value);
     }
+    finish(&result, flags, output_to_stderr);
+    result
+}
 
-    // Initial render
-    if !flags.silent {
-        render_select_line(&filter, cursor_pos, options, &matches, match_idx, &mut prev_width);
+/// `--json-file`: (label, value) pairs from a JSON array whose items are
+/// strings (or numbers), or objects with a `value` and optionally a
+/// `label` to show instead.  Other members are ignored, `key` included:
+/// hotkeys come from `--initials`, which picks them so they never clash.
+pub fn json_options(text: &str) -> Result<Vec<(String, String)>, String> {
+    let json::Value::Array(items) = json::parse(text)? else {
+        return Err("expected an array of options".to_string());
+    };
+    items
+        .iter()
+        .enumerate()
+        .map(|(n, item)| {
+            let value = match item {
+                json::Value::Object(_) => item.get("value").and_then(json::Value::as_text),
+                _ => item.as_text(),
+            };
+            let Some(value) = value.filter(|v| !v.is_empty()) else {
+                return Err(format!("item {}: expected a string or an object with a \"value\"", n));
+            };
+            let label = item.get("label").and_then(json::Value::as_text).unwrap_or_else(|| value.clone());
+            Ok((label, value))
+        })
+        .collect()
+}
+
+/// `--list-matches`: write every option `filter` matches, one per line in
+/// the order the widget would list them, and return the exit code: 0 if
+/// anything matched, 1 if not.  Nothing is read and the terminal is left
+/// alone, so this works in a pipeline.
+pub fn list_matches(options: &[String], values: &[String], filter: &str, flags: &Flags, output_to_stderr: bool) -> i32 {
+    let order = display_order(options, flags.sort);
+    let view: Vec<String> = order.iter().map(|&i| options[i].clone()).collect();
+    let matches = compute_matches(&view, filter, flags);
+    for &i in &matches {
+        let line = choice_text(&flags.post.apply(&values[order[i]]), order[i], flags);
+        output::output_str(&format!("{}\n", line), output_to_stderr, flags.both);
     }
+    if matches.is_empty() { 1 } else { 0 }
+}
 
-    let filter_str_fn = |f: &[u8]| String::from_utf8_lossy(f).into_owned();
+pub fn run_select_mode(
+    options: &[String],
+    values: &[String],
+    confirm: &[bool],
+    flags: &Flags,
+    default_string: &Option<String>,
+    output_to_stderr: bool,
+    stdin_fd: i32,
+) -> SelectResult {
+    run(options, values, confirm, flags, default_string, output_to_stderr, stdin_fd, Layout::Compact)
+}
 
-    loop {
-        // Check timeout
-        if TIMED_OUT.load(Ordering::Relaxed) {
-            if let Some(ds) = default_string {
-                if let Some(i) = find_default_option(ds, options) {
-                    if !flags.silent {
-                        clear_select_line(&mut prev_width);
-                        if flags.json.is_none() {
-                            output::output_str(&options[i], output_to_stderr, flags.both);
-                        }
-                    }
-                    return SelectResult { exit_code: i as i32, value: options[i].clone(), status: "default", timed_out: true, default_used: true, index: Some(i as i32), filter: filter_str_fn(&filter) };
+pub fn run_select_lr_mode(
+    options: &[String],
+    values: &[String],
+    confirm: &[bool],
+    flags: &Flags,
+    default_string: &Option<String>,
+    output_to_stderr: bool,
+    stdin_fd: i32,
+) -> SelectResult {
+    run(options, values, confirm, flags, default_string, output_to_stderr, stdin_fd, Layout::Horizontal)
+}
+
+// ---------------------------------------------------------------------------
+// Tree navigation (--tree)
+// ---------------------------------------------------------------------------
+
+/// One entry at the current tree level: a leaf (an option ending here) or a
+/// branch (a segment with options below it).
+struct TreeEntry {
+    name: String,
+    branch: bool,
+    index: usize, // original option index, for leaves
+}
+
+/// The entries directly under `prefix`, in order of first appearance.
+fn tree_level(options: &[String], prefix: &str, sep: &str) -> Vec<TreeEntry> {
+    let mut level: Vec<TreeEntry> = Vec::new();
+    for (i, opt) in options.iter().enumerate() {
+        let Some(rest) = opt.strip_prefix(prefix) else {
+            continue;
+        };
+        match rest.split_once(sep) {
+            Some((head, _)) => {
+                if !level.iter().any(|e| e.branch && e.name == head) {
+                    level.push(TreeEntry { name: head.to_string(), branch: true, index: i });
                 }
             }
-            if !flags.silent {
-                clear_select_line(&mut prev_width);
-            }
-            return SelectResult { exit_code: 254, value: String::new(), status: "timeout", timed_out: true, default_used: false, index: None, filter: filter_str_fn(&filter) };
+            None => level.push(TreeEntry { name: rest.to_string(), branch: false, index: i }),
         }
+    }
+    level
+}
 
-        let key = match input::read_key(stdin_fd) {
-            Ok(k) => k,
-            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
-            Err(_) => break,
-        };
+/// `--tree`: options are paths split on `--tree-sep`.  Each level shows only
+/// the next segment (branches with a trailing separator); choosing a branch
+/// descends into it with a fresh filter, and choosing a leaf returns the
+/// full path and its original index.
+fn run_tree(
+    options: &[String],
+    confirm: &[bool],
+    flags: &Flags,
+    default_string: &Option<String>,
+    stdin_fd: i32,
+    layout: &mut Layout,
+) -> SelectResult {
+    let sep = flags.tree_sep.as_str();
+    let mut prefix = String::new();
+    loop {
+        let level = tree_level(options, &prefix, sep);
+        let labels = tree_labels(&level, sep);
+        let level_confirm: Vec<bool> = level.iter().map(|e| !e.branch && confirm[e.index]).collect();
+        let level_default = tree_default(default_string, &prefix, sep);
 
-        match key {
-            KeyInput::Char(b) => {
-                let ch = apply_char_filters(b as char, flags, &None, &None).unwrap_or(b as char);
-                filter.insert(cursor_pos, ch as u8);
-                cursor_pos += 1;
-                recompute_and_render(&filter, options, &mut matches, &mut match_idx, flags,
-                    |f, o, m, mi| render_select_line(f, cursor_pos, o, m, mi, &mut prev_width));
-            }
-            KeyInput::Backspace => {
-                if cursor_pos > 0 {
-                    filter.remove(cursor_pos - 1);
-                    cursor_pos -= 1;
-                    recompute_and_render(&filter, options, &mut matches, &mut match_idx, flags,
-                        |f, o, m, mi| render_select_line(f, cursor_pos, o, m, mi, &mut prev_width));
-                }
-            }
-            KeyInput::Delete => {
-                if cursor_pos < filter.len() {
-                    filter.remove(cursor_pos);
-                    recompute_and_render(&filter, options, &mut matches, &mut match_idx, flags,
-                        |f, o, m, mi| render_select_line(f, cursor_pos, o, m, mi, &mut prev_width));
-                }
-            }
-            KeyInput::Left => {
-                if cursor_pos > 0 {
-                    cursor_pos -= 1;
-                    if !flags.silent {
-                        let _ = io::stderr().write_all(CURSOR_LEFT);
-                        let _ = io::stderr().flush();
-                    }
-                }
-            }
-            KeyInput::Right => {
-                if cursor_pos < filter.len() {
-                    cursor_pos += 1;
-                    if !flags.silent {
-                        let _ = io::stderr().write_all(CURSOR_RIGHT);
-                        let _ = io::stderr().flush();
-                    }
-                }
-            }
-            KeyInput::Home => {
-                if cursor_pos > 0 {
-                    if !flags.silent {
-                        let mut stderr = io::stderr();
-                        output::cursor_left_n(&mut stderr, cursor_pos);
-                        let _ = stderr.flush();
-                    }
-                    cursor_pos = 0;
-                }
-            }
-            KeyInput::End => {
-                if cursor_pos < filter.len() {
-                    let delta = filter.len() - cursor_pos;
-                    if !flags.silent {
-                        let mut stderr = io::stderr();
-                        output::cursor_right_n(&mut stderr, delta);
-                        let _ = stderr.flush();
-                    }
-                    cursor_pos = filter.len();
-                }
-            }
-            KeyInput::KillToEnd => {
-                if cursor_pos < filter.len() {
-                    filter.truncate(cursor_pos);
-                    recompute_and_render(&filter, options, &mut matches, &mut match_idx, flags,
-                        |f, o, m, mi| render_select_line(f, cursor_pos, o, m, mi, &mut prev_width));
-                }
-            }
-            KeyInput::KillToStart => {
-                if cursor_pos > 0 {
-                    filter.drain(..cursor_pos);
-                    cursor_pos = 0;
-                    recompute_and_render(&filter, options, &mut matches, &mut match_idx, flags,
-                        |f, o, m, mi| render_select_line(f, cursor_pos, o, m, mi, &mut prev_width));
-                }
-            }
-            KeyInput::KillWordBack => {
-                if cursor_pos > 0 {
-                    let mut new_pos = cursor_pos;
-                    while new_pos > 0 && filter[new_pos - 1] == b' ' {
-                        new_pos -= 1;
-                    }
-                    while new_pos > 0 && filter[new_pos - 1] != b' ' {
-                        new_pos -= 1;
-                    }
-                    filter.drain(new_pos..cursor_pos);
-                    cursor_pos = new_pos;
-                    recompute_and_render(&filter, options, &mut matches, &mut match_idx, flags,
-                        |f, o, m, mi| render_select_line(f, cursor_pos, o, m, mi, &mut prev_width));
-                }
-            }
-            KeyInput::Up => {
-                if !matches.is_empty() {
-                    if match_idx == 0 {
-                        match_idx = matches.len() - 1;
-                    } else {
-                        match_idx -= 1;
-                    }
-                    if !flags.silent {
-                        render_select_line(&filter, cursor_pos, options, &matches, match_idx, &mut prev_width);
-                    }
-                }
-            }
-            KeyInput::Down => {
-                if !matches.is_empty() {
-                    match_idx = (match_idx + 1) % matches.len();
-                    if !flags.silent {
-                        render_select_line(&filter, cursor_pos, options, &matches, match_idx, &mut prev_width);
-                    }
-                }
-            }
-            KeyInput::Tab => {
-                if !matches.is_empty() {
-                    let selected = options[matches[match_idx]].clone();
-                    filter = selected.as_bytes().to_vec();
-                    cursor_pos = filter.len();
-                    let filter_str = String::from_utf8_lossy(&filter);
-                    matches = compute_matches(options, &filter_str, &flags.filter_style);
-                    // Find the same option in the new matches
-                    match_idx = 0;
-                    let sel_lower = selected.to_lowercase();
-                    for (i, idx) in matches.iter().enumerate() {
-                        if options[*idx].to_lowercase() == sel_lower {
-                            match_idx = i;
-                            break;
-                        }
-                    }
-                    if !flags.silent {
-                        render_select_line(&filter, cursor_pos, options, &matches, match_idx, &mut prev_width);
-                    }
-                }
-            }
-            KeyInput::Enter => {
-                if !matches.is_empty() {
-                    let original_idx = matches[match_idx];
-                    let selected = &options[original_idx];
-                    if !flags.silent {
-                        clear_select_line(&mut prev_width);
-                        if flags.json.is_none() {
-                            output::output_str(selected, output_to_stderr, flags.both);
-                        }
-                    }
-                    return SelectResult { exit_code: original_idx as i32, value: selected.clone(), status: "ok", timed_out: false, default_used: false, index: Some(original_idx as i32), filter: filter_str_fn(&filter) };
-                }
-                // If no matches, Enter does nothing
-            }
-            KeyInput::Escape => {
-                let esc_exit = match flags.esc_code {
-                    Some(0) => { continue; } // no-op
-                    Some(n) => n,
-                    None => 255,
+        let mut result = select_loop(&labels, &level_confirm, flags, &level_default, stdin_fd, layout);
+        let Some(i) = result.index.map(|i| i as usize) else {
+            return result;
+        };
+        let entry = &level[i];
+        if entry.branch {
+            prefix = format!("{}{}{}", prefix, entry.name, sep);
+            if result.timed_out {
+                // -d took the timeout to this branch: follow the rest of its
+                // path to the leaf.  Anything else has no leaf to return.
+                let leaf = result.default_used.then(|| tree_default_leaf(options, confirm, flags, default_string, prefix)).flatten();
+                return match leaf {
+                    Some(idx) => SelectResult { exit_code: idx as i32, value: options[idx].clone(), status: "default", timed_out: true, default_used: true, index: Some(idx as i32), filter: result.filter },
+                    None => SelectResult { exit_code: flags.timeout_code, value: String::new(), status: "timeout", timed_out: true, default_used: false, index: None, filter: result.filter },
                 };
-                if !flags.silent {
-                    clear_select_line(&mut prev_width);
-                }
-                return SelectResult { exit_code: esc_exit, value: String::new(), status: "cancelled", timed_out: false, default_used: false, index: None, filter: filter_str_fn(&filter) };
             }
-            KeyInput::Unknown => {}
+            continue;
         }
+        result.exit_code = entry.index as i32;
+        result.index = Some(entry.index as i32);
+        result.value = options[entry.index].clone();
+        return result;
     }
+}
 
-    // EOF or error
-    if !flags.silent {
-        clear_select_line(&mut prev_width);
+/// The labels shown for a tree level: branches carry a trailing separator.
+fn tree_labels(level: &[TreeEntry], sep: &str) -> Vec<String> {
+    level
+        .iter()
+        .map(|e| if e.branch { format!("{}{}", e.name, sep) } else { e.name.clone() })
+        .collect()
+}
+
+/// -d names a full path; at the level under `prefix` it picks out the next
+/// segment.
+fn tree_default(default_string: &Option<String>, prefix: &str, sep: &str) -> Option<String> {
+    default_string.as_ref().and_then(|ds| {
+        let rest = ds.strip_prefix(prefix)?;
+        Some(match rest.split_once(sep) {
+            Some((head, _)) => format!("{}{}", head, sep),
+            None => rest.to_string(),
+        })
+    })
+}
+
+/// Follow the -d path down from `prefix` without asking, as a timeout does.
+/// Returns the leaf's original index, or `None` if the path does not lead
+/// to one or the leaf needs --confirm.
+fn tree_default_leaf(options: &[String], confirm: &[bool], flags: &Flags, default_string: &Option<String>, mut prefix: String) -> Option<usize> {
+    let sep = flags.tree_sep.as_str();
+    loop {
+        let level = tree_level(options, &prefix, sep);
+        let ds = tree_default(default_string, &prefix, sep)?;
+        let entry = &level[find_default_option(&ds, &tree_labels(&level, sep), flags)?];
+        if !entry.branch {
+            return (!confirm[entry.index]).then_some(entry.index);
+        }
+        prefix = format!("{}{}{}", prefix, entry.name, sep);
     }
-    SelectResult { exit_code: 255, value: String::new(), status: "cancelled", timed_out: false, default_used: false, index: None, filter: filter_str_fn(&filter) }
 }
 
 // ---------------------------------------------------------------------------
@@ -388,30 +715,30 @@ pub fn run_select_mode(
 // ---------------------------------------------------------------------------
 
 /// Render the select-lr widget on stderr.
-/// Layout: `<filter_text> → highlight(match1) match2 match3 ... (N matches)`
+/// Layout: `<filter_text> → highlight(match1) match2 match3 ... (N matches)`,
+/// or `... [I/N]` with `--position-indicator`, and no suffix at all with
+/// `--no-trailing-match-count`
 fn render_select_lr_line(
     filter: &[u8],
     cursor_pos: usize,
     options: &[String],
     matches: &[usize],
     match_idx: usize,
-    highlight_style: &HighlightStyle,
+    flags: &Flags,
     prev_total_width: &mut usize,
 ) {
-    let mut stderr = io::stderr();
+    let mut stderr = output::tty();
 
     // Move back to start of widget
-    if *prev_total_width > 0 {
-        output::cursor_left_n(&mut stderr, *prev_total_width);
-    }
+    output::to_origin(&mut stderr, *prev_total_width);
     let _ = stderr.write_all(CLEAR_TO_EOL);
 
     let filter_str = String::from_utf8_lossy(filter);
 
     if matches.is_empty() {
-        let hint = format!("{} \u{2192} (no matches)", filter_str);
+        let hint = format!("{} \u{2192} {}", filter_str, no_match_text(filter, flags));
         let total_width = hint.chars().count();
-        let _ = stderr.write_all(hint.as_bytes());
+        write_line(&mut stderr, &hint, true, flags);
         let tail = total_width - cursor_pos;
         if tail > 0 {
             output::cursor_left_n(&mut stderr, tail);
@@ -434,7 +761,7 @@ fn render_select_lr_line(
         }
         let opt = &options[opt_idx];
         if i == match_idx {
-            match highlight_style {
+            match flags.highlight_style {
                 HighlightStyle::Reverse => {
                     let _ = stderr.write_all(REVERSE_ON);
                     let _ = stderr.write_all(opt.as_bytes());
@@ -460,12 +787,18 @@ fn render_select_lr_line(
         }
     }
 
-    // Append match count
-    let count_str = format!(
-        "  ({} match{})",
-        matches.len(),
-        if matches.len() == 1 { "" } else { "es" }
-    );
+    // Append match count, with the highlighted match's position if asked
+    let count_str = if flags.no_match_count {
+        String::new()
+    } else if flags.position_indicator {
+        format!("  [{}/{}]", match_idx + 1, matches.len())
+    } else {
+        format!(
+            "  ({} match{})",
+            matches.len(),
+            if matches.len() == 1 { "" } else { "es" }
+        )
+    };
     let _ = stderr.write_all(count_str.as_bytes());
     display_width += count_str.chars().count();
 
@@ -479,83 +812,320 @@ fn render_select_lr_line(
     let _ = stderr.flush();
 }
 
-pub fn run_select_lr_mode(
+// ---------------------------------------------------------------------------
+// The widget loop (both layouts)
+// ---------------------------------------------------------------------------
+
+/// Which way the widget is drawn.  `select` starts compact and `select-lr`
+/// horizontal; Ctrl-G switches between them, keeping the filter and the
+/// highlighted match.
+#[derive(Clone, Copy, PartialEq)]
+enum Layout {
+    Compact,    // select: `<filter> → <match> (N matches) ↑↓`
+    Horizontal, // select-lr: every match on the line
+}
+
+/// Ctrl-G: toggle the layout.  Read as a plain byte, so it only means
+/// anything inside select.
+const TOGGLE_LAYOUT: u8 = 0x07;
+
+#[allow(clippy::too_many_arguments)]
+fn render_widget(
+    layout: Layout,
+    filter: &[u8],
+    cursor_pos: usize,
     options: &[String],
+    matches: &[usize],
+    match_idx: usize,
+    flags: &Flags,
+    prev_total_width: &mut usize,
+) {
+    match layout {
+        Layout::Compact => render_select_line(filter, cursor_pos, options, matches, match_idx, flags, prev_total_width),
+        Layout::Horizontal => render_select_lr_line(filter, cursor_pos, options, matches, match_idx, flags, prev_total_width),
+    }
+}
+
+/// `--no-filter`: the position in `matches` of the next option after
+/// `current` (wrapping) whose first character is `c`, ignoring case.
+fn next_by_initial(options: &[String], matches: &[usize], current: usize, c: char) -> Option<usize> {
+    let wanted: String = c.to_lowercase().collect();
+    (1..=matches.len())
+        .map(|step| (current + step) % matches.len())
+        .find(|&pos| {
+            options[matches[pos]].chars().next().is_some_and(|first| first.to_lowercase().collect::<String>() == wanted)
+        })
+}
+
+/// `--confirm`: whether choosing option `i` has to wait for a second Enter.
+/// The first Enter on a marked option asks; the next one, if nothing else
+/// was pressed in between, goes through.
+fn awaits_confirm(confirm: &[bool], pending: &mut Option<usize>, i: usize) -> bool {
+    if confirm.get(i) != Some(&true) || *pending == Some(i) {
+        return false;
+    }
+    *pending = Some(i);
+    true
+}
+
+/// The options as drawn while option `i` waits for its second Enter.
+fn confirm_labels(labels: &[String], i: usize) -> Vec<String> {
+    let mut shown = labels.to_vec();
+    shown[i] = format!("{} (press Enter again to confirm)", labels[i]);
+    shown
+}
+
+/// Run the widget until a choice, timeout, or cancel.  The layout is left
+/// as the user toggled it, so `--tree` keeps it from level to level.
+#[allow(clippy::collapsible_if)]
+fn select_loop(
+    options: &[String],
+    confirm: &[bool],
     flags: &Flags,
     default_string: &Option<String>,
-    output_to_stderr: bool,
     stdin_fd: i32,
+    layout: &mut Layout,
 ) -> SelectResult {
     let mut filter: Vec<u8> = Vec::new();
     let mut cursor_pos: usize = 0;
-    let mut matches = compute_matches(options, "", &flags.filter_style);
+    let mut matches = compute_matches(options, "", flags);
     let mut match_idx: usize = 0;
     let mut prev_width: usize = 0;
+    // --initials: hotkeys, and the options as drawn with them marked
+    let hotkeys = if flags.initials { initials(options) } else { Vec::new() };
+    let marked: Vec<String> = hotkeys.iter().zip(options).map(|(&h, o)| initials_label(o, h)).collect();
+    let labels: &[String] = if flags.initials { &marked } else { options };
+    // --confirm: the marked option the last Enter asked to have confirmed
+    let mut pending_confirm: Option<usize> = None;
 
-    // If -d is set, find and highlight that option initially
+    // If -d is set, find and highlight that option initially; otherwise
+    // --default-re highlights the first option it matches
     if let Some(ds) = default_string {
-        match_idx = find_default_match(ds, options, &matches);
+        match_idx = find_default_match(ds, options, &matches, flags);
+    } else if let Some(ref re) = flags.default_re {
+        match_idx = matches.iter().position(|&i| re.is_match(&options[i])).unwrap_or(0);
     }
 
     // Initial render
     if !flags.silent {
-        render_select_lr_line(
-            &filter, cursor_pos, options, &matches, match_idx,
-            &flags.highlight_style, &mut prev_width,
-        );
+        render_widget(*layout, &filter, cursor_pos, labels, &matches, match_idx, flags, &mut prev_width);
     }
+    let mut preview = open_preview(flags);
+    let mut watch = Watch::open(flags);
 
     let filter_str_fn = |f: &[u8]| String::from_utf8_lossy(f).into_owned();
 
     loop {
-        // Check timeout
+        if let Some(ref mut w) = watch {
+            w.update(options, matches.get(match_idx).copied(), &filter);
+        }
+        if let Some(ref mut p) = preview {
+            p.update(stdin_fd, options, matches.get(match_idx).copied());
+        }
+        // Check timeout.  --timeout-dump lists the matches from `run`
+        // instead of picking one.
+        if TIMED_OUT.load(Ordering::Relaxed) && flags.timeout_dump.is_some() {
+            if !flags.silent {
+                clear_select_line(&mut prev_width);
+            }
+            return SelectResult { exit_code: TIMEOUT_DUMP_CODE, value: String::new(), status: "timeout", timed_out: true, default_used: false, index: None, filter: filter_str_fn(&filter) };
+        }
+        // A --confirm option is never returned without its second Enter
         if TIMED_OUT.load(Ordering::Relaxed) {
             if let Some(ds) = default_string {
-                if let Some(i) = find_default_option(ds, options) {
+                if let Some(i) = find_default_option(ds, options, flags).filter(|&i| confirm.get(i) != Some(&true)) {
                     if !flags.silent {
                         clear_select_line(&mut prev_width);
-                        if flags.json.is_none() {
-                            output::output_str(&options[i], output_to_stderr, flags.both);
-                        }
                     }
                     return SelectResult { exit_code: i as i32, value: options[i].clone(), status: "default", timed_out: true, default_used: true, index: Some(i as i32), filter: filter_str_fn(&filter) };
                 }
             }
+            if let Some(i) = timeout_pick(flags, &matches, match_idx).filter(|&i| confirm.get(i) != Some(&true)) {
+                if !flags.silent {
+                    clear_select_line(&mut prev_width);
+                }
+                return SelectResult { exit_code: i as i32, value: options[i].clone(), status: "timeout", timed_out: true, default_used: false, index: Some(i as i32), filter: filter_str_fn(&filter) };
+            }
             if !flags.silent {
                 clear_select_line(&mut prev_width);
             }
-            return SelectResult { exit_code: 254, value: String::new(), status: "timeout", timed_out: true, default_used: false, index: None, filter: filter_str_fn(&filter) };
+            return SelectResult { exit_code: flags.timeout_code, value: String::new(), status: "timeout", timed_out: true, default_used: false, index: None, filter: filter_str_fn(&filter) };
         }
 
-        let key = match input::read_key(stdin_fd) {
+        let mut key = match input::read_key(stdin_fd) {
+            // --accept-keys names Ctrl-D itself, not what --ctrl-d maps it to
+            Ok(KeyInput::CtrlD) if flags.accept_keys.as_ref().is_some_and(|k| k.contains(&KeyInput::CtrlD)) => KeyInput::Enter,
+            Ok(KeyInput::CtrlD) => ctrl_d_key(flags, filter.is_empty()).unwrap_or(KeyInput::Enter),
             Ok(k) => k,
             Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
             Err(_) => break,
         };
+        // --accept-keys confirm the highlight exactly as Enter does
+        if flags.accept_keys.as_ref().is_some_and(|k| k.contains(&key)) {
+            key = KeyInput::Enter;
+        }
+        // Any other key drops a pending confirmation, and its prompt
+        if !matches!(key, KeyInput::Enter) && pending_confirm.take().is_some() && !flags.silent {
+            render_widget(*layout, &filter, cursor_pos, labels, &matches, match_idx, flags, &mut prev_width);
+        }
 
+        let mode = *layout;
+        let horizontal = mode == Layout::Horizontal;
         match key {
+            KeyInput::Char(TOGGLE_LAYOUT) => {
+                *layout = if horizontal { Layout::Compact } else { Layout::Horizontal };
+                if !flags.silent {
+                    render_widget(*layout, &filter, cursor_pos, labels, &matches, match_idx, flags, &mut prev_width);
+                }
+            }
             KeyInput::Char(b) => {
+                if non_ascii_rejected(flags, stdin_fd, b) {
+                    continue;
+                }
+                // A hotkey picks its option outright; once a non-hotkey
+                // starts the filter, keys filter until it is empty again
+                if filter.is_empty() {
+                    let key = b.to_ascii_lowercase();
+                    if let Some(i) = hotkeys.iter().position(|h| h.is_some_and(|(_, k)| k == key)) {
+                        // A marked option's hotkey only moves to it; Enter confirms
+                        if awaits_confirm(confirm, &mut pending_confirm, i) {
+                            match_idx = matches.iter().position(|&m| m == i).unwrap_or(match_idx);
+                            if !flags.silent {
+                                render_widget(mode, &filter, cursor_pos, &confirm_labels(labels, i), &matches, match_idx, flags, &mut prev_width);
+                            }
+                            continue;
+                        }
+                        if !flags.silent {
+                            clear_select_line(&mut prev_width);
+                        }
+                        return SelectResult { exit_code: i as i32, value: options[i].clone(), status: "ok", timed_out: false, default_used: false, index: Some(i as i32), filter: String::new() };
+                    }
+                }
+                // --no-filter: a key jumps to the next option starting with it
+                if flags.no_filter {
+                    let typed = grapheme::decode(&input::read_codepoint(stdin_fd, b)).0;
+                    match typed.and_then(|c| next_by_initial(options, &matches, match_idx, c)) {
+                        Some(i) => {
+                            match_idx = i;
+                            if !flags.silent {
+                                render_widget(mode, &filter, cursor_pos, labels, &matches, match_idx, flags, &mut prev_width);
+                            }
+                        }
+                        None => output::alert(),
+                    }
+                    continue;
+                }
+                if input_cap_reached(flags, filter.len(), 1) {
+                    continue;
+                }
                 let ch = apply_char_filters(b as char, flags, &None, &None).unwrap_or(b as char);
                 filter.insert(cursor_pos, ch as u8);
                 cursor_pos += 1;
                 recompute_and_render(&filter, options, &mut matches, &mut match_idx, flags,
-                    |f, o, m, mi| render_select_lr_line(f, cursor_pos, o, m, mi, &flags.highlight_style, &mut prev_width));
+                    |f, _, m, mi| render_widget(mode, f, cursor_pos, labels, m, mi, flags, &mut prev_width));
             }
             KeyInput::Backspace => {
                 if cursor_pos > 0 {
                     filter.remove(cursor_pos - 1);
                     cursor_pos -= 1;
                     recompute_and_render(&filter, options, &mut matches, &mut match_idx, flags,
-                        |f, o, m, mi| render_select_lr_line(f, cursor_pos, o, m, mi, &flags.highlight_style, &mut prev_width));
+                        |f, _, m, mi| render_widget(mode, f, cursor_pos, labels, m, mi, flags, &mut prev_width));
                 }
             }
             KeyInput::Delete => {
                 if cursor_pos < filter.len() {
                     filter.remove(cursor_pos);
                     recompute_and_render(&filter, options, &mut matches, &mut match_idx, flags,
-                        |f, o, m, mi| render_select_lr_line(f, cursor_pos, o, m, mi, &flags.highlight_style, &mut prev_width));
+                        |f, _, m, mi| render_widget(mode, f, cursor_pos, labels, m, mi, flags, &mut prev_width));
+                }
+            }
+            // Compact: the cursor keys and kill keys edit the filter.  Alt-Left
+            // and Alt-Right move the filter cursor in either layout.
+            KeyInput::Left | KeyInput::AltLeft if !horizontal || matches!(key, KeyInput::AltLeft) => {
+                if cursor_pos > 0 {
+                    cursor_pos -= 1;
+                    if !flags.silent {
+                        let _ = output::tty().write_all(CURSOR_LEFT);
+                        let _ = output::tty().flush();
+                    }
+                }
+            }
+            KeyInput::Right | KeyInput::AltRight if !horizontal || matches!(key, KeyInput::AltRight) => {
+                if cursor_pos < filter.len() {
+                    cursor_pos += 1;
+                    if !flags.silent {
+                        let _ = output::tty().write_all(CURSOR_RIGHT);
+                        let _ = output::tty().flush();
+                    }
                 }
             }
+            KeyInput::Home if !horizontal => {
+                if cursor_pos > 0 {
+                    if !flags.silent {
+                        let mut stderr = output::tty();
+                        output::to_origin(&mut stderr, cursor_pos);
+                        let _ = stderr.flush();
+                    }
+                    cursor_pos = 0;
+                }
+            }
+            KeyInput::End if !horizontal => {
+                if cursor_pos < filter.len() {
+                    let delta = filter.len() - cursor_pos;
+                    if !flags.silent {
+                        let mut stderr = output::tty();
+                        output::cursor_right_n(&mut stderr, delta);
+                        let _ = stderr.flush();
+                    }
+                    cursor_pos = filter.len();
+                }
+            }
+            KeyInput::KillToEnd if !horizontal => {
+                if cursor_pos < filter.len() {
+                    filter.truncate(cursor_pos);
+                    recompute_and_render(&filter, options, &mut matches, &mut match_idx, flags,
+                        |f, _, m, mi| render_widget(mode, f, cursor_pos, labels, m, mi, flags, &mut prev_width));
+                }
+            }
+            KeyInput::KillToStart if !horizontal => {
+                if cursor_pos > 0 {
+                    filter.drain(..cursor_pos);
+                    cursor_pos = 0;
+                    recompute_and_render(&filter, options, &mut matches, &mut match_idx, flags,
+                        |f, _, m, mi| render_widget(mode, f, cursor_pos, labels, m, mi, flags, &mut prev_width));
+                }
+            }
+            KeyInput::KillWordBack if !horizontal => {
+                if cursor_pos > 0 {
+                    let mut new_pos = cursor_pos;
+                    while new_pos > 0 && filter[new_pos - 1] == b' ' {
+                        new_pos -= 1;
+                    }
+                    while new_pos > 0 && filter[new_pos - 1] != b' ' {
+                        new_pos -= 1;
+                    }
+                    filter.drain(new_pos..cursor_pos);
+                    cursor_pos = new_pos;
+                    recompute_and_render(&filter, options, &mut matches, &mut match_idx, flags,
+                        |f, _, m, mi| render_widget(mode, f, cursor_pos, labels, m, mi, flags, &mut prev_width));
+                }
+            }
+            KeyInput::KillWordForward if !horizontal => {
+                if cursor_pos < filter.len() {
+                    let mut end = cursor_pos;
+                    while end < filter.len() && filter[end] == b' ' {
+                        end += 1;
+                    }
+                    while end < filter.len() && filter[end] != b' ' {
+                        end += 1;
+                    }
+                    filter.drain(cursor_pos..end);
+                    recompute_and_render(&filter, options, &mut matches, &mut match_idx, flags,
+                        |f, _, m, mi| render_widget(mode, f, cursor_pos, labels, m, mi, flags, &mut prev_width));
+                }
+            }
+            // Horizontal: Left/Right browse the matches like Up/Down, Home/End
+            // jump to the first/last, and the kill keys clear the filter
             KeyInput::Left | KeyInput::Up => {
                 if !matches.is_empty() {
                     if match_idx == 0 {
@@ -564,10 +1134,7 @@ pub fn run_select_lr_mode(
                         match_idx -= 1;
                     }
                     if !flags.silent {
-                        render_select_lr_line(
-                            &filter, cursor_pos, options, &matches, match_idx,
-                            &flags.highlight_style, &mut prev_width,
-                        );
+                        render_widget(mode, &filter, cursor_pos, labels, &matches, match_idx, flags, &mut prev_width);
                     }
                 }
             }
@@ -575,10 +1142,7 @@ pub fn run_select_lr_mode(
                 if !matches.is_empty() {
                     match_idx = (match_idx + 1) % matches.len();
                     if !flags.silent {
-                        render_select_lr_line(
-                            &filter, cursor_pos, options, &matches, match_idx,
-                            &flags.highlight_style, &mut prev_width,
-                        );
+                        render_widget(mode, &filter, cursor_pos, labels, &matches, match_idx, flags, &mut prev_width);
                     }
                 }
             }
@@ -586,10 +1150,7 @@ pub fn run_select_lr_mode(
                 if !matches.is_empty() {
                     match_idx = 0;
                     if !flags.silent {
-                        render_select_lr_line(
-                            &filter, cursor_pos, options, &matches, match_idx,
-                            &flags.highlight_style, &mut prev_width,
-                        );
+                        render_widget(mode, &filter, cursor_pos, labels, &matches, match_idx, flags, &mut prev_width);
                     }
                 }
             }
@@ -597,68 +1158,101 @@ pub fn run_select_lr_mode(
                 if !matches.is_empty() {
                     match_idx = matches.len() - 1;
                     if !flags.silent {
-                        render_select_lr_line(
-                            &filter, cursor_pos, options, &matches, match_idx,
-                            &flags.highlight_style, &mut prev_width,
-                        );
+                        render_widget(mode, &filter, cursor_pos, labels, &matches, match_idx, flags, &mut prev_width);
                     }
                 }
             }
-            KeyInput::KillToEnd | KeyInput::KillToStart | KeyInput::KillWordBack => {
+            KeyInput::KillToEnd | KeyInput::KillToStart | KeyInput::KillWordBack | KeyInput::KillWordForward => {
                 // Clear the filter
                 filter.clear();
                 cursor_pos = 0;
                 recompute_and_render(&filter, options, &mut matches, &mut match_idx, flags,
-                    |f, o, m, mi| render_select_lr_line(f, cursor_pos, o, m, mi, &flags.highlight_style, &mut prev_width));
+                    |f, _, m, mi| render_widget(mode, f, cursor_pos, labels, m, mi, flags, &mut prev_width));
             }
             KeyInput::Tab => {
-                if !matches.is_empty() {
-                    let selected = options[matches[match_idx]].clone();
-                    filter = selected.as_bytes().to_vec();
+                if !matches.is_empty() && !flags.no_filter {
+                    let selected = matches[match_idx];
+                    filter = completed_filter(&options[selected], flags);
                     cursor_pos = filter.len();
                     let filter_str = String::from_utf8_lossy(&filter);
-                    matches = compute_matches(options, &filter_str, &flags.filter_style);
-                    match_idx = 0;
-                    let sel_lower = selected.to_lowercase();
-                    for (i, idx) in matches.iter().enumerate() {
-                        if options[*idx].to_lowercase() == sel_lower {
-                            match_idx = i;
-                            break;
-                        }
-                    }
+                    matches = compute_matches(options, &filter_str, flags);
+                    // Stay on the option that was completed
+                    match_idx = matches.iter().position(|&i| i == selected).unwrap_or(0);
                     if !flags.silent {
-                        render_select_lr_line(
-                            &filter, cursor_pos, options, &matches, match_idx,
-                            &flags.highlight_style, &mut prev_width,
-                        );
+                        render_widget(mode, &filter, cursor_pos, labels, &matches, match_idx, flags, &mut prev_width);
                     }
                 }
             }
             KeyInput::Enter => {
+                // --require-unique: narrow the filter to one match first
+                if flags.require_unique && matches.len() != 1 {
+                    output::alert();
+                    continue;
+                }
+                if filter.is_empty() && flags.enter_empty != EnterEmpty::Current {
+                    if let Some(i) = enter_empty_pick(flags, options, &matches, default_string) {
+                        if awaits_confirm(confirm, &mut pending_confirm, i) {
+                            match_idx = matches.iter().position(|&m| m == i).unwrap_or(match_idx);
+                            if !flags.silent {
+                                render_widget(mode, &filter, cursor_pos, &confirm_labels(labels, i), &matches, match_idx, flags, &mut prev_width);
+                            }
+                            continue;
+                        }
+                        if !flags.silent {
+                            clear_select_line(&mut prev_width);
+                        }
+                        let default_used = flags.enter_empty == EnterEmpty::Default;
+                        let status = if default_used { "default" } else { "ok" };
+                        return SelectResult { exit_code: i as i32, value: options[i].clone(), status, timed_out: false, default_used, index: Some(i as i32), filter: String::new() };
+                    }
+                    continue;
+                }
                 if !matches.is_empty() {
                     let original_idx = matches[match_idx];
+                    if awaits_confirm(confirm, &mut pending_confirm, original_idx) {
+                        if !flags.silent {
+                            render_widget(mode, &filter, cursor_pos, &confirm_labels(labels, original_idx), &matches, match_idx, flags, &mut prev_width);
+                        }
+                        continue;
+                    }
                     let selected = &options[original_idx];
                     if !flags.silent {
                         clear_select_line(&mut prev_width);
-                        if flags.json.is_none() {
-                            output::output_str(selected, output_to_stderr, flags.both);
-                        }
                     }
                     return SelectResult { exit_code: original_idx as i32, value: selected.clone(), status: "ok", timed_out: false, default_used: false, index: Some(original_idx as i32), filter: filter_str_fn(&filter) };
                 }
+                // If no matches, Enter does nothing
+            }
+            // A paste goes into the filter in one go
+            KeyInput::Paste(text) => {
+                if flags.no_filter {
+                    continue;
+                }
+                for b in input::paste_bytes(&text) {
+                    if flags.ascii_only && !b.is_ascii() {
+                        continue;
+                    }
+                    if input_cap_reached(flags, filter.len(), 1) {
+                        break;
+                    }
+                    let ch = apply_char_filters(b as char, flags, &None, &None).unwrap_or(b as char);
+                    filter.insert(cursor_pos, ch as u8);
+                    cursor_pos += 1;
+                }
+                recompute_and_render(&filter, options, &mut matches, &mut match_idx, flags,
+                    |f, _, m, mi| render_widget(mode, f, cursor_pos, labels, m, mi, flags, &mut prev_width));
             }
             KeyInput::Escape => {
-                let esc_exit = match flags.esc_code {
-                    Some(0) => { continue; } // no-op
-                    Some(n) => n,
-                    None => 255,
+                let Some(esc_exit) = escape_exit(flags, Some(255)) else {
+                    continue; // -B0: no-op
                 };
                 if !flags.silent {
                     clear_select_line(&mut prev_width);
                 }
                 return SelectResult { exit_code: esc_exit, value: String::new(), status: "cancelled", timed_out: false, default_used: false, index: None, filter: filter_str_fn(&filter) };
             }
-            KeyInput::Unknown => {}
+            // (Alt-Left/Alt-Right are always taken by the filter cursor arms)
+            KeyInput::AltLeft | KeyInput::AltRight | KeyInput::CtrlD | KeyInput::Count(_) | KeyInput::Function(_) | KeyInput::Unknown => {}
         }
     }
 
@@ -668,3 +1262,106 @@ pub fn run_select_lr_mode(
     }
     SelectResult { exit_code: 255, value: String::new(), status: "cancelled", timed_out: false, default_used: false, index: None, filter: filter_str_fn(&filter) }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opts(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn flags_with(strategy: MatchStrategy) -> Flags {
+        let mut flags = Flags::new();
+        flags.match_strategy = strategy;
+        flags
+    }
+
+    #[test]
+    fn prefix_hit_and_miss() {
+        assert_eq!(MatchStrategy::Prefix.matches("apple", "ap"), Some((0, 0, 0)));
+        assert_eq!(MatchStrategy::Prefix.matches("apple", "pl"), None);
+    }
+
+    #[test]
+    fn contains_hit_and_miss() {
+        assert_eq!(MatchStrategy::Contains.matches("apple", "pl"), Some((0, 0, 0)));
+        assert_eq!(MatchStrategy::Contains.matches("apple", "pa"), None);
+    }
+
+    #[test]
+    fn fuzzy_hit_and_miss() {
+        assert!(MatchStrategy::Fuzzy.matches("apple", "ale").is_some());
+        assert_eq!(MatchStrategy::Fuzzy.matches("apple", "ela"), None);
+        assert_eq!(MatchStrategy::Fuzzy.matches("apple", "apples"), None);
+    }
+
+    #[test]
+    fn fuzzy_score_groups() {
+        // start of option, then start of a word, then anywhere
+        assert_eq!(fuzzy_rank("config", "con"), (0, 0, 0));
+        assert_eq!(fuzzy_rank("my-config", "con"), (1, 0, 3));
+        assert_eq!(fuzzy_rank("deconfig", "con"), (2, 0, 2));
+    }
+
+    #[test]
+    fn fuzzy_score_prefers_contiguous_then_early() {
+        // "src" skips nothing in "xsrc" but two characters in "scarce"
+        assert_eq!(fuzzy_rank("xsrc", "src"), (2, 0, 1));
+        assert_eq!(fuzzy_rank("scarce", "src"), (2, 2, 0));
+        assert!(fuzzy_rank("xsrc", "src") < fuzzy_rank("scarce", "src"));
+        // the tightest placement wins over the first one
+        assert_eq!(fuzzy_span("s-r-c src", "src"), (0, 6));
+        assert!(fuzzy_rank("xab", "ab") < fuzzy_rank("xxab", "ab"));
+    }
+
+    #[test]
+    fn compute_matches_orders_by_score_then_list() {
+        let options = opts(&["scarce", "xsrc", "src-a", "my-src", "src-b"]);
+        let flags = flags_with(MatchStrategy::Fuzzy);
+        assert_eq!(compute_matches(&options, "src", &flags), vec![2, 4, 3, 1, 0]);
+    }
+
+    #[test]
+    fn compute_matches_per_strategy() {
+        let options = opts(&["apple", "grape", "pear"]);
+        assert_eq!(compute_matches(&options, "ap", &flags_with(MatchStrategy::Prefix)), vec![0]);
+        assert_eq!(compute_matches(&options, "ap", &flags_with(MatchStrategy::Contains)), vec![0, 1]);
+        assert_eq!(compute_matches(&options, "pe", &flags_with(MatchStrategy::Fuzzy)), vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn compute_matches_empty_filter() {
+        let options = opts(&["a", "b"]);
+        let mut flags = flags_with(MatchStrategy::Prefix);
+        assert_eq!(compute_matches(&options, "", &flags), vec![0, 1]);
+        flags.empty_matches_none = true;
+        assert!(compute_matches(&options, "", &flags).is_empty());
+    }
+
+    #[test]
+    fn compute_matches_case() {
+        let options = opts(&["Apple", "apricot"]);
+        let mut flags = flags_with(MatchStrategy::Prefix);
+        assert_eq!(compute_matches(&options, "AP", &flags), vec![0, 1]);
+        flags.case_sensitive = true;
+        assert_eq!(compute_matches(&options, "Ap", &flags), vec![0]);
+        assert_eq!(compute_matches(&options, "AP", &flags), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn compute_matches_diacritics() {
+        let options = opts(&["café", "cafe\u{301}s", "cafeteria"]);
+        let mut flags = flags_with(MatchStrategy::Prefix);
+        assert_eq!(compute_matches(&options, "cafe", &flags), vec![1, 2]);
+        flags.ignore_diacritics = true;
+        assert_eq!(compute_matches(&options, "cafe", &flags), vec![0, 1, 2]);
+        assert_eq!(compute_matches(&options, "CAFÉ", &flags), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn strip_diacritics_folds_composed_and_decomposed() {
+        assert_eq!(strip_diacritics("Crème brûlée"), "Creme brulee");
+        assert_eq!(strip_diacritics("cafe\u{301}"), "cafe");
+    }
+}
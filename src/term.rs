@@ -18,7 +18,7 @@
 //! with POSIX termios via libc.
 
 use std::mem::MaybeUninit;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
 
 // Async-signal-safe storage for the saved termios.
 //
@@ -30,17 +30,73 @@ use std::sync::atomic::{AtomicBool, Ordering};
 static TERMIOS_SAVED: AtomicBool = AtomicBool::new(false);
 static mut SAVED_TERMIOS: MaybeUninit<libc::termios> = MaybeUninit::uninit();
 
-/// Put the terminal into raw (cbreak) mode with echo off.
-/// Returns the original termios so we can restore it later.
-pub fn init_term(flush: bool) -> libc::termios {
+// The terminal keys are read from: 0, or a descriptor for /dev/tty.  Set
+// once in init_term() alongside the saved termios, for the same reason.
+static INPUT_FD: AtomicI32 = AtomicI32::new(0);
+
+/// The descriptor to read keys from: stdin, or `/dev/tty` when `force`
+/// (`--tty`) is set or stdin is not a terminal (`echo x | grabchars ...`).
+/// Exits 255 if neither is a terminal.
+pub fn open_input(force: bool) -> i32 {
+    if !force && unsafe { libc::isatty(0) } != 0 {
+        return 0;
+    }
+    let fd = unsafe { libc::open(c"/dev/tty".as_ptr(), libc::O_RDWR | libc::O_CLOEXEC) };
+    if fd < 0 {
+        if force {
+            eprintln!("--tty option: cannot open /dev/tty: {}", std::io::Error::last_os_error());
+        } else {
+            eprintln!("grabchars: stdin is not a terminal");
+        }
+        std::process::exit(255);
+    }
+    fd
+}
+
+// Whether bracketed paste is on, so restore turns it off only if it was
+// turned on.  Atomic for the signal handler's restore.
+static PASTE_ON: AtomicBool = AtomicBool::new(false);
+
+/// Ask the terminal for bracketed paste: pasted text arrives between
+/// `ESC [200~` and `ESC [201~`, which `read_key` delivers as one Paste
+/// key.  Sent to stderr only when it is the terminal.
+pub fn enable_paste() {
+    if unsafe { libc::isatty(2) } != 0 {
+        write_stderr(b"\x1b[?2004h");
+        PASTE_ON.store(true, Ordering::Release);
+    }
+}
+
+/// Turn bracketed paste back off.  Plain write(2), as the signal handler
+/// calls this too.
+fn disable_paste() {
+    if PASTE_ON.swap(false, Ordering::AcqRel) {
+        write_stderr(b"\x1b[?2004l");
+    }
+}
+
+fn write_stderr(bytes: &[u8]) {
+    unsafe {
+        libc::write(2, bytes.as_ptr() as *const libc::c_void, bytes.len());
+    }
+}
+
+/// The descriptor `init_term` set up.
+pub fn input_fd() -> i32 {
+    INPUT_FD.load(Ordering::Relaxed)
+}
+
+/// Put the terminal on `fd` (from `open_input`) into raw (cbreak) mode
+/// with echo off.  Returns the original termios so we can restore it later.
+pub fn init_term(fd: i32, flush: bool) -> libc::termios {
     unsafe {
-        if libc::isatty(0) == 0 {
+        if libc::isatty(fd) == 0 {
             eprintln!("grabchars: stdin is not a terminal");
             std::process::exit(255);
         }
 
         let mut orig: libc::termios = std::mem::zeroed();
-        if libc::tcgetattr(0, &mut orig) != 0 {
+        if libc::tcgetattr(fd, &mut orig) != 0 {
             eprintln!("grabchars: tcgetattr failed");
             std::process::exit(255);
         }
@@ -50,46 +106,115 @@ pub fn init_term(flush: bool) -> libc::termios {
         // Use addr_of_mut! to get a raw pointer without creating a reference
         // (required by Rust 2024 static_mut_refs lint).
         std::ptr::addr_of_mut!(SAVED_TERMIOS).write(MaybeUninit::new(orig));
+        INPUT_FD.store(fd, Ordering::Relaxed);
         TERMIOS_SAVED.store(true, Ordering::Release);
 
-        let mut raw = orig;
-
-        // Equivalent to CBREAK + ~ECHO on BSD:
-        // Turn off canonical mode (line buffering) and echo.
-        raw.c_lflag &= !(libc::ICANON | libc::ECHO);
-
-        // Read one character at a time, no timeout
-        raw.c_cc[libc::VMIN] = 1;
-        raw.c_cc[libc::VTIME] = 0;
-
         if flush {
             // TCSAFLUSH: flush input buffer (like BSD TIOCSETP)
-            libc::tcsetattr(0, libc::TCSAFLUSH, &raw);
+            set_raw(fd, &orig, libc::TCSAFLUSH);
         } else {
             // TCSANOW: don't flush (like BSD TIOCSETN)
-            libc::tcsetattr(0, libc::TCSANOW, &raw);
+            set_raw(fd, &orig, libc::TCSANOW);
         }
 
         orig
     }
 }
 
+/// Set `fd` to the raw mode derived from `orig`, applied with `action`.
+fn set_raw(fd: i32, orig: &libc::termios, action: libc::c_int) {
+    let mut raw = *orig;
+
+    // Equivalent to CBREAK + ~ECHO on BSD:
+    // Turn off canonical mode (line buffering) and echo.
+    raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+
+    // Read one character at a time, no timeout
+    raw.c_cc[libc::VMIN] = 1;
+    raw.c_cc[libc::VTIME] = 0;
+
+    unsafe {
+        libc::tcsetattr(fd, action, &raw);
+    }
+}
+
+/// Put `fd` back into the raw mode `init_term` set, without saving the
+/// original again.  Called after a child process has run, since one that
+/// opens the terminal itself can leave it cooked.  Keys typed meanwhile
+/// are kept.
+pub fn apply_raw(fd: i32) {
+    if !TERMIOS_SAVED.load(Ordering::Acquire) {
+        return;
+    }
+    unsafe {
+        let tp = std::ptr::addr_of!(SAVED_TERMIOS) as *const libc::termios;
+        set_raw(fd, &*tp, libc::TCSANOW);
+    }
+}
+
+/// Width of the terminal on `fd` in columns, or 80 if it can't be read.
+pub fn columns(fd: i32) -> usize {
+    let mut ws: libc::winsize = unsafe { std::mem::zeroed() };
+    let ok = unsafe { libc::ioctl(fd, libc::TIOCGWINSZ, &mut ws) } == 0;
+    if ok && ws.ws_col > 0 { ws.ws_col as usize } else { 80 }
+}
+
+/// Color support for `--query-colors`: `none`, `16`, `256`, or `truecolor`.
+/// `$COLORTERM` and `$TERM` decide most cases; otherwise the terminal is
+/// probed for 24-bit color before falling back to what `$TERM` claims.
+pub fn color_support(fd: i32) -> &'static str {
+    let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+    if colorterm == "truecolor" || colorterm == "24bit" {
+        return "truecolor";
+    }
+    let term = std::env::var("TERM").unwrap_or_default();
+    if term.is_empty() || term == "dumb" {
+        return "none";
+    }
+    if term.ends_with("-direct") || probe_truecolor(fd) {
+        return "truecolor";
+    }
+    if term.contains("256color") {
+        return "256";
+    }
+    "16"
+}
+
+/// Set a 24-bit background, ask for the current SGR with DECRQSS, and
+/// reset.  A terminal that really keeps 24-bit color reports the same
+/// components back; others answer with something else or not at all.
+fn probe_truecolor(fd: i32) -> bool {
+    use std::io::Write;
+    let mut stderr = crate::output::tty();
+    let _ = stderr.write_all(b"\x1b[48;2;1;2;3m\x1bP$qm\x1b\\\x1b[m");
+    let _ = stderr.flush();
+    match crate::input::read_dcs_reply(fd, 100) {
+        Some(body) => {
+            let reply = String::from_utf8_lossy(&body);
+            reply.starts_with("1$r") && (reply.contains("1:2:3") || reply.contains("1;2;3"))
+        }
+        None => false,
+    }
+}
+
 /// Restore terminal to original settings.
 pub fn restore_term(orig: &libc::termios) {
+    disable_paste();
     unsafe {
-        libc::tcsetattr(0, libc::TCSAFLUSH, orig);
+        libc::tcsetattr(input_fd(), libc::TCSAFLUSH, orig);
     }
 }
 
 /// Restore from the saved static copy (used in signal handlers
 /// where we can't pass parameters).
 pub fn restore_saved() {
+    disable_paste();
     if TERMIOS_SAVED.load(Ordering::Acquire) {
         unsafe {
             // addr_of! gives a raw pointer without creating a reference;
             // MaybeUninit<T> has the same layout as T, so the cast is valid.
             let tp = std::ptr::addr_of!(SAVED_TERMIOS) as *const libc::termios;
-            libc::tcsetattr(0, libc::TCSAFLUSH, tp);
+            libc::tcsetattr(input_fd(), libc::TCSAFLUSH, tp);
         }
     }
 }
@@ -0,0 +1,176 @@
+// Copyright 2026 Daniel Smith
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Timeout deadlines: a fixed total limit (`-t`) and a resettable idle
+//! limit (`--idle`).
+//!
+//! Both share the one SIGALRM.  The interval timer is armed for whichever
+//! deadline is nearer and re-armed after every keystroke, so when the alarm
+//! fires `TIMED_OUT` is set and `fired()` tells which deadline it was.
+//!
+//! `--timings` rides on the same keystroke hook: once `start_timings()` is
+//! called, `touch()` also counts keys, and the time since the start and the
+//! count go into the JSON output or a line on a file descriptor at exit.
+
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::TIMED_OUT;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum TimeoutKind {
+    Total, // -t: time since start
+    Idle,  // --idle: time since the last keystroke
+}
+
+struct Deadlines {
+    total: Option<Instant>,
+    idle: Option<Duration>,
+    idle_at: Option<Instant>,
+}
+
+// Only touched from the main thread (never from the signal handler), so a
+// Mutex is fine here; the handler itself only stores TIMED_OUT.
+static DEADLINES: Mutex<Deadlines> = Mutex::new(Deadlines { total: None, idle: None, idle_at: None });
+
+/// Install the SIGALRM handler and arm the timer.  No-op if neither limit is set.
+pub fn start(total: Option<Duration>, idle: Option<Duration>) {
+    if total.is_none() && idle.is_none() {
+        return;
+    }
+    let now = Instant::now();
+    {
+        let mut d = DEADLINES.lock().unwrap();
+        d.total = total.map(|t| now + t);
+        d.idle = idle;
+        d.idle_at = idle.map(|i| now + i);
+    }
+    unsafe {
+        // Use sigaction instead of signal() for portable behavior.
+        // signal() varies by platform: macOS sets SA_RESTART (read() resumes
+        // after signal, never returning EINTR), glibc on Linux also sets it.
+        // sigaction with sa_flags=0 guarantees read() returns EINTR on all
+        // POSIX systems (macOS, Linux, WSL).
+        let mut sa: libc::sigaction = std::mem::zeroed();
+        sa.sa_sigaction = alarm_handler as *const () as libc::sighandler_t;
+        sa.sa_flags = 0;
+        libc::sigaction(libc::SIGALRM, &sa, std::ptr::null_mut());
+    }
+    arm();
+}
+
+extern "C" fn alarm_handler(_sig: libc::c_int) {
+    TIMED_OUT.store(true, Ordering::Relaxed);
+}
+
+/// `--timings`: when the read started and how many keys it has seen.
+/// `None` until `start_timings()`, so an unused counter costs nothing.
+static TIMINGS: Mutex<Option<(Instant, usize)>> = Mutex::new(None);
+
+/// `--timings`: fd for the `elapsed_ms=N keystrokes=N` line at exit, or -1.
+static TIMINGS_FD: AtomicI32 = AtomicI32::new(-1);
+
+/// Start the `--timings` clock and counter.  With `fd`, the totals are
+/// written there when the process exits, however it exits.
+pub fn start_timings(fd: Option<i32>) {
+    *TIMINGS.lock().unwrap() = Some((Instant::now(), 0));
+    if let Some(fd) = fd {
+        TIMINGS_FD.store(fd, Ordering::Relaxed);
+        unsafe {
+            libc::atexit(report_timings);
+        }
+    }
+}
+
+/// Milliseconds since `start_timings()` and keys read, or `None` if
+/// `--timings` is off.
+pub fn timings() -> Option<(u128, usize)> {
+    TIMINGS.lock().unwrap().map(|(start, keys)| (start.elapsed().as_millis(), keys))
+}
+
+extern "C" fn report_timings() {
+    let Some((ms, keys)) = timings() else {
+        return;
+    };
+    let line = format!("elapsed_ms={} keystrokes={}\n", ms, keys);
+    unsafe {
+        libc::write(TIMINGS_FD.load(Ordering::Relaxed), line.as_ptr() as *const libc::c_void, line.len());
+    }
+}
+
+/// Record a keystroke: count it for `--timings`, push the idle deadline
+/// out and re-arm.
+pub fn touch() {
+    if let Some((_, keys)) = TIMINGS.lock().unwrap().as_mut() {
+        *keys += 1;
+    }
+    {
+        let mut d = DEADLINES.lock().unwrap();
+        match d.idle {
+            Some(idle) => d.idle_at = Some(Instant::now() + idle),
+            None => return,
+        }
+    }
+    arm();
+}
+
+/// Arm the interval timer for the nearer of the two deadlines.
+fn arm() {
+    let nearest = {
+        let d = DEADLINES.lock().unwrap();
+        match (d.total, d.idle_at) {
+            (Some(t), Some(i)) => t.min(i),
+            (Some(t), None) => t,
+            (None, Some(i)) => i,
+            (None, None) => return,
+        }
+    };
+    let remaining = nearest.saturating_duration_since(Instant::now());
+    if remaining.is_zero() {
+        TIMED_OUT.store(true, Ordering::Relaxed);
+        return;
+    }
+    let it = libc::itimerval {
+        it_interval: libc::timeval { tv_sec: 0, tv_usec: 0 },
+        it_value: libc::timeval {
+            tv_sec: remaining.as_secs() as libc::time_t,
+            tv_usec: remaining.subsec_micros() as libc::suseconds_t,
+        },
+    };
+    unsafe {
+        libc::setitimer(libc::ITIMER_REAL, &it, std::ptr::null_mut());
+    }
+}
+
+/// Which deadline fired, or `None` if no timeout has happened.
+pub fn fired() -> Option<TimeoutKind> {
+    if !TIMED_OUT.load(Ordering::Relaxed) {
+        return None;
+    }
+    let d = DEADLINES.lock().unwrap();
+    match d.total {
+        Some(t) if Instant::now() >= t => Some(TimeoutKind::Total),
+        _ if d.idle.is_some() => Some(TimeoutKind::Idle),
+        _ => Some(TimeoutKind::Total),
+    }
+}
+
+/// Name of the deadline that fired, for JSON output.
+pub fn fired_name() -> Option<&'static str> {
+    fired().map(|k| match k {
+        TimeoutKind::Total => "total",
+        TimeoutKind::Idle => "idle",
+    })
+}
@@ -62,4 +62,39 @@ show_command "-h"
 actual_exit=$?
 check_exit "$actual_exit" "255" && pass || fail "exit should be 255 for -h"
 
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "anykey: an arrow key satisfies the read"
+echo    "  Nothing is echoed, and none of the arrow's bytes are left over."
+instruct "Press the Up arrow"
+show_command "anykey"
+echo
+actual_out=$("$GRABCHARS" anykey 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "" && check_exit "$actual_exit" "0" && pass || fail "expected no output with exit 0"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "piped stdin: keys are read from /dev/tty"
+echo    "  stdin is a pipe here, so grabchars opens the terminal itself."
+instruct "Type 'k'"
+show_command "echo foo | grabchars"
+echo
+actual_out=$(echo foo | "$GRABCHARS" 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "k" && check_exit "$actual_exit" "1" && pass || fail "expected 'k' with exit 1"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "--no-silent: switches off a -s set in the config file"
+instruct "Type 'x'"
+show_command "--no-silent   (with GRABCHARS_CONFIG holding 's = true')"
+echo
+cfg=$(mktemp)
+echo 's = true' > "$cfg"
+actual_out=$(GRABCHARS_CONFIG="$cfg" "$GRABCHARS" --no-silent 2>/dev/tty)
+actual_exit=$?
+rm -f "$cfg"
+echo
+check_output "$actual_out" "x" && check_exit "$actual_exit" "1" && pass || fail "expected 'x' with exit 1"
+
 print_summary
@@ -62,4 +62,15 @@ actual_exit=$?
 echo
 check_output "$actual_out" "xyz" && check_exit "$actual_exit" "3" && pass || fail "expected 'xyz' with exit 3"
 
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "-n3: a paste fills only the remaining count"
+echo    "  Copy the word 'hello' from this line first."
+instruct "Paste 'hello' (do not type it)"
+show_command '-n3'
+echo
+actual_out=$("$GRABCHARS" -n3 -q "  Paste here: " 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "hel" && check_exit "$actual_exit" "3" && pass || fail "expected 'hel' with exit 3"
+
 print_summary
@@ -65,4 +65,26 @@ actual_exit=$?
 echo
 check_output "$actual_out" "gumby" && check_exit "$actual_exit" "5" && pass || fail "expected 'gumby' with exit 5"
 
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "--edit-default without -n: typing extends the default"
+echo    "  The default is prefilled and the line is unbounded, so typing adds to it."
+instruct "Type ' world', then press Enter"
+show_command "-dhello --edit-default"
+echo
+actual_out=$("$GRABCHARS" -dhello --edit-default 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "hello world" && check_exit "$actual_exit" "11" && pass || fail "expected 'hello world' with exit 11"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "--edit-default: a timeout keeps the edits"
+echo    "  An edited default is returned as it stands when time runs out."
+instruct "Press Backspace twice, then wait 3 seconds"
+show_command "-dhello --edit-default -t3"
+echo
+actual_out=$("$GRABCHARS" -dhello --edit-default -t3 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "hel" && check_exit "$actual_exit" "3" && pass || fail "expected 'hel' with exit 3"
+
 print_summary
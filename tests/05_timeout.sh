@@ -85,4 +85,87 @@ echo
 check_output "$actual_out" "" "stdout (should be empty)"
 check_exit "$actual_exit" "254" && pass || fail "expected exit 254"
 
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "select -t2 --timeout-select=first: timeout returns the first match"
+echo    "  With no -d, --timeout-select=first picks the first match on timeout."
+echo    "  Output: 'red'. Exit code = 0 (index of 'red')."
+instruct "Do NOT type anything — let it time out"
+show_command 'select "red,green,blue" -t2 --timeout-select=first'
+echo
+watch_note "timing out in 2 seconds..."
+actual_out=$("$GRABCHARS" select "red,green,blue" -q "  Color (2s): " -t2 --timeout-select=first 2>/dev/null)
+actual_exit=$?
+echo
+check_output "$actual_out" "red" && check_exit "$actual_exit" "0" && pass || fail "expected 'red' with exit 0"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "select -t3 --timeout-select=current: timeout returns the highlighted match"
+echo    "  --timeout-select=current returns whatever is highlighted when time runs out."
+echo    "  Output: 'blue'. Exit code = 2 (index of 'blue')."
+instruct "Press Down twice (highlight 'blue'), then wait"
+show_command 'select "red,green,blue" -t3 --timeout-select=current'
+echo
+watch_note "timing out in 3 seconds..."
+actual_out=$("$GRABCHARS" select "red,green,blue" -q "  Color (3s): " -t3 --timeout-select=current 2>/dev/null)
+actual_exit=$?
+echo
+check_output "$actual_out" "blue" && check_exit "$actual_exit" "2" && pass || fail "expected 'blue' with exit 2"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "--idle=2 with -t10: idle deadline fires first"
+echo    "  --idle restarts on every keystroke; -t is a fixed total limit."
+echo    "  Type one character, then stop. Two seconds later the idle timer fires."
+echo    "  JSON timeout_kind = \"idle\". Exit code = 254."
+instruct "Type 'a', then wait"
+show_command '-J -n5 -t10 --idle=2'
+echo
+actual_out=$("$GRABCHARS" -J -n5 -t10 --idle=2 -q "  Type 'a' then wait: " 2>/dev/null)
+actual_exit=$?
+echo
+check_output_contains "$actual_out" '"timeout_kind":"idle"' \
+  && check_exit "$actual_exit" "254" && pass || fail "expected idle timeout with exit 254"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "-t0.5: fractional timeout"
+echo    "  Half a second is too short to react to. Exit code = 254."
+instruct "Do NOT type anything"
+show_command '-t0.5'
+echo
+watch_note "timing out in half a second..."
+actual_out=$("$GRABCHARS" -q "  Waiting (timeout in 0.5s): " -t0.5 2>/dev/null)
+actual_exit=$?
+echo
+check_output "$actual_out" "" "stdout (should be empty)"
+check_exit "$actual_exit" "254" && pass || fail "expected exit 254 on timeout"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "select -t3 --timeout-dump: timeout lists the matches"
+echo    "  The matches for the filter left on screen go to fd 3 with their"
+echo    "  indices, even with -d. Exit code = 253."
+instruct "Type 'b', then wait"
+show_command 'select "apple,banana,blueberry" -dapple -t3 --timeout-dump=3 3>file'
+echo
+_tmp_dump=$(mktemp)
+actual_out=$("$GRABCHARS" select "apple,banana,blueberry" -q "  Fruit (3s): " -dapple -t3 --timeout-dump=3 2>/dev/null 3>"$_tmp_dump")
+actual_exit=$?
+echo
+dumped=$(tr '\t\n' ':,' < "$_tmp_dump")
+rm -f "$_tmp_dump"
+check_output "$actual_out" "" "stdout (should be empty)"
+check_exit "$actual_exit" "253" && check_output "$dumped" "1:banana,2:blueberry," "dump" \
+    && pass || fail "expected exit 253 and the two b matches in the dump"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "select --confirm -t2: a timeout never returns a marked option"
+echo    "  -d and --timeout-select=current both name '!!delete', which needs a"
+echo    "  second Enter, so the timeout falls back to a plain timeout. Exit code = 254."
+instruct "Do NOT type anything"
+show_command 'select --confirm -ddelete -t2 --timeout-select=current "keep,!!delete"'
+echo
+actual_out=$("$GRABCHARS" select --confirm -ddelete -t2 --timeout-select=current "keep,!!delete" -q "  Action (2s): " 2>/dev/null)
+actual_exit=$?
+echo
+check_output "$actual_out" "" "stdout (should be empty)"
+check_exit "$actual_exit" "254" && pass || fail "expected exit 254 with nothing chosen"
+
 print_summary
@@ -103,4 +103,42 @@ else
     fail "expected -Z0 stderr ($len_without bytes) to be shorter than default ($len_with bytes)"
 fi
 
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "--echo-fd copies the terminal output to another descriptor"
+instruct "Type 'hi', then press Enter"
+show_command "--echo-fd=3 -q 'Name: ' -n 5 -r 3>file"
+echo
+_tmp_echo=$(mktemp)
+actual_out=$("$GRABCHARS" --echo-fd=3 -q "Name: " -n 5 -r 2>/dev/tty 3>"$_tmp_echo")
+actual_exit=$?
+echo
+recorded=$(cat "$_tmp_echo")
+rm -f "$_tmp_echo"
+check_output "$actual_out" "hi" && check_exit "$actual_exit" "2" \
+    && check_output_contains "$recorded" "Name: " "recording" && pass || fail "expected 'hi' and a recording of the prompt"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "--outcome-file writes a key=value summary"
+instruct "Type 'hi', then press Enter"
+show_command "--outcome-file=file -n 5 -r"
+echo
+_tmp_outcome=$(mktemp)
+actual_out=$("$GRABCHARS" --outcome-file="$_tmp_outcome" -n 5 -r 2>/dev/tty)
+actual_exit=$?
+echo
+recorded=$(. "$_tmp_outcome"; echo "$value $status $count")
+rm -f "$_tmp_outcome"
+check_output "$actual_out" "hi" && check_exit "$actual_exit" "2" \
+    && check_output "$recorded" "hi ok 2" "outcome file" && pass || fail "expected 'hi' and an outcome file with status ok"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "--post=title: an apostrophe does not start a new word"
+instruct "Type \"it's ok\", then press Enter"
+show_command "--post=title -n 10 -r"
+echo
+actual_out=$("$GRABCHARS" --post=title -n 10 -r 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "It's Ok" && check_exit "$actual_exit" "7" && pass || fail "expected \"It's Ok\" with exit 7"
+
 print_summary
@@ -43,6 +43,70 @@ actual_exit=$?
 echo
 check_output "$actual_out" "xz" && check_exit "$actual_exit" "2" && pass || fail "expected 'xz' with exit 2"
 
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "-n1 without -E: no editing — Backspace is the one character read"
+echo    "  A single-key read has editing off unless -E/-E1 is given."
+instruct "Press Backspace — grabchars should exit at once (1 char received)"
+show_command "-n1"
+echo
+actual_out=$("$GRABCHARS" -n1 2>/dev/tty)
+actual_exit=$?
+echo
+echo    "  Exit code: $actual_exit  (expected 1)"
+check_exit "$actual_exit" "1" && pass || fail "expected exit 1 (Backspace read as a character)"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "-n1 -E1: editing forced on for a single-key read"
+echo    "  With -E1, Backspace is an editing key even for -n1, so it is not read."
+instruct "Press Backspace, then 'q'  →  result should be 'q'"
+show_command "-n1 -E1"
+echo
+actual_out=$("$GRABCHARS" -n1 -E1 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "q" && check_exit "$actual_exit" "1" && pass || fail "expected 'q' with exit 1"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "-n2 without -E: editing is on by default"
+instruct "Type 'a', Backspace, 'b', 'c'  →  result should be 'bc'"
+show_command "-n2"
+echo
+actual_out=$("$GRABCHARS" -n2 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "bc" && check_exit "$actual_exit" "2" && pass || fail "expected 'bc' with exit 2"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "-n1 -E0: editing off, same as the -n1 default"
+instruct "Type 'z'  →  result should be 'z'"
+show_command "-n1 -E0"
+echo
+actual_out=$("$GRABCHARS" -n1 -E0 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "z" && check_exit "$actual_exit" "1" && pass || fail "expected 'z' with exit 1"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "-n2 -E0: editing off even though -n > 1"
+echo    "  The explicit -E0 wins over the -n2 default; Backspace is the 0x7f byte."
+instruct "Type 'a', then Backspace  →  result should be '61 7f'"
+show_command "-n2 -E0 --hex-output"
+echo
+actual_out=$("$GRABCHARS" -n2 -E0 --hex-output 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "61 7f" && check_exit "$actual_exit" "2" && pass || fail "expected '61 7f' with exit 2"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "-n2 -E1: editing on, same as the -n2 default"
+instruct "Type 'a', Backspace, 'b', 'c'  →  result should be 'bc'"
+show_command "-n2 -E1"
+echo
+actual_out=$("$GRABCHARS" -n2 -E1 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "bc" && check_exit "$actual_exit" "2" && pass || fail "expected 'bc' with exit 2"
+
 # ─────────────────────────────────────────────────────────────────────────────
 test_start "cursor movement: left/right arrows reposition within buffer"
 echo    "  In edit mode with -n4, use Left arrow to back up, then type to insert."
@@ -79,4 +143,171 @@ actual_exit=$?
 echo
 check_output "$actual_out" "" "stdout (should be empty)" && check_exit "$actual_exit" "0" && pass || fail "expected empty with exit 0"
 
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "quoted insert (Ctrl-V)"
+echo    "  Ctrl-V inserts the next key literally instead of running its editing command."
+instruct "Type 'a', Ctrl-V, Tab, 'b', then Enter (-r mode)"
+echo    "  Result should be 'a<TAB>b' (3 characters)"
+show_command "-n10 -r"
+echo
+actual_out=$("$GRABCHARS" -n10 -r 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" $'a\tb' && check_exit "$actual_exit" "3" && pass || fail "expected 'a<TAB>b' with exit 3"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "-V with --reprompt-on-invalid: fix the input instead of starting over"
+echo    "  -V '[a-z]+[0-9]' requires letters followed by one digit."
+echo    "  'abc' fails: '(invalid)' is shown and the buffer is kept for editing."
+instruct "Type 'abc' (see (invalid)), then Backspace, then '1'"
+echo    "  Result should be 'ab1'"
+show_command "-n3 -V '[a-z]+[0-9]' --reprompt-on-invalid"
+echo
+actual_out=$("$GRABCHARS" -n3 -V '[a-z]+[0-9]' --reprompt-on-invalid 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "ab1" && check_exit "$actual_exit" "3" && pass || fail "expected 'ab1' with exit 3"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "--reprompt-text: a failed attempt retries on a new line under the prompt"
+echo    "  'abc' fails; 'Code: abc' is drawn again on the next line."
+instruct "Type 'abc' (see the new line), then Backspace, then '1'"
+show_command "-n3 -V '[a-z]+[0-9]' --reprompt-on-invalid --reprompt-text -q 'Code: '"
+echo
+actual_out=$("$GRABCHARS" -n3 -V '[a-z]+[0-9]' --reprompt-on-invalid --reprompt-text -q 'Code: ' 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "ab1" && check_exit "$actual_exit" "3" && pass || fail "expected 'ab1' with exit 3"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "--graphemes: an emoji with a skin-tone modifier is one character"
+echo    "  Without --graphemes, 👍🏽 is 8 bytes; with it, one key."
+instruct "Type or paste 👍🏽 (thumbs up, medium skin tone)"
+show_command "-n1 --graphemes"
+echo
+actual_out=$("$GRABCHARS" -n1 --graphemes 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "👍🏽" && check_exit "$actual_exit" "1" && pass || fail "expected '👍🏽' with exit 1"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "--paragraph: Enter on an empty line ends a multi-line read"
+instruct "Type 'ab', Enter, 'cx', Backspace, 'd', Enter, then Enter again"
+echo    "  Result should be two lines, 'ab' and 'cd'"
+show_command "--paragraph"
+echo
+actual_out=$("$GRABCHARS" --paragraph 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" $'ab\ncd' && check_exit "$actual_exit" "5" && pass || fail "expected 'ab<NL>cd' with exit 5"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "--field-width: input is drawn in a padded, scrolling field"
+instruct "Type 'abcdefg', press Home, then Enter"
+echo    "  The field stays 4 columns wide ('____' while empty) and scrolls as you type"
+show_command "-n 10 -r --field-width=4:_"
+echo
+actual_out=$("$GRABCHARS" -n 10 -r --field-width=4:_ 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "abcdefg" && check_exit "$actual_exit" "7" && pass || fail "expected 'abcdefg' with exit 7"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "--disable-edit-keys: Ctrl-U is captured instead of killing the line"
+instruct "Type 'a', Ctrl-U, 'b', then Enter"
+echo    "  Result should be 'a', byte 0x15, 'b'"
+show_command "-n 5 -r --disable-edit-keys=ctrl-u"
+echo
+actual_out=$("$GRABCHARS" -n 5 -r --disable-edit-keys=ctrl-u 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" $'a\x15b' && check_exit "$actual_exit" "3" && pass || fail "expected 'a<Ctrl-U>b' with exit 3"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "--range: keys that would leave the range are refused"
+instruct "Type '1', '5', '0' (bell), then Enter"
+echo    "  150 is over 100, so the '0' is refused; result should be '15'"
+show_command "--range=1..100"
+echo
+actual_out=$("$GRABCHARS" --range=1..100 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "15" && check_exit "$actual_exit" "2" && pass || fail "expected '15' with exit 2"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "--col: input starts at a fixed column"
+echo    "  The input starts in column 7, right after 'Code: ', and Home goes back there."
+instruct "Type 'abc', press Home, type 'x', then press Enter"
+show_command "printf 'Code: ' >&2; grabchars --col=7 -n 5 -r"
+echo
+printf 'Code: ' >/dev/tty
+actual_out=$("$GRABCHARS" --col=7 -n 5 -r 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "xabc" && check_exit "$actual_exit" "4" && pass || fail "expected 'xabc' with exit 4"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "--tab=complete: Tab fills in the rest of the -d default"
+instruct "Type 'ne', press Tab, then press Enter"
+show_command "-n20 -r -dnew-york --tab=complete"
+echo
+actual_out=$("$GRABCHARS" -n20 -r -dnew-york --tab=complete 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "new-york" && check_exit "$actual_exit" "8" && pass || fail "expected 'new-york' with exit 8"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "--tab=submit: Tab ends the read"
+instruct "Type 'ab', then press Tab"
+show_command "-n5 --tab=submit"
+echo
+actual_out=$("$GRABCHARS" -n5 --tab=submit 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "ab" && check_exit "$actual_exit" "2" && pass || fail "expected 'ab' with exit 2"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "--disable-edit-keys: repeating the option adds to the list"
+instruct "Type 'a', Ctrl-U, 'b', Ctrl-W, 'c', then Enter"
+echo    "  Result should be 'a', byte 0x15, 'b', byte 0x17, 'c'"
+show_command "-n 6 -r --disable-edit-keys=ctrl-u --disable-edit-keys=ctrl-w"
+echo
+actual_out=$("$GRABCHARS" -n 6 -r --disable-edit-keys=ctrl-u --disable-edit-keys=ctrl-w 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" $'a\x15b\x17c' && check_exit "$actual_exit" "5" && pass || fail "expected 'a<Ctrl-U>b<Ctrl-W>c' with exit 5"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "Ctrl-V Escape: drawn as ^[ and the line still redraws"
+instruct "Type 'a', Ctrl-V, Escape, 'c', Ctrl-A (Home), 'x', then Enter"
+echo    "  The line should read 'xa^[c' with the cursor after the 'x' before Enter;"
+echo    "  the Escape byte is kept in the result"
+show_command "-n 10 -r"
+echo
+actual_out=$("$GRABCHARS" -n 10 -r 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" $'xa\x1bc' && check_exit "$actual_exit" "4" && pass || fail "expected 'xa<ESC>c' with exit 4"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "--strip on a line read: trailing characters from the set are trimmed"
+echo    "  -r with a large -n reads one line; --strip trims it before output."
+instruct "Type 'hi.. ' (two dots, a space), then Enter"
+show_command "-n 20 -r \"--strip= .\""
+echo
+actual_out=$("$GRABCHARS" -n 20 -r "--strip= ." 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "hi" && check_exit "$actual_exit" "2" && pass || fail "expected 'hi' with exit 2"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "--strip on a line read: a quoted CR is dropped by the default set"
+instruct "Type 'ok', Ctrl-V, Enter (a literal CR), then Enter"
+show_command "-n 20 -r --strip"
+echo
+actual_out=$("$GRABCHARS" -n 20 -r --strip 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "ok" && check_exit "$actual_exit" "2" && pass || fail "expected 'ok' with exit 2"
+
 print_summary
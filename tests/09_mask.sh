@@ -104,4 +104,26 @@ echo
 check_output "$actual_out" "" "stdout (should be empty)"
 check_exit "$actual_exit" "255" && pass || fail "expected exit 255 on Escape"
 
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "-m repeat count: 'c{2,4}' -r"
+echo    "  Mask 'c{2,4}': two to four letters. Enter accepts from the second on."
+instruct "Type 'a', 'b', '1' (rejected), then press Enter"
+show_command "-m 'c{2,4}' -r -e -b"
+echo
+actual_out=$("$GRABCHARS" -m'c{2,4}' -r -e -b)
+actual_exit=$?
+echo
+check_output "$actual_out" "ab" && check_exit "$actual_exit" "2" && pass || fail "expected 'ab' with exit 2"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "--literal-color: inserted literals in another color"
+echo    "  The '-' appears in grey as you type; the output is unchanged."
+instruct "Type '1234'"
+show_command "-m 'nn-nn' --literal-color=244 -e -b"
+echo
+actual_out=$("$GRABCHARS" -m'nn-nn' --literal-color=244 -e -b)
+actual_exit=$?
+echo
+check_output "$actual_out" "12-34" && check_exit "$actual_exit" "5" && pass || fail "expected '12-34' with exit 5"
+
 print_summary
@@ -133,6 +133,45 @@ actual_exit=$?
 echo
 check_output "$actual_out" "new york" && check_exit "$actual_exit" "1" && pass || fail "expected 'new york' with exit 1"
 
+# ─────────────────────────────────────────────────────────────────────────────
+RANK_OPTS="c-o-n-f,reconfigure,account-config,config-backup"
+test_start "select -Ff: fuzzy ranking — prefix match comes first"
+echo    "  All four options match 'co'. Fuzzy results are ranked: options that"
+echo    "  start with the filter, then a contiguous match at a word start"
+echo    "  ('account-config'), then everything else — so 'config-backup' (last"
+echo    "  in the list) is highlighted first."
+instruct "Type 'c', 'o', then press Enter"
+show_command "select -Ff \"$RANK_OPTS\""
+echo
+actual_out=$("$GRABCHARS" select -Ff "$RANK_OPTS" 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "config-backup" && check_exit "$actual_exit" "3" && pass || fail "expected 'config-backup' with exit 3"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "select -Ff: fuzzy ranking — word-start match comes second"
+echo    "  Same list: one Down arrow moves from 'config-backup' to 'account-config',"
+echo    "  ahead of the scattered matches 'c-o-n-f' and 'reconfigure'."
+instruct "Type 'c', 'o', press Down once, then press Enter"
+show_command "select -Ff \"$RANK_OPTS\""
+echo
+actual_out=$("$GRABCHARS" select -Ff "$RANK_OPTS" 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "account-config" && check_exit "$actual_exit" "2" && pass || fail "expected 'account-config' with exit 2"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "select -Ff: fuzzy ranking — tighter scattered match first"
+echo    "  Same list: 'reconfigure' has 'co' together, 'c-o-n-f' skips a"
+echo    "  character, so two Down arrows land on 'reconfigure'."
+instruct "Type 'c', 'o', press Down twice, then press Enter"
+show_command "select -Ff \"$RANK_OPTS\""
+echo
+actual_out=$("$GRABCHARS" select -Ff "$RANK_OPTS" 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "reconfigure" && check_exit "$actual_exit" "1" && pass || fail "expected 'reconfigure' with exit 1"
+
 # ─────────────────────────────────────────────────────────────────────────────
 test_start "select -Fc: contains — 'york' isolates 'new york'"
 echo    "  Contains: the typed text must appear as a contiguous substring anywhere."
@@ -145,6 +184,16 @@ actual_exit=$?
 echo
 check_output "$actual_out" "new york" && check_exit "$actual_exit" "1" && pass || fail "expected 'new york' with exit 1"
 
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "select -I: the same substring filter as -Fc"
+instruct "Type 'e', 'r', 'r', then press Enter"
+show_command "select -I \"apple,parse_error,cherry\""
+echo
+actual_out=$("$GRABCHARS" select -I "apple,parse_error,cherry" 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "parse_error" && check_exit "$actual_exit" "1" && pass || fail "expected 'parse_error' with exit 1"
+
 # ─────────────────────────────────────────────────────────────────────────────
 test_start "select -Fc: contains — 'ren' isolates 'renew annually'"
 echo    "  'ren' appears only at the start of 'renew annually' in this list."
@@ -156,4 +205,187 @@ actual_exit=$?
 echo
 check_output "$actual_out" "renew annually" && check_exit "$actual_exit" "3" && pass || fail "expected 'renew annually' with exit 3"
 
+# ─────────────────────────────────────────────────────────────────────────────
+ACCENT_OPTS="tea,café,mañana,über"
+test_start "select --ignore-diacritics: é → e"
+echo    "  Accents are ignored on both sides, so 'cafe' matches 'café'."
+instruct "Type 'cafe', then press Enter"
+show_command "select --ignore-diacritics \"$ACCENT_OPTS\""
+echo
+actual_out=$("$GRABCHARS" select --ignore-diacritics "$ACCENT_OPTS" 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "café" && check_exit "$actual_exit" "1" && pass || fail "expected 'café' with exit 1"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "select --ignore-diacritics: ñ → n"
+echo    "  Accents are ignored on both sides, so 'man' matches 'mañana'."
+instruct "Type 'man', then press Enter"
+show_command "select --ignore-diacritics \"$ACCENT_OPTS\""
+echo
+actual_out=$("$GRABCHARS" select --ignore-diacritics "$ACCENT_OPTS" 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "mañana" && check_exit "$actual_exit" "2" && pass || fail "expected 'mañana' with exit 2"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "select --ignore-diacritics: ü → u"
+echo    "  Accents are ignored on both sides, so 'ub' matches 'über'."
+instruct "Type 'ub', then press Enter"
+show_command "select --ignore-diacritics \"$ACCENT_OPTS\""
+echo
+actual_out=$("$GRABCHARS" select --ignore-diacritics "$ACCENT_OPTS" 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "über" && check_exit "$actual_exit" "3" && pass || fail "expected 'über' with exit 3"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "select --initials: a duplicate initial takes the next letter"
+echo    "  Save is (S)ave, so Sort falls through to S(o)rt."
+instruct "Press 'o' (no Enter)"
+show_command "select --initials \"Save,Quit,Sort\""
+echo
+actual_out=$("$GRABCHARS" select --initials "Save,Quit,Sort" 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "Sort" && check_exit "$actual_exit" "2" && pass || fail "expected 'Sort' with exit 2"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "select --require-unique: Enter waits for a single match"
+echo    "  With two matches for 'b', Enter only rings the bell."
+instruct "Type 'b', press Enter (bell), then type 'a' and press Enter"
+show_command "select --require-unique \"apple,banana,blueberry\""
+echo
+actual_out=$("$GRABCHARS" select --require-unique "apple,banana,blueberry" 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "banana" && check_exit "$actual_exit" "1" && pass || fail "expected 'banana' with exit 1"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "select --json-file: the label is shown, the value is printed"
+echo    "  Options come from a JSON file; 'Ireland' returns 'eu-west-1'."
+JSON_OPTS=$(mktemp)
+printf '[{"value":"us-east-1","label":"Virginia"},{"value":"eu-west-1","label":"Ireland"}]' > "$JSON_OPTS"
+instruct "Type 'ir', then press Enter"
+show_command "select --json-file=$JSON_OPTS"
+echo
+actual_out=$("$GRABCHARS" select --json-file="$JSON_OPTS" 2>/dev/tty)
+actual_exit=$?
+rm -f "$JSON_OPTS"
+echo
+check_output "$actual_out" "eu-west-1" && check_exit "$actual_exit" "1" && pass || fail "expected 'eu-west-1' with exit 1"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "select --json-file: a high surrogate needs a low one after it"
+watch_note "fully automated — no keystrokes needed"
+JSON_OPTS=$(mktemp)
+printf '["a\\ud83d\\u0041"]' > "$JSON_OPTS"
+show_command "select --json-file=$JSON_OPTS"
+actual_out=$("$GRABCHARS" select --json-file="$JSON_OPTS" 2>&1 </dev/null)
+actual_exit=$?
+rm -f "$JSON_OPTS"
+check_output_contains "$actual_out" "bad surrogate pair" "error message"
+check_exit "$actual_exit" "255" && pass || fail "expected exit 255 for a bad surrogate pair"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "select --exit-map: the chosen option sets the exit code"
+echo    "  'retry' is index 1 but is mapped to exit 7."
+instruct "Type 'r', then press Enter"
+show_command "select --exit-map 'quit=0,retry=7,abort=2' \"quit,retry,abort\""
+echo
+actual_out=$("$GRABCHARS" select --exit-map 'quit=0,retry=7,abort=2' "quit,retry,abort" 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "retry" && check_exit "$actual_exit" "7" && pass || fail "expected 'retry' with exit 7"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "select --no-match-text / --no-match-color: custom no-match line"
+echo    "  Typing 'z' shows 'nothing found' in red (plain under NO_COLOR)."
+instruct "Type 'z' (see the red line), Backspace, then press Enter"
+show_command "select --no-match-text='nothing found' --no-match-color \"apple,banana\""
+echo
+actual_out=$("$GRABCHARS" select --no-match-text='nothing found' --no-match-color "apple,banana" 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "apple" && check_exit "$actual_exit" "0" && pass || fail "expected 'apple' with exit 0"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "select --accept-keys=f2: a function key confirms the highlight"
+instruct "Type 'b', then press F2"
+show_command "select --accept-keys=f2 \"apple,banana\""
+echo
+actual_out=$("$GRABCHARS" select --accept-keys=f2 "apple,banana" 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "banana" && check_exit "$actual_exit" "1" && pass || fail "expected 'banana' with exit 1"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "select --no-filter: keys jump by first letter"
+instruct "Press 'b' twice, then press Enter"
+show_command "select --no-filter \"apple,banana,blueberry,cherry\""
+echo
+actual_out=$("$GRABCHARS" select --no-filter "apple,banana,blueberry,cherry" 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "blueberry" && check_exit "$actual_exit" "2" && pass || fail "expected 'blueberry' with exit 2"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "select --no-filter: rejected with --empty=none"
+watch_note "fully automated — no keystrokes needed"
+show_command "select --no-filter --empty=none \"apple,banana\""
+actual_out=$("$GRABCHARS" select --no-filter --empty=none "apple,banana" 2>&1 </dev/null)
+actual_exit=$?
+check_output_contains "$actual_out" "--empty=none" "error message"
+check_exit "$actual_exit" "255" && pass || fail "expected exit 255 for --no-filter with --empty=none"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "select --confirm: a marked option needs a second Enter"
+echo    "  The first Enter shows 'press Enter again to confirm'; Down/Up start over."
+instruct "Type 'd', press Enter, Up, Down, then Enter twice"
+show_command "select --confirm \"keep,!!delete\""
+echo
+actual_out=$("$GRABCHARS" select --confirm "keep,!!delete" 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "delete" && check_exit "$actual_exit" "1" && pass || fail "expected 'delete' with exit 1"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "select --print-index: print the index instead of the option"
+instruct "Type 'ch', then press Enter"
+show_command "select --print-index \"apple,banana,cherry\""
+echo
+actual_out=$("$GRABCHARS" select --print-index "apple,banana,cherry" 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "2" && check_exit "$actual_exit" "2" && pass || fail "expected '2' with exit 2"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "select: a newline-separated list is trimmed like a comma one"
+watch_note "fully automated — no keystrokes needed"
+show_command "select --list-matches= \"\$(printf 'apple\\n banana ')\""
+actual_out=$("$GRABCHARS" select --list-matches= "$(printf 'apple\n banana ')" 2>/dev/null </dev/null)
+actual_exit=$?
+check_output "$actual_out" "$(printf 'apple\nbanana')" && check_exit "$actual_exit" "0" && pass || fail "expected 'apple' and 'banana' without spaces"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "select --tree: a timeout follows a full-path -d to its leaf"
+instruct "Press nothing — wait 2 seconds"
+show_command "select --tree -t2 -da/b/d \"a/b/c,a/b/d,x\""
+echo
+actual_out=$("$GRABCHARS" select --tree -t2 -da/b/d "a/b/c,a/b/d,x" 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "a/b/d" && check_exit "$actual_exit" "1" && pass || fail "expected 'a/b/d' with exit 1"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "select --tree --enter-empty=default: Enter follows -d down the tree"
+echo    "  Each Enter on an empty filter picks the -d segment at that level."
+instruct "Press Enter three times"
+show_command "select --tree --enter-empty=default -da/b/d \"a/b/c,a/b/d,x\""
+echo
+actual_out=$("$GRABCHARS" select --tree --enter-empty=default -da/b/d "a/b/c,a/b/d,x" 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "a/b/d" && check_exit "$actual_exit" "1" && pass || fail "expected 'a/b/d' with exit 1"
+
 print_summary
@@ -188,4 +188,26 @@ actual_exit=$?
 echo
 check_output "$actual_out" "renew annually" && check_exit "$actual_exit" "3" && pass || fail "expected 'renew annually' with exit 3"
 
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "select-lr: Ctrl-G toggles to the compact view, keeping the highlight"
+echo    "  Ctrl-G switches layouts; afterwards Left/Right move the filter cursor."
+instruct "Press Right arrow once (on 'no'), Ctrl-G, Right arrow again, then Enter"
+show_command "select-lr $OPTS"
+echo
+actual_out=$("$GRABCHARS" select-lr "$OPTS" 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "no" && check_exit "$actual_exit" "1" && pass || fail "expected 'no' with exit 1"
+
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "select-lr: Alt-Left moves the filter cursor to fix a typo"
+echo    "  'ac' matches nothing; Alt-Left puts the cursor between the letters."
+instruct "Type 'ac', press Alt-Left, type 'b', then Enter"
+show_command "select-lr \"abc,xyz,ab\""
+echo
+actual_out=$("$GRABCHARS" select-lr "abc,xyz,ab" 2>/dev/tty)
+actual_exit=$?
+echo
+check_output "$actual_out" "abc" && check_exit "$actual_exit" "0" && pass || fail "expected 'abc' with exit 0"
+
 print_summary
@@ -290,4 +290,18 @@ check_output "$status" "timeout" "status" \
   && check_exit "$actual_exit" "254" \
   && pass || fail "unexpected JSON for timeout without default"
 
+# ─────────────────────────────────────────────────────────────────────────────
+test_start "-J with --prefix/--suffix: the value stays the raw result"
+instruct "Type 'ab', then press Enter"
+show_command "-J -n3 -r --prefix='<' --suffix='>'"
+echo
+actual_out=$("$GRABCHARS" -J -n3 -r --prefix='<' --suffix='>' 2>/dev/tty)
+actual_exit=$?
+echo
+echo "  JSON: $actual_out"
+value=$(json_field "$actual_out" "value")
+check_output "$value" "ab" "value" \
+  && check_exit "$actual_exit" "2" \
+  && pass || fail "expected value 'ab' without the prefix and suffix"
+
 print_summary